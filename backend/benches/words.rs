@@ -0,0 +1,123 @@
+//! Compares per-round word lookups against SQLite with the in-memory index.
+//!
+//! Runs against a generated 40k-word dictionary by default. Set `WORDS_DB` to a
+//! database imported with `tools/seed` to benchmark the real dataset:
+//!
+//! ```bash
+//! WORDS_DB=sqlite:yomitaisen.db cargo bench --bench words
+//! ```
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use sqlx::SqlitePool;
+use tokio::runtime::Runtime;
+use yomitaisen::WordRepository;
+
+const DATASET_SIZE: usize = 40_000;
+
+async fn setup_pool() -> SqlitePool {
+    if let Ok(url) = std::env::var("WORDS_DB") {
+        return SqlitePool::connect(&url)
+            .await
+            .expect("Failed to open WORDS_DB");
+    }
+
+    let pool = SqlitePool::connect(":memory:").await.unwrap();
+    sqlx::migrate!().run(&pool).await.unwrap();
+
+    let mut tx = pool.begin().await.unwrap();
+    for i in 0..DATASET_SIZE {
+        let (kanji, reading) = synthetic_word(i);
+        sqlx::query(
+            "INSERT OR IGNORE INTO words (kanji, reading, frequency_rank) VALUES (?, ?, ?)",
+        )
+        .bind(kanji)
+        .bind(reading)
+        .bind(i as i64 + 100)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    }
+    tx.commit().await.unwrap();
+    pool
+}
+
+/// Two-kanji compound and a kana reading, unique per `i`.
+/// Every tenth kanji gets a second reading, like real homographs.
+fn synthetic_word(i: usize) -> (String, String) {
+    let base = i / 10 * 9 + (i % 10).min(8);
+    let kanji: String = [base / 200, base % 200]
+        .iter()
+        .map(|&n| char::from_u32(0x4E00 + n as u32).unwrap())
+        .collect();
+    let reading: String = [i / 2500, (i / 50) % 50, i % 50]
+        .iter()
+        .map(|&n| char::from_u32(0x3041 + n as u32).unwrap())
+        .collect();
+    (kanji, reading)
+}
+
+async fn sample_word(pool: &SqlitePool) -> (String, String) {
+    sqlx::query_as("SELECT kanji, reading FROM words ORDER BY id LIMIT 1 OFFSET 1000")
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+fn word_lookups(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let pool = rt.block_on(setup_pool());
+    let words = rt.block_on(WordRepository::load(pool.clone())).unwrap();
+    let (kanji, reading) = rt.block_on(sample_word(&pool));
+
+    let mut group = c.benchmark_group("draw_random_word");
+    group.bench_function("sqlite", |b| {
+        b.to_async(&rt).iter(|| async {
+            let row: Option<(String, String)> =
+                sqlx::query_as("SELECT kanji, reading FROM words ORDER BY RANDOM() LIMIT 1")
+                    .fetch_optional(&pool)
+                    .await
+                    .unwrap();
+            black_box(row)
+        })
+    });
+    group.bench_function("index", |b| b.iter(|| black_box(words.get_random())));
+    group.finish();
+
+    let mut group = c.benchmark_group("validate_answer");
+    group.bench_function("sqlite", |b| {
+        b.to_async(&rt).iter(|| async {
+            let found =
+                sqlx::query_scalar::<_, i32>("SELECT 1 FROM words WHERE kanji = ? AND reading = ?")
+                    .bind(&kanji)
+                    .bind(&reading)
+                    .fetch_optional(&pool)
+                    .await
+                    .unwrap();
+            black_box(found.is_some())
+        })
+    });
+    group.bench_function("index", |b| {
+        b.iter(|| black_box(words.is_valid_reading(&kanji, &reading)))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("readings_for_kanji");
+    group.bench_function("sqlite", |b| {
+        b.to_async(&rt).iter(|| async {
+            let readings =
+                sqlx::query_scalar::<_, String>("SELECT reading FROM words WHERE kanji = ?")
+                    .bind(&kanji)
+                    .fetch_all(&pool)
+                    .await
+                    .unwrap();
+            black_box(readings)
+        })
+    });
+    group.bench_function("index", |b| {
+        b.iter(|| black_box(words.get_readings_for_kanji(&kanji)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, word_lookups);
+criterion_main!(benches);
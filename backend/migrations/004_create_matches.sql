@@ -0,0 +1,16 @@
+CREATE TABLE matches (
+    id INTEGER PRIMARY KEY,
+    game_id TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    player1 TEXT NOT NULL,
+    player2 TEXT NOT NULL,
+    -- Word sequence seed; replaying it with the same dictionary gives the same rounds
+    seed INTEGER NOT NULL,
+    player1_score INTEGER NOT NULL,
+    player2_score INTEGER NOT NULL,
+    winner TEXT,
+    rounds INTEGER NOT NULL,
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_matches_game_id ON matches(game_id);
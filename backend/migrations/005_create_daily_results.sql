@@ -0,0 +1,13 @@
+CREATE TABLE daily_results (
+    id INTEGER PRIMARY KEY,
+    -- UTC date of the challenge, YYYY-MM-DD
+    date TEXT NOT NULL,
+    player_name TEXT NOT NULL,
+    correct INTEGER NOT NULL,
+    -- Total time spent on the challenge's rounds, in milliseconds
+    time_ms INTEGER NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')),
+    UNIQUE (date, player_name)
+);
+
+CREATE INDEX idx_daily_results_ranking ON daily_results(date, correct DESC, time_ms);
@@ -0,0 +1,29 @@
+CREATE TABLE ghost_challenges (
+    -- Share code, same format as ephemeral game codes
+    id TEXT PRIMARY KEY,
+    creator TEXT NOT NULL,
+    seed INTEGER NOT NULL,
+    rounds INTEGER NOT NULL,
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+-- The creator's run, replayed as the ghost
+CREATE TABLE ghost_rounds (
+    challenge_id TEXT NOT NULL REFERENCES ghost_challenges(id),
+    round INTEGER NOT NULL,
+    correct INTEGER NOT NULL,
+    time_ms INTEGER NOT NULL,
+    PRIMARY KEY (challenge_id, round)
+);
+
+CREATE TABLE ghost_results (
+    id INTEGER PRIMARY KEY,
+    challenge_id TEXT NOT NULL REFERENCES ghost_challenges(id),
+    challenger TEXT NOT NULL,
+    challenger_score INTEGER NOT NULL,
+    creator_score INTEGER NOT NULL,
+    winner TEXT,
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_ghost_results_challenge_id ON ghost_results(challenge_id);
@@ -0,0 +1,15 @@
+-- Event log of one game task (a match plus any rematches), for replays
+CREATE TABLE game_logs (
+    id TEXT PRIMARY KEY,
+    game_id TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    player1 TEXT NOT NULL,
+    player2 TEXT NOT NULL,
+    -- JSON array of timed events
+    events TEXT NOT NULL,
+    event_count INTEGER NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')),
+    updated_at TEXT DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_game_logs_game_id ON game_logs(game_id);
@@ -0,0 +1,12 @@
+-- Live games and open lobbies saved periodically and at shutdown,
+-- restored when the server starts
+CREATE TABLE game_snapshots (
+    mode TEXT NOT NULL,
+    -- 'game' or 'pending'
+    kind TEXT NOT NULL,
+    game_id TEXT NOT NULL,
+    -- JSON snapshot
+    data TEXT NOT NULL,
+    saved_at TEXT DEFAULT (datetime('now')),
+    PRIMARY KEY (mode, kind, game_id)
+);
@@ -0,0 +1,3 @@
+-- Why the match ended (score, max_rounds, forfeit, ...); NULL for matches
+-- recorded before reasons were kept. A forfeit's loser left the match.
+ALTER TABLE matches ADD COLUMN end_reason TEXT;
@@ -0,0 +1,6 @@
+-- Secrets the server generates for itself on first start, kept so they
+-- survive restarts (e.g. the key the daily challenge seeds are derived from)
+CREATE TABLE server_secrets (
+    name TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
@@ -0,0 +1,132 @@
+// Generated by `cargo run --bin protocol_schema` from the backend types. Do not edit.
+
+export const PROTOCOL_VERSION = 1;
+
+export type Capability = "latency" | "resume";
+
+export type ClientMessage = { "type": "hello", protocol_version: number, capabilities?: Array<string>, } | { "type": "join", user_id: string, } | { "type": "create_game", player_name: string, 
+/**
+ * Word sequence seed, to replay a previous game's words. Random if omitted.
+ */
+seed?: number, } | { "type": "join_game", game_id: string, player_name: string, } | { "type": "start_daily", player_name: string, } | { "type": "create_challenge", player_name: string, } | { "type": "accept_challenge", challenge_id: string, player_name: string, } | { "type": "resume", token: string, } | { "type": "answer", answer: string, 
+/**
+ * Round the answer is meant for. Answers for a round that already
+ * ended get `stale_answer`; untagged answers count for the current round.
+ */
+round?: number, } | { "type": "skip" } | { "type": "request_rematch" } | { "type": "sync" } | { "type": "pause" } | { "type": "unpause" } | { "type": "offer_draw" } | { "type": "accept_draw" };
+
+export type ClientEnvelope = { request_id?: string, } & ({ "type": "hello", protocol_version: number, capabilities?: Array<string>, } | { "type": "join", user_id: string, } | { "type": "create_game", player_name: string, 
+/**
+ * Word sequence seed, to replay a previous game's words. Random if omitted.
+ */
+seed?: number, } | { "type": "join_game", game_id: string, player_name: string, } | { "type": "start_daily", player_name: string, } | { "type": "create_challenge", player_name: string, } | { "type": "accept_challenge", challenge_id: string, player_name: string, } | { "type": "resume", token: string, } | { "type": "answer", answer: string, 
+/**
+ * Round the answer is meant for. Answers for a round that already
+ * ended get `stale_answer`; untagged answers count for the current round.
+ */
+round?: number, } | { "type": "skip" } | { "type": "request_rematch" } | { "type": "sync" } | { "type": "pause" } | { "type": "unpause" } | { "type": "offer_draw" } | { "type": "accept_draw" });
+
+export type ErrorCode = "bad_request" | "not_in_game" | "unauthenticated" | "rate_limited" | "wrong_endpoint" | "unsupported_protocol_version" | "invalid_name" | "invalid_game_code" | "invalid_user_id" | "invalid_answer" | "invalid_seed" | "already_played" | "internal" | "invalid_resume_token" | "maintenance" | "kicked" | "too_many_connections" | "server_full" | "pause_not_allowed" | "queue_cooldown" | "name_taken" | "game_paused" | "draw_not_allowed" | "no_draw_offer";
+
+export type ServerMessage = { "type": "welcome", protocol_version: number, min_protocol_version: number, 
+/**
+ * Mode served by this endpoint
+ */
+mode: string, 
+/**
+ * All modes offered by the server
+ */
+modes: Array<string>, 
+/**
+ * Capabilities enabled for this connection
+ */
+capabilities: Array<Capability>, } | { "type": "waiting" } | { "type": "game_created", game_id: string, } | { "type": "waiting_for_opponent" } | { "type": "opponent_joined", opponent_name: string, } | { "type": "game_full" } | { "type": "game_not_found" } | { "type": "daily_start", 
+/**
+ * UTC date of the challenge, YYYY-MM-DD
+ */
+date: string, rounds: number, } | { "type": "daily_end", correct: number, time_ms: number, 
+/**
+ * Position on today's leaderboard
+ */
+rank: number, } | { "type": "challenge_start", rounds: number, 
+/**
+ * Name of the recorded player being raced, None while recording
+ */
+ghost: string | null, } | { "type": "challenge_created", challenge_id: string, correct: number, time_ms: number, } | { "type": "game_start", opponent: string, 
+/**
+ * Seed the game's words are drawn from
+ */
+seed: number, } | { "type": "round_start", kanji: string, round: number, readings: Array<string>, } | { "type": "round_result", winner: string | null, correct_reading: string, } | { "type": "wrong_answer" } | { "type": "stale_answer", round: number, } | { "type": "skip_waiting" } | { "type": "rematch_waiting" } | { "type": "opponent_disconnected" } | { "type": "opponent_connection_changed", connected: boolean, grace_remaining?: number, } | { "type": "game_paused", player: string, } | { "type": "unpause_requested", player: string, } | { "type": "game_unpaused", remaining_ms: number, } | { "type": "draw_offered", player: string, } | { "type": "player_latency", player: string, latency_ms: number, } | { "type": "game_end", winner: string | null, reason: GameEndReason, } | { "type": "game_state", game_id: string, opponent: string, your_score: number, opponent_score: number, 
+/**
+ * False once the match is over (see the rematch votes) or while a
+ * restored game waits for its players
+ */
+match_running: boolean, 
+/**
+ * None between rounds
+ */
+round: RoundState | null, 
+/**
+ * Whether the round timer is stopped by a pause or an absent player
+ */
+paused: boolean, you_want_rematch: boolean, opponent_wants_rematch: boolean, 
+/**
+ * Whether a draw offer stands, and from whom
+ */
+you_offered_draw: boolean, opponent_offered_draw: boolean, opponent_connected: boolean, } | { "type": "resume_token", token: string, } | { "type": "resumed", game_id: string, opponent: string, seed: number, your_score: number, opponent_score: number, } | { "type": "server_draining", seconds_left: number, } | { "type": "server_shutdown" } | { "type": "announcement", message: string, } | { "type": "resync", dropped: number, } | { "type": "error", code: ErrorCode, message: string, 
+/**
+ * `request_id` of the client message that caused this error, if it had one
+ */
+request_id?: string, };
+
+export type GameEndReason = "score" | "max_rounds" | "forfeit" | "draw_agreed" | "aborted";
+
+export type RoundState = { round: number, kanji: string, readings: Array<string>, 
+/**
+ * Time left to answer
+ */
+remaining_ms: number, you_skipped: boolean, opponent_skipped: boolean, };
+
+export type LobbyGame = { game_id: string, host_name: string, 
+/**
+ * Seconds since the game was created
+ */
+created_at_secs: number, };
+
+export type LobbyList = { games: Array<LobbyGame>, };
+
+export type DailyLeaderboardEntry = { 
+/**
+ * Equal results share a rank
+ */
+rank: number, player_name: string, correct: number, 
+/**
+ * Total time spent on the challenge's rounds
+ */
+time_ms: number, };
+
+export type DailyLeaderboard = { 
+/**
+ * UTC date of the challenge, YYYY-MM-DD
+ */
+date: string, 
+/**
+ * Words in the challenge
+ */
+rounds: number, entries: Array<DailyLeaderboardEntry>, };
+
+export type GhostChallengeResult = { challenger: string, challenger_score: number, creator_score: number, 
+/**
+ * None for a draw
+ */
+winner: string | null, };
+
+export type GhostChallengeSummary = { challenge_id: string, creator: string, rounds: number, creator_correct: number, creator_time_ms: number, results: Array<GhostChallengeResult>, };
+
+export type ReplaySummary = { replay_id: string, game_id: string, mode: string, player1: string, player2: string, 
+/**
+ * UTC, `YYYY-MM-DD HH:MM:SS`
+ */
+created_at: string, };
+
+export type ReplayList = { replays: Array<ReplaySummary>, };
@@ -0,0 +1,166 @@
+use crate::AppState;
+use crate::game::core::messages::ServerMessage;
+use crate::game::engine::connections::ConnectedClient;
+use crate::game::engine::registry::GameStatus;
+use crate::game::ephemeral::LobbyList;
+use axum::{
+    Json, Router,
+    extract::{Path, Request, State},
+    http::{self, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::Response,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Longest message `POST /admin/broadcast` sends
+const MAX_ANNOUNCEMENT_LEN: usize = 500;
+
+/// Operator endpoints, all behind the admin token
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/games", get(list_games))
+        .route("/games/:game_id/end", post(end_game))
+        .route("/pending", get(list_pending))
+        .route("/queue", get(list_queue))
+        .route("/players", get(list_players))
+        .route("/players/:connection_id/kick", post(kick_player))
+        .route("/broadcast", post(broadcast))
+        .route("/maintenance", post(set_maintenance))
+        .route("/drain", post(drain))
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+/// Check the `Authorization: Bearer` header against the admin token.
+/// Admin endpoints don't exist when no token is configured.
+fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let Some(expected) = &state.admin_token else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let token = headers
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if token == Some(expected.as_str()) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    authorize(&state, request.headers())?;
+    Ok(next.run(request).await)
+}
+
+#[derive(Serialize)]
+struct GameList {
+    games: Vec<GameStatus>,
+}
+
+/// Games in the ephemeral and matchmaking registries, with scores and round
+async fn list_games(State(state): State<AppState>) -> Json<GameList> {
+    let mut games = state.ephemeral.registry.statuses().await;
+    games.extend(state.matchmaking.registry.statuses().await);
+    Json(GameList { games })
+}
+
+/// End a game without a winner; its players stay connected
+async fn end_game(State(state): State<AppState>, Path(game_id): Path<String>) -> StatusCode {
+    if state.ephemeral.registry.end_game(&game_id) || state.matchmaking.registry.end_game(&game_id)
+    {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Every ephemeral game waiting for an opponent, however old
+async fn list_pending(State(state): State<AppState>) -> Json<LobbyList> {
+    Json(state.ephemeral.list_pending_games(u64::MAX))
+}
+
+#[derive(Serialize)]
+struct Queue {
+    waiting: Vec<String>,
+}
+
+async fn list_queue(State(state): State<AppState>) -> Json<Queue> {
+    Json(Queue {
+        waiting: state.matchmaking.lobby.waiting_players(),
+    })
+}
+
+#[derive(Serialize)]
+struct PlayerList {
+    players: Vec<ConnectedClient>,
+}
+
+/// Every open WebSocket connection, including ones that haven't joined anything yet
+async fn list_players(State(state): State<AppState>) -> Json<PlayerList> {
+    Json(PlayerList {
+        players: state.connections.list(),
+    })
+}
+
+async fn kick_player(State(state): State<AppState>, Path(connection_id): Path<u64>) -> StatusCode {
+    if state.connections.kick(connection_id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[derive(Deserialize)]
+struct BroadcastRequest {
+    message: String,
+}
+
+#[derive(Serialize)]
+struct BroadcastResult {
+    /// Connections the message was sent to
+    sent: usize,
+}
+
+/// Send an `announcement` to everyone connected
+async fn broadcast(
+    State(state): State<AppState>,
+    Json(request): Json<BroadcastRequest>,
+) -> Result<Json<BroadcastResult>, StatusCode> {
+    let message = request.message.trim();
+    if message.is_empty() || message.chars().count() > MAX_ANNOUNCEMENT_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let sent = state.connections.broadcast(&ServerMessage::Announcement {
+        message: message.to_string(),
+    });
+    info!(sent, "Broadcast announcement");
+    Ok(Json(BroadcastResult { sent }))
+}
+
+#[derive(Deserialize, Serialize)]
+struct Maintenance {
+    enabled: bool,
+}
+
+/// Refuse new games (and hide the lobby) without draining the server
+async fn set_maintenance(
+    State(state): State<AppState>,
+    Json(request): Json<Maintenance>,
+) -> Json<Maintenance> {
+    state.drain.set_maintenance(request.enabled);
+    Json(Maintenance {
+        enabled: state.drain.in_maintenance(),
+    })
+}
+
+/// Start draining for a deploy; the server exits once games have finished
+async fn drain(State(state): State<AppState>) -> StatusCode {
+    state.drain.start();
+    StatusCode::ACCEPTED
+}
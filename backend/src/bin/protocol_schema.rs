@@ -0,0 +1,24 @@
+//! Regenerate `protocol/schema.json` and `protocol/protocol.ts` from the Rust types.
+//!
+//! Usage: `cargo run --bin protocol_schema [OUTPUT_DIR]`
+
+use std::path::PathBuf;
+use yomitaisen::protocol;
+
+fn main() -> std::io::Result<()> {
+    let dir = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(protocol::output_dir);
+    std::fs::create_dir_all(&dir)?;
+
+    let schema_path = dir.join(protocol::JSON_SCHEMA_FILE);
+    std::fs::write(&schema_path, protocol::json_schema_string())?;
+    println!("Wrote {}", schema_path.display());
+
+    let ts_path = dir.join(protocol::TYPESCRIPT_FILE);
+    std::fs::write(&ts_path, protocol::typescript())?;
+    println!("Wrote {}", ts_path.display());
+
+    Ok(())
+}
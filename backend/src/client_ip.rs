@@ -0,0 +1,77 @@
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// Address of the client behind a connection. `X-Forwarded-For` is only
+/// believed when the connection comes from a trusted proxy; the client is the
+/// last address in it that isn't one of the proxies, since anything before
+/// that could have been sent by the client itself.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if !trusted(&peer) {
+        return peer;
+    }
+
+    let mut forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|entry| entry.trim().parse::<IpAddr>());
+    let mut client = peer;
+    // Walk from the nearest hop outwards, stopping at anything unparseable
+    while let Some(Ok(ip)) = forwarded.next_back() {
+        client = ip;
+        if !trusted(&ip) {
+            break;
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(forwarded: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in forwarded {
+            headers.append("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+        let forged = headers(&["1.2.3.4"]);
+        assert_eq!(
+            client_ip(ip("203.0.113.9"), &forged, &trusted),
+            ip("203.0.113.9")
+        );
+        assert_eq!(client_ip(ip("10.0.0.2"), &forged, &[]), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn takes_the_last_untrusted_hop_from_a_trusted_proxy() {
+        let trusted = ["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()];
+        let peer = ip("10.0.0.2");
+
+        assert_eq!(
+            client_ip(peer, &headers(&["198.51.100.7"]), &trusted),
+            ip("198.51.100.7")
+        );
+        // The client prepended a fake address; the proxy appended the real one
+        let spoofed = headers(&["1.2.3.4, 198.51.100.7, 10.0.0.5"]);
+        assert_eq!(client_ip(peer, &spoofed, &trusted), ip("198.51.100.7"));
+        let split = headers(&["1.2.3.4", "198.51.100.7"]);
+        assert_eq!(client_ip(peer, &split, &trusted), ip("198.51.100.7"));
+        assert_eq!(client_ip(peer, &headers(&[]), &trusted), peer);
+        assert_eq!(client_ip(peer, &headers(&["garbage"]), &trusted), peer);
+    }
+}
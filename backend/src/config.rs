@@ -1,23 +1,861 @@
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use yomitaisen::{
+    AllowedOrigins, AppOptions, BucketConfig, ForfeitConfig, HeartbeatConfig, RateLimitConfig,
+};
 
+/// File read when `CONFIG_FILE` isn't set; it's fine for it not to exist
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Every setting by its path in the config file. Each one can be overridden
+/// with `YOMI_<PATH>`, the path upper-cased with dots as underscores: e.g.
+/// `YOMI_GAME_WINS_NEEDED` or `YOMI_RATE_LIMITS_ANSWERS_CAPACITY`.
+const CONFIG_KEYS: &[&str] = &[
+    "server.bind_address",
+    "server.port",
+    "server.database_url",
+    "server.allowed_origins",
+    "server.trusted_proxies",
+    "server.admin_token",
+    "server.daily_seed_secret",
+    "server.min_words_per_band",
+    "game.round_timeout_secs",
+    "game.wins_needed",
+    "game.max_rounds",
+    "timeouts.resume_grace_secs",
+    "timeouts.reconnect_grace_secs",
+    "timeouts.lobby_max_age_secs",
+    "timeouts.snapshot_interval_secs",
+    "timeouts.drain_timeout_secs",
+    "timeouts.ping_interval_secs",
+    "timeouts.pong_timeout_secs",
+    "rate_limits.answers.capacity",
+    "rate_limits.answers.refill_per_sec",
+    "rate_limits.joins.capacity",
+    "rate_limits.joins.refill_per_sec",
+    "rate_limits.messages.capacity",
+    "rate_limits.messages.refill_per_sec",
+    "rate_limits.ip_answers.capacity",
+    "rate_limits.ip_answers.refill_per_sec",
+    "rate_limits.ip_joins.capacity",
+    "rate_limits.ip_joins.refill_per_sec",
+    "rate_limits.cooldown_base_secs",
+    "rate_limits.cooldown_max_secs",
+    "rate_limits.max_invalid_messages",
+    "forfeits.window_secs",
+    "forfeits.free_forfeits",
+    "forfeits.free_address_forfeits",
+    "forfeits.cooldown_base_secs",
+    "forfeits.cooldown_max_secs",
+    "channels.connection_capacity",
+    "limits.max_connections_per_ip",
+    "limits.max_games",
+    "logging.format",
+];
+
+/// Server settings. Built from the defaults, then the TOML file, then
+/// environment variables, each overriding the one before.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
+    pub server: ServerConfig,
+    pub game: GameConfig,
+    pub timeouts: TimeoutConfig,
+    pub rate_limits: RateLimitSettings,
+    pub forfeits: ForfeitSettings,
+    pub channels: ChannelConfig,
+    pub limits: LimitConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: IpAddr,
     pub port: u16,
     pub database_url: String,
+    /// Browser origins allowed by CORS; `*` allows any
+    pub allowed_origins: Vec<String>,
+    /// Proxies whose `X-Forwarded-For` header is believed, as CIDR ranges
+    /// like `172.16.0.0/12` or `127.0.0.1/32`
+    pub trusted_proxies: Vec<IpNet>,
+    /// Enables the `/admin` endpoints
+    pub admin_token: Option<String>,
+    /// Key the daily challenge seeds are derived from; generated and kept in
+    /// the database if unset
+    pub daily_seed_secret: Option<String>,
+    /// Words each frequency band needs for `/ready` to pass
+    pub min_words_per_band: usize,
 }
 
-impl Config {
-    pub fn from_env() -> Self {
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3000,
+            database_url: "sqlite:data.db?mode=rwc".to_string(),
+            allowed_origins: vec![yomitaisen::DEFAULT_ORIGIN.to_string()],
+            trusted_proxies: Vec::new(),
+            admin_token: None,
+            daily_seed_secret: None,
+            min_words_per_band: yomitaisen::readiness::DEFAULT_MIN_WORDS_PER_BAND,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub round_timeout_secs: u64,
+    /// Round wins that take a match
+    pub wins_needed: u32,
+    /// Rounds after which a match ends whatever the score
+    pub max_rounds: u32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        let rules = yomitaisen::MatchRules::default();
+        Self {
+            round_timeout_secs: rules.round_timeout.as_secs(),
+            wins_needed: rules.wins_needed,
+            max_rounds: rules.max_rounds,
+        }
+    }
+}
+
+/// How long things are kept around or waited for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutConfig {
+    /// How long a restored game waits for its players to reconnect
+    pub resume_grace_secs: u64,
+    /// How long a match waits for a player whose connection dropped; 0 ends it at once
+    pub reconnect_grace_secs: u64,
+    /// How long a pending game stays listed in the lobby
+    pub lobby_max_age_secs: u64,
+    /// How often live games are saved for recovery
+    pub snapshot_interval_secs: u64,
+    /// How long running games get to finish when draining
+    pub drain_timeout_secs: u64,
+    pub ping_interval_secs: u64,
+    /// Disconnect clients that answer no ping for this long
+    pub pong_timeout_secs: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        let heartbeat = HeartbeatConfig::default();
+        Self {
+            resume_grace_secs: yomitaisen::MatchRules::default().resume_grace.as_secs(),
+            reconnect_grace_secs: yomitaisen::MatchRules::default().reconnect_grace.as_secs(),
+            lobby_max_age_secs: yomitaisen::DEFAULT_LOBBY_MAX_AGE.as_secs(),
+            snapshot_interval_secs: yomitaisen::DEFAULT_SNAPSHOT_INTERVAL.as_secs(),
+            drain_timeout_secs: yomitaisen::DEFAULT_DRAIN_TIMEOUT.as_secs(),
+            ping_interval_secs: heartbeat.interval.as_secs(),
+            pong_timeout_secs: heartbeat.timeout.as_secs(),
+        }
+    }
+}
+
+/// [`RateLimitConfig`] as written in the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitSettings {
+    pub answers: BucketSettings,
+    pub joins: BucketSettings,
+    pub messages: BucketSettings,
+    pub ip_answers: BucketSettings,
+    pub ip_joins: BucketSettings,
+    pub cooldown_base_secs: u64,
+    pub cooldown_max_secs: u64,
+    pub max_invalid_messages: u32,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        let limits = RateLimitConfig::default();
+        Self {
+            answers: limits.answers.into(),
+            joins: limits.joins.into(),
+            messages: limits.messages.into(),
+            ip_answers: limits.ip_answers.into(),
+            ip_joins: limits.ip_joins.into(),
+            cooldown_base_secs: limits.cooldown_base.as_secs(),
+            cooldown_max_secs: limits.cooldown_max.as_secs(),
+            max_invalid_messages: limits.max_invalid_messages,
+        }
+    }
+}
+
+/// [`ForfeitConfig`] as written in the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForfeitSettings {
+    /// How long leaving a ranked match counts against the player
+    pub window_secs: u64,
+    /// Forfeits within the window that carry no queue cooldown
+    pub free_forfeits: u32,
+    /// The same for all players on one address together
+    pub free_address_forfeits: u32,
+    pub cooldown_base_secs: u64,
+    pub cooldown_max_secs: u64,
+}
+
+impl Default for ForfeitSettings {
+    fn default() -> Self {
+        let forfeits = ForfeitConfig::default();
+        Self {
+            window_secs: forfeits.window.as_secs(),
+            free_forfeits: forfeits.free_forfeits,
+            free_address_forfeits: forfeits.free_address_forfeits,
+            cooldown_base_secs: forfeits.cooldown_base.as_secs(),
+            cooldown_max_secs: forfeits.cooldown_max.as_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BucketSettings {
+    pub capacity: f64,
+    pub refill_per_sec: f64,
+}
+
+impl BucketSettings {
+    fn is_usable(&self) -> bool {
+        self.capacity.is_finite()
+            && self.capacity >= 1.0
+            && self.refill_per_sec.is_finite()
+            && self.refill_per_sec > 0.0
+    }
+}
+
+impl From<BucketConfig> for BucketSettings {
+    fn from(bucket: BucketConfig) -> Self {
+        Self {
+            capacity: bucket.capacity,
+            refill_per_sec: bucket.refill_per_sec,
+        }
+    }
+}
+
+impl From<BucketSettings> for BucketConfig {
+    fn from(bucket: BucketSettings) -> Self {
+        BucketConfig::new(bucket.capacity, bucket.refill_per_sec)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelConfig {
+    /// Outgoing messages a connection can have queued before droppable ones
+    /// are skipped and the client is resynced
+    pub connection_capacity: usize,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
         Self {
-            port: env::var("PORT")
-                .ok()
-                .and_then(|p| p.parse().ok())
-                .unwrap_or(3000),
-            database_url: env::var("DATABASE_URL")
-                .unwrap_or_else(|_| "sqlite:data.db?mode=rwc".to_string()),
+            connection_capacity: yomitaisen::DEFAULT_CHANNEL_CAPACITY,
+        }
+    }
+}
+
+/// Caps on how much of the server one client, or everyone together, can use
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitConfig {
+    /// Open WebSocket connections per client address
+    pub max_connections_per_ip: usize,
+    /// Pending games, running games and solo runs at once
+    pub max_games: usize,
+}
+
+impl Default for LimitConfig {
+    fn default() -> Self {
+        Self {
+            max_connections_per_ip: yomitaisen::DEFAULT_MAX_CONNECTIONS_PER_IP,
+            max_games: yomitaisen::DEFAULT_MAX_GAMES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Why the configuration could not be loaded
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, Box<toml::de::Error>),
+    /// An environment variable that doesn't parse as its setting
+    Env {
+        name: String,
+        value: String,
+    },
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "invalid {}: {}", path.display(), e),
+            ConfigError::Env { name, value } => write!(f, "invalid {}={:?}", name, value),
+            ConfigError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Load `CONFIG_FILE` (or `config.toml` if it exists), apply environment
+    /// overrides and validate the result
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match env::var("CONFIG_FILE") {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            Err(_) => Self::default(),
+        };
+        config.apply_env(|name| env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        Self::from_toml(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), Box::new(e)))
+    }
+
+    fn from_toml(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Override settings from environment variables, looked up with `var`:
+    /// first the short names kept from before every setting had one, then
+    /// `YOMI_*` (see [`CONFIG_KEYS`])
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let server = &mut self.server;
+        parse_env(&var, "BIND_ADDRESS", &mut server.bind_address)?;
+        parse_env(&var, "PORT", &mut server.port)?;
+        if let Some(url) = var("DATABASE_URL") {
+            server.database_url = url;
+        }
+        if let Some(origins) = var("ALLOWED_ORIGINS") {
+            server.allowed_origins = origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some(proxies) = var("TRUSTED_PROXIES") {
+            server.trusted_proxies = proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(parse_proxy)
+                .collect::<Result<_, _>>()
+                .map_err(|_| ConfigError::Env {
+                    name: "TRUSTED_PROXIES".to_string(),
+                    value: proxies.clone(),
+                })?;
+        }
+        if var("CORS_ALLOW_ALL").is_some_and(|v| v == "true" || v == "1") {
+            server.allowed_origins = vec!["*".to_string()];
+        }
+        if let Some(token) = var("ADMIN_TOKEN") {
+            server.admin_token = Some(token).filter(|t| !t.is_empty());
+        }
+        if let Some(secret) = var("DAILY_SEED_SECRET") {
+            server.daily_seed_secret = Some(secret).filter(|s| !s.is_empty());
+        }
+        parse_env(&var, "MIN_WORDS_PER_BAND", &mut server.min_words_per_band)?;
+
+        let game = &mut self.game;
+        parse_env(&var, "ROUND_TIMEOUT_SECS", &mut game.round_timeout_secs)?;
+        parse_env(&var, "WINS_NEEDED", &mut game.wins_needed)?;
+        parse_env(&var, "MAX_ROUNDS", &mut game.max_rounds)?;
+
+        parse_env(
+            &var,
+            "DRAIN_TIMEOUT_SECS",
+            &mut self.timeouts.drain_timeout_secs,
+        )?;
+        parse_env(
+            &var,
+            "MAX_CONNECTIONS_PER_IP",
+            &mut self.limits.max_connections_per_ip,
+        )?;
+        parse_env(&var, "MAX_GAMES", &mut self.limits.max_games)?;
+        parse_env(&var, "LOG_FORMAT", &mut self.logging.format)?;
+        self.apply_prefixed_env(&var)
+    }
+
+    /// Override any setting in [`CONFIG_KEYS`] from its `YOMI_*` variable.
+    /// Values are written as in the file, lists comma-separated; an empty
+    /// value puts the setting back to its default.
+    fn apply_prefixed_env(
+        &mut self,
+        var: &impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        let mut settings = toml::Value::try_from(&*self).expect("config is representable as TOML");
+        for key in CONFIG_KEYS {
+            let name = env_name(key);
+            let Some(value) = var(&name) else {
+                continue;
+            };
+            let invalid = || ConfigError::Env {
+                name: name.clone(),
+                value: value.clone(),
+            };
+
+            let mut path: Vec<&str> = key.split('.').collect();
+            let leaf = path.pop().expect("keys name a section");
+            let mut table = settings.as_table_mut().expect("config is a table");
+            for part in path {
+                table = table
+                    .entry(part)
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    .as_table_mut()
+                    .ok_or_else(invalid)?;
+            }
+            if value.trim().is_empty() {
+                table.remove(leaf);
+            } else {
+                let parsed = env_value(table.get(leaf), &value).ok_or_else(invalid)?;
+                table.insert(leaf.to_string(), parsed);
+            }
+
+            // Check each variable on its own, so an error names the one at fault
+            settings
+                .clone()
+                .try_into::<Config>()
+                .map_err(|_| invalid())?;
+        }
+        *self = settings.try_into().expect("every override was checked");
+        Ok(())
+    }
+
+    /// Reject settings the server can't run with
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| Err(ConfigError::Invalid(reason.to_string()));
+        if let Err(e) = AllowedOrigins::parse(&self.server.allowed_origins) {
+            return Err(ConfigError::Invalid(e.to_string()));
+        }
+
+        let game = &self.game;
+        if game.round_timeout_secs == 0 {
+            return invalid("game.round_timeout_secs must be at least 1");
+        }
+        if game.wins_needed == 0 || game.max_rounds == 0 {
+            return invalid("game.wins_needed and game.max_rounds must be at least 1");
+        }
+
+        let timeouts = &self.timeouts;
+        if timeouts.lobby_max_age_secs == 0 || timeouts.snapshot_interval_secs == 0 {
+            return invalid(
+                "timeouts.lobby_max_age_secs and timeouts.snapshot_interval_secs must be at least 1",
+            );
+        }
+        if timeouts.ping_interval_secs == 0
+            || timeouts.pong_timeout_secs <= timeouts.ping_interval_secs
+        {
+            return invalid(
+                "timeouts.pong_timeout_secs must be longer than timeouts.ping_interval_secs, which must be at least 1",
+            );
+        }
+
+        let limits = &self.rate_limits;
+        for (name, bucket) in [
+            ("answers", limits.answers),
+            ("joins", limits.joins),
+            ("messages", limits.messages),
+            ("ip_answers", limits.ip_answers),
+            ("ip_joins", limits.ip_joins),
+        ] {
+            if !bucket.is_usable() {
+                return Err(ConfigError::Invalid(format!(
+                    "rate_limits.{} needs a capacity of at least 1 and a positive refill_per_sec",
+                    name
+                )));
+            }
+        }
+        if limits.cooldown_base_secs > limits.cooldown_max_secs {
+            return invalid("rate_limits.cooldown_base_secs can't exceed cooldown_max_secs");
+        }
+        if limits.max_invalid_messages == 0 {
+            return invalid("rate_limits.max_invalid_messages must be at least 1");
+        }
+
+        let forfeits = &self.forfeits;
+        if forfeits.cooldown_base_secs > forfeits.cooldown_max_secs {
+            return invalid("forfeits.cooldown_base_secs can't exceed cooldown_max_secs");
+        }
+
+        if self.channels.connection_capacity == 0 {
+            return invalid("channels.connection_capacity must be at least 1");
+        }
+        if self.limits.max_connections_per_ip == 0 || self.limits.max_games == 0 {
+            return invalid(
+                "limits.max_connections_per_ip and limits.max_games must be at least 1",
+            );
+        }
+        Ok(())
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.server.bind_address, self.server.port)
+    }
+
+    /// The settings [`yomitaisen::build`] takes. Only valid after [`Config::load`].
+    pub fn app_options(&self) -> AppOptions {
+        let limits = &self.rate_limits;
+        let timeouts = &self.timeouts;
+        AppOptions {
+            round_timeout: Some(Duration::from_secs(self.game.round_timeout_secs)),
+            wins_needed: Some(self.game.wins_needed),
+            max_rounds: Some(self.game.max_rounds),
+            resume_grace: Some(Duration::from_secs(timeouts.resume_grace_secs)),
+            reconnect_grace: Some(Duration::from_secs(timeouts.reconnect_grace_secs)),
+            rate_limits: RateLimitConfig {
+                answers: limits.answers.into(),
+                joins: limits.joins.into(),
+                messages: limits.messages.into(),
+                ip_answers: limits.ip_answers.into(),
+                ip_joins: limits.ip_joins.into(),
+                cooldown_base: Duration::from_secs(limits.cooldown_base_secs),
+                cooldown_max: Duration::from_secs(limits.cooldown_max_secs),
+                max_invalid_messages: limits.max_invalid_messages,
+            },
+            heartbeat: HeartbeatConfig {
+                interval: Duration::from_secs(timeouts.ping_interval_secs),
+                timeout: Duration::from_secs(timeouts.pong_timeout_secs),
+            },
+            forfeits: ForfeitConfig {
+                window: Duration::from_secs(self.forfeits.window_secs),
+                free_forfeits: self.forfeits.free_forfeits,
+                free_address_forfeits: self.forfeits.free_address_forfeits,
+                cooldown_base: Duration::from_secs(self.forfeits.cooldown_base_secs),
+                cooldown_max: Duration::from_secs(self.forfeits.cooldown_max_secs),
+            },
+            channel_capacity: Some(self.channels.connection_capacity),
+            lobby_max_age: Some(Duration::from_secs(timeouts.lobby_max_age_secs)),
+            allowed_origins: AllowedOrigins::parse(&self.server.allowed_origins)
+                .expect("origins are checked by validate"),
+            snapshot_interval: Some(Duration::from_secs(timeouts.snapshot_interval_secs)),
+            drain_timeout: Some(Duration::from_secs(timeouts.drain_timeout_secs)),
+            max_connections_per_ip: Some(self.limits.max_connections_per_ip),
+            max_games: Some(self.limits.max_games),
+            trusted_proxies: self.server.trusted_proxies.clone(),
+            admin_token: self.server.admin_token.clone(),
+            daily_seed_secret: self.server.daily_seed_secret.clone(),
+            min_words_per_band: Some(self.server.min_words_per_band),
+        }
+    }
+}
+
+/// A proxy address or CIDR range; a bare address is a single-host range
+fn parse_proxy(proxy: &str) -> Result<IpNet, ()> {
+    proxy
+        .parse::<IpNet>()
+        .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| ())
+}
+
+/// Parse `name` into `target` if it is set
+fn parse_env<T: FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    target: &mut T,
+) -> Result<(), ConfigError> {
+    if let Some(value) = var(name) {
+        *target = value.trim().parse().map_err(|_| ConfigError::Env {
+            name: name.to_string(),
+            value,
+        })?;
+    }
+    Ok(())
+}
+
+/// Environment variable that overrides the setting at `key`
+fn env_name(key: &str) -> String {
+    format!("YOMI_{}", key.replace('.', "_").to_uppercase())
+}
+
+/// An environment value as TOML, typed like the setting's `current` value.
+/// Settings without one are unset optional strings.
+fn env_value(current: Option<&toml::Value>, value: &str) -> Option<toml::Value> {
+    let value = value.trim();
+    Some(match current {
+        Some(toml::Value::Integer(_)) => toml::Value::Integer(value.parse().ok()?),
+        Some(toml::Value::Float(_)) => toml::Value::Float(value.parse().ok()?),
+        Some(toml::Value::Boolean(_)) => toml::Value::Boolean(value.parse().ok()?),
+        Some(toml::Value::Array(_)) => toml::Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| toml::Value::String(item.to_string()))
+                .collect(),
+        ),
+        _ => toml::Value::String(value.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = Config::default();
+        config.validate().unwrap();
+        assert_eq!(config.addr().to_string(), "0.0.0.0:3000");
+        assert_eq!(
+            config.app_options().allowed_origins,
+            AllowedOrigins::default()
+        );
+    }
+
+    #[test]
+    fn example_file_is_valid() {
+        let config = Config::from_toml(include_str!("../config.example.toml")).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.game.wins_needed, Config::default().game.wins_needed);
+    }
+
+    #[test]
+    fn file_overrides_defaults_and_env_overrides_file() {
+        let mut config = Config::from_toml(
+            r#"
+            [server]
+            port = 8080
+            allowed_origins = ["http://localhost:5173"]
+
+            [game]
+            wins_needed = 5
+
+            [rate_limits.joins]
+            capacity = 6
+            refill_per_sec = 1
+
+            [logging]
+            format = "json"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.game.wins_needed, 5);
+        assert_eq!(config.game.max_rounds, 30);
+        assert_eq!(config.rate_limits.joins.capacity, 6.0);
+        assert_eq!(config.logging.format, LogFormat::Json);
+
+        config
+            .apply_env(env(&[("PORT", "9000"), ("LOG_FORMAT", "text")]))
+            .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.logging.format, LogFormat::Text);
+        assert_eq!(config.app_options().wins_needed, Some(5));
+    }
+
+    #[test]
+    fn allowed_origins_from_env() {
+        let mut config = Config::default();
+        config
+            .apply_env(env(&[(
+                "ALLOWED_ORIGINS",
+                "https://a.example, http://localhost:5173",
+            )]))
+            .unwrap();
+        assert_eq!(
+            config.server.allowed_origins,
+            ["https://a.example", "http://localhost:5173"]
+        );
+
+        config
+            .apply_env(env(&[("TRUSTED_PROXIES", "127.0.0.1, 172.16.0.0/12")]))
+            .unwrap();
+        assert_eq!(
+            config.app_options().trusted_proxies,
+            [
+                "127.0.0.1/32".parse::<IpNet>().unwrap(),
+                "172.16.0.0/12".parse().unwrap()
+            ]
+        );
+
+        config
+            .apply_env(env(&[("CORS_ALLOW_ALL", "true")]))
+            .unwrap();
+        assert_eq!(config.app_options().allowed_origins, AllowedOrigins::Any);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_bad_env_values() {
+        assert!(Config::from_toml("[game]\nwins = 3").is_err());
+
+        let mut config = Config::default();
+        let err = config.apply_env(env(&[("PORT", "eighty")])).unwrap_err();
+        assert!(matches!(err, ConfigError::Env { name, .. } if name == "PORT"));
+
+        let err = config
+            .apply_env(env(&[("YOMI_SERVER_BIND_ADDRESS", "localhost")]))
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Env { name, .. } if name == "YOMI_SERVER_BIND_ADDRESS"));
+    }
+
+    /// Setting at `key` in `settings`
+    fn lookup<'a>(settings: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+        key.split('.')
+            .try_fold(settings, |value, part| value.get(part))
+    }
+
+    /// Paths of every setting in `settings`
+    fn leaf_keys(settings: &toml::Value, prefix: &str, keys: &mut Vec<String>) {
+        match settings.as_table() {
+            Some(table) => {
+                for (name, value) in table {
+                    let key = if prefix.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{}.{}", prefix, name)
+                    };
+                    leaf_keys(value, &key, keys);
+                }
+            }
+            None => keys.push(prefix.to_string()),
         }
     }
 
-    pub fn addr(&self) -> String {
-        format!("0.0.0.0:{}", self.port)
+    #[test]
+    fn every_setting_has_an_env_override() {
+        let mut config = Config::default();
+        config.server.admin_token = Some("token".to_string());
+        config.server.daily_seed_secret = Some("secret".to_string());
+        let mut keys = Vec::new();
+        leaf_keys(&toml::Value::try_from(&config).unwrap(), "", &mut keys);
+        keys.sort();
+
+        let mut listed: Vec<String> = CONFIG_KEYS.iter().map(|key| key.to_string()).collect();
+        listed.sort();
+        assert_eq!(keys, listed);
+    }
+
+    #[test]
+    fn env_overrides_each_setting() {
+        let defaults = toml::Value::try_from(Config::default()).unwrap();
+        for key in CONFIG_KEYS {
+            let (value, expected) = match (*key, lookup(&defaults, key)) {
+                ("server.bind_address", _) => {
+                    ("127.0.0.1".to_string(), toml::Value::from("127.0.0.1"))
+                }
+                ("server.allowed_origins", _) => (
+                    "https://a.example, http://localhost:5173".to_string(),
+                    toml::Value::from(vec!["https://a.example", "http://localhost:5173"]),
+                ),
+                ("server.trusted_proxies", _) => (
+                    "10.0.0.0/8".to_string(),
+                    toml::Value::from(vec!["10.0.0.0/8"]),
+                ),
+                ("logging.format", _) => ("json".to_string(), toml::Value::from("json")),
+                (_, Some(toml::Value::Integer(n))) => {
+                    ((n + 1).to_string(), toml::Value::from(n + 1))
+                }
+                (_, Some(toml::Value::Float(f))) => {
+                    ((f + 0.5).to_string(), toml::Value::from(f + 0.5))
+                }
+                _ => ("override".to_string(), toml::Value::from("override")),
+            };
+
+            let mut config = Config::default();
+            config
+                .apply_env(env(&[(&env_name(key), &value)]))
+                .unwrap_or_else(|e| panic!("{key}: {e}"));
+            let settings = toml::Value::try_from(&config).unwrap();
+            assert_eq!(lookup(&settings, key), Some(&expected), "{key}");
+        }
+    }
+
+    #[test]
+    fn empty_env_value_restores_the_default() {
+        let mut config =
+            Config::from_toml("[server]\nport = 8080\nadmin_token = \"token\"").unwrap();
+        config
+            .apply_env(env(&[
+                ("YOMI_SERVER_PORT", ""),
+                ("YOMI_SERVER_ADMIN_TOKEN", ""),
+                ("YOMI_RATE_LIMITS_JOINS_CAPACITY", "6"),
+            ]))
+            .unwrap();
+        assert_eq!(config.server.port, 3000);
+        assert_eq!(config.server.admin_token, None);
+        assert_eq!(config.rate_limits.joins.capacity, 6.0);
+    }
+
+    #[test]
+    fn rejects_settings_the_server_cannot_run_with() {
+        for toml in [
+            "[game]\nround_timeout_secs = 0",
+            "[game]\nwins_needed = 0",
+            "[timeouts]\nsnapshot_interval_secs = 0",
+            "[timeouts]\nping_interval_secs = 30\npong_timeout_secs = 10",
+            "[rate_limits.answers]\ncapacity = 0\nrefill_per_sec = 1",
+            "[forfeits]\ncooldown_base_secs = 600\ncooldown_max_secs = 60",
+            "[channels]\nconnection_capacity = 0",
+            "[limits]\nmax_games = 0",
+            "[server]\nallowed_origins = [\"yomi.alsvik.cloud\"]",
+        ] {
+            let config = Config::from_toml(toml).unwrap();
+            assert!(
+                matches!(config.validate(), Err(ConfigError::Invalid(_))),
+                "{toml}"
+            );
+        }
     }
 }
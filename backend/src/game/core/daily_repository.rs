@@ -0,0 +1,103 @@
+use sqlx::SqlitePool;
+
+/// One player's finished daily challenge, as stored in the `daily_results` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyResult {
+    /// UTC date of the challenge, YYYY-MM-DD
+    pub date: String,
+    pub player_name: String,
+    pub correct: u32,
+    pub time_ms: u64,
+}
+
+/// Daily challenge results. Players are ranked by correct answers, then by
+/// total time, so accuracy always beats speed.
+#[derive(Clone)]
+pub struct DailyRepository {
+    pool: SqlitePool,
+}
+
+impl DailyRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Key the daily seeds are derived from when none is configured.
+    /// Generated on first use and stored, so the day's words survive restarts.
+    pub async fn seed_secret(&self) -> Result<String, sqlx::Error> {
+        let generated: String = rand::random::<[u8; 32]>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        sqlx::query("INSERT OR IGNORE INTO server_secrets (name, value) VALUES ('daily_seed', ?)")
+            .bind(generated)
+            .execute(&self.pool)
+            .await?;
+        let (secret,): (String,) =
+            sqlx::query_as("SELECT value FROM server_secrets WHERE name = 'daily_seed'")
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(secret)
+    }
+
+    pub async fn has_played(&self, date: &str, player_name: &str) -> Result<bool, sqlx::Error> {
+        let row: Option<(i64,)> =
+            sqlx::query_as("SELECT 1 FROM daily_results WHERE date = ? AND player_name = ?")
+                .bind(date)
+                .bind(player_name)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(row.is_some())
+    }
+
+    /// Store a result. Fails if the player already has one for that date.
+    pub async fn record(&self, result: &DailyResult) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO daily_results (date, player_name, correct, time_ms) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&result.date)
+        .bind(&result.player_name)
+        .bind(result.correct)
+        .bind(result.time_ms as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// 1-based position of a stored result on its date's leaderboard
+    pub async fn rank(&self, result: &DailyResult) -> Result<u32, sqlx::Error> {
+        let (better,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM daily_results
+             WHERE date = ? AND (correct > ? OR (correct = ? AND time_ms < ?))",
+        )
+        .bind(&result.date)
+        .bind(result.correct)
+        .bind(result.correct)
+        .bind(result.time_ms as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(better as u32 + 1)
+    }
+
+    /// Best results for a date, best first
+    pub async fn leaderboard(&self, date: &str, limit: u32) -> Result<Vec<DailyResult>, sqlx::Error> {
+        let rows: Vec<(String, String, u32, i64)> = sqlx::query_as(
+            "SELECT date, player_name, correct, time_ms FROM daily_results
+             WHERE date = ? ORDER BY correct DESC, time_ms, id LIMIT ?",
+        )
+        .bind(date)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(date, player_name, correct, time_ms)| DailyResult {
+                date,
+                player_name,
+                correct,
+                time_ms: time_ms as u64,
+            })
+            .collect())
+    }
+}
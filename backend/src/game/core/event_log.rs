@@ -0,0 +1,230 @@
+use super::messages::{GameEndReason, ServerMessage};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// A state transition of a game, as recorded for replays
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// A match started (the first one, or a rematch)
+    GameStart {
+        player1: String,
+        player2: String,
+        seed: u64,
+    },
+    RoundStart {
+        round: u32,
+        kanji: String,
+        readings: Vec<String>,
+    },
+    WrongAnswer {
+        player: String,
+        answer: String,
+    },
+    SkipVote {
+        player: String,
+    },
+    /// Both players skipped, ending the round
+    RoundSkipped {
+        round: u32,
+        correct_reading: String,
+    },
+    CorrectAnswer {
+        player: String,
+        round: u32,
+        correct_reading: String,
+    },
+    Timeout {
+        round: u32,
+        correct_reading: String,
+    },
+    GameEnd {
+        winner: Option<String>,
+        #[serde(default)]
+        reason: GameEndReason,
+    },
+    RematchRequested {
+        player: String,
+    },
+    DrawOffered {
+        player: String,
+    },
+    Disconnect {
+        player: String,
+    },
+}
+
+impl GameEvent {
+    /// What `viewer` was sent when this happened, if anything
+    pub fn replay_message(&self, viewer: &str) -> Option<ServerMessage> {
+        match self {
+            GameEvent::GameStart {
+                player1,
+                player2,
+                seed,
+            } => Some(ServerMessage::GameStart {
+                opponent: if viewer == player1 { player2 } else { player1 }.clone(),
+                seed: *seed,
+            }),
+            GameEvent::RoundStart {
+                round,
+                kanji,
+                readings,
+            } => Some(ServerMessage::RoundStart {
+                kanji: kanji.clone(),
+                round: *round,
+                readings: readings.clone(),
+            }),
+            GameEvent::WrongAnswer { player, .. } => {
+                (player == viewer).then_some(ServerMessage::WrongAnswer)
+            }
+            GameEvent::SkipVote { player } => {
+                (player == viewer).then_some(ServerMessage::SkipWaiting)
+            }
+            GameEvent::RoundSkipped {
+                correct_reading, ..
+            }
+            | GameEvent::Timeout {
+                correct_reading, ..
+            } => Some(ServerMessage::RoundResult {
+                winner: None,
+                correct_reading: correct_reading.clone(),
+            }),
+            GameEvent::CorrectAnswer {
+                player,
+                correct_reading,
+                ..
+            } => Some(ServerMessage::RoundResult {
+                winner: Some(player.clone()),
+                correct_reading: correct_reading.clone(),
+            }),
+            GameEvent::GameEnd { winner, reason } => Some(ServerMessage::GameEnd {
+                winner: winner.clone(),
+                reason: *reason,
+            }),
+            GameEvent::RematchRequested { player } => {
+                (player == viewer).then_some(ServerMessage::RematchWaiting)
+            }
+            GameEvent::DrawOffered { player } => Some(ServerMessage::DrawOffered {
+                player: player.clone(),
+            }),
+            GameEvent::Disconnect { player } => {
+                (player != viewer).then_some(ServerMessage::OpponentDisconnected)
+            }
+        }
+    }
+}
+
+/// An event and when it happened, relative to the start of the log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimedEvent {
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
+
+/// Append-only record of a game's events
+#[derive(Debug)]
+pub struct EventLog {
+    started: Instant,
+    events: Vec<TimedEvent>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, event: GameEvent) {
+        self.events.push(TimedEvent {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            event,
+        });
+    }
+
+    pub fn events(&self) -> &[TimedEvent] {
+        &self.events
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_timestamped_in_order() {
+        let mut log = EventLog::new();
+        log.record(GameEvent::SkipVote {
+            player: "Alice".to_string(),
+        });
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        log.record(GameEvent::SkipVote {
+            player: "Bob".to_string(),
+        });
+
+        let events = log.events();
+        assert_eq!(events.len(), 2);
+        assert!(events[1].at_ms >= events[0].at_ms + 5);
+    }
+
+    #[test]
+    fn replay_follows_the_viewers_perspective() {
+        let start = GameEvent::GameStart {
+            player1: "Alice".to_string(),
+            player2: "Bob".to_string(),
+            seed: 7,
+        };
+        assert_eq!(
+            start.replay_message("Bob"),
+            Some(ServerMessage::GameStart {
+                opponent: "Alice".to_string(),
+                seed: 7
+            })
+        );
+
+        let wrong = GameEvent::WrongAnswer {
+            player: "Alice".to_string(),
+            answer: "x".to_string(),
+        };
+        assert_eq!(
+            wrong.replay_message("Alice"),
+            Some(ServerMessage::WrongAnswer)
+        );
+        assert_eq!(wrong.replay_message("Bob"), None);
+
+        let left = GameEvent::Disconnect {
+            player: "Alice".to_string(),
+        };
+        assert_eq!(
+            left.replay_message("Bob"),
+            Some(ServerMessage::OpponentDisconnected)
+        );
+        assert_eq!(left.replay_message("Alice"), None);
+    }
+
+    #[test]
+    fn timed_events_serialize_flat() {
+        let event = TimedEvent {
+            at_ms: 1500,
+            event: GameEvent::Timeout {
+                round: 3,
+                correct_reading: "にほん".to_string(),
+            },
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"at_ms":1500,"type":"timeout","round":3,"correct_reading":"にほん"}"#
+        );
+        assert_eq!(serde_json::from_str::<TimedEvent>(&json).unwrap(), event);
+    }
+}
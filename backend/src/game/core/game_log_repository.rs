@@ -0,0 +1,116 @@
+use super::event_log::TimedEvent;
+use sqlx::SqlitePool;
+
+/// A game's recorded events, as stored in the `game_logs` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameLog {
+    /// Replay id, unique per game task
+    pub id: String,
+    pub game_id: String,
+    pub mode: String,
+    pub player1: String,
+    pub player2: String,
+    pub events: Vec<TimedEvent>,
+}
+
+/// Listing entry for a stored game log
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameLogSummary {
+    pub id: String,
+    pub game_id: String,
+    pub mode: String,
+    pub player1: String,
+    pub player2: String,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`
+    pub created_at: String,
+}
+
+#[derive(Clone)]
+pub struct GameLogRepository {
+    pool: SqlitePool,
+}
+
+impl GameLogRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Insert or extend a log. Saves may complete out of order, so a save
+    /// never replaces a log with one holding fewer events.
+    pub async fn save(&self, log: &GameLog) -> Result<(), sqlx::Error> {
+        let events =
+            serde_json::to_string(&log.events).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        sqlx::query(
+            "INSERT INTO game_logs (id, game_id, mode, player1, player2, events, event_count)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 events = excluded.events,
+                 event_count = excluded.event_count,
+                 updated_at = datetime('now')
+             WHERE excluded.event_count > game_logs.event_count",
+        )
+        .bind(&log.id)
+        .bind(&log.game_id)
+        .bind(&log.mode)
+        .bind(&log.player1)
+        .bind(&log.player2)
+        .bind(events)
+        .bind(log.events.len() as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<GameLog>, sqlx::Error> {
+        let row: Option<(String, String, String, String, String, String)> = sqlx::query_as(
+            "SELECT id, game_id, mode, player1, player2, events FROM game_logs WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some((id, game_id, mode, player1, player2, events)) = row else {
+            return Ok(None);
+        };
+        let events = serde_json::from_str(&events).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        Ok(Some(GameLog {
+            id,
+            game_id,
+            mode,
+            player1,
+            player2,
+            events,
+        }))
+    }
+
+    /// Most recent logs first, optionally only those of one game code
+    pub async fn list(
+        &self,
+        game_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<GameLogSummary>, sqlx::Error> {
+        let rows: Vec<(String, String, String, String, String, String)> = sqlx::query_as(
+            "SELECT id, game_id, mode, player1, player2, created_at FROM game_logs
+             WHERE ?1 IS NULL OR game_id = ?1
+             ORDER BY created_at DESC, rowid DESC LIMIT ?2",
+        )
+        .bind(game_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, game_id, mode, player1, player2, created_at)| GameLogSummary {
+                    id,
+                    game_id,
+                    mode,
+                    player1,
+                    player2,
+                    created_at,
+                },
+            )
+            .collect())
+    }
+}
@@ -0,0 +1,139 @@
+use super::solo::{RoundRecord, RunRecord};
+use sqlx::SqlitePool;
+
+/// A recorded run others can play against, as stored in `ghost_challenges`
+/// and `ghost_rounds`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GhostChallenge {
+    pub id: String,
+    pub creator: String,
+    pub seed: u64,
+    pub rounds: u32,
+    pub ghost: RunRecord,
+}
+
+/// Someone's game against a challenge's ghost, as stored in `ghost_results`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GhostResult {
+    pub challenge_id: String,
+    pub challenger: String,
+    pub challenger_score: u32,
+    pub creator_score: u32,
+    pub winner: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct GhostRepository {
+    pool: SqlitePool,
+}
+
+impl GhostRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn exists(&self, id: &str) -> Result<bool, sqlx::Error> {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM ghost_challenges WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Store a challenge together with its ghost's rounds
+    pub async fn create(&self, challenge: &GhostChallenge) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO ghost_challenges (id, creator, seed, rounds) VALUES (?, ?, ?, ?)")
+            .bind(&challenge.id)
+            .bind(&challenge.creator)
+            .bind(challenge.seed as i64)
+            .bind(challenge.rounds)
+            .execute(&mut *tx)
+            .await?;
+        for (i, round) in challenge.ghost.rounds.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO ghost_rounds (challenge_id, round, correct, time_ms) VALUES (?, ?, ?, ?)",
+            )
+            .bind(&challenge.id)
+            .bind(i as u32 + 1)
+            .bind(round.correct)
+            .bind(round.time_ms as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<GhostChallenge>, sqlx::Error> {
+        let Some((id, creator, seed, rounds)): Option<(String, String, i64, u32)> =
+            sqlx::query_as("SELECT id, creator, seed, rounds FROM ghost_challenges WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?
+        else {
+            return Ok(None);
+        };
+
+        let ghost_rounds: Vec<(bool, i64)> = sqlx::query_as(
+            "SELECT correct, time_ms FROM ghost_rounds WHERE challenge_id = ? ORDER BY round",
+        )
+        .bind(&id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(Some(GhostChallenge {
+            id,
+            creator,
+            seed: seed as u64,
+            rounds,
+            ghost: RunRecord {
+                rounds: ghost_rounds
+                    .into_iter()
+                    .map(|(correct, time_ms)| RoundRecord {
+                        correct,
+                        time_ms: time_ms as u64,
+                    })
+                    .collect(),
+            },
+        }))
+    }
+
+    pub async fn record_result(&self, result: &GhostResult) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO ghost_results (challenge_id, challenger, challenger_score, creator_score, winner)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&result.challenge_id)
+        .bind(&result.challenger)
+        .bind(result.challenger_score)
+        .bind(result.creator_score)
+        .bind(&result.winner)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Everyone who has played a challenge, oldest first
+    pub async fn results(&self, challenge_id: &str) -> Result<Vec<GhostResult>, sqlx::Error> {
+        let rows: Vec<(String, String, u32, u32, Option<String>)> = sqlx::query_as(
+            "SELECT challenge_id, challenger, challenger_score, creator_score, winner
+             FROM ghost_results WHERE challenge_id = ? ORDER BY id",
+        )
+        .bind(challenge_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(challenge_id, challenger, challenger_score, creator_score, winner)| GhostResult {
+                    challenge_id,
+                    challenger,
+                    challenger_score,
+                    creator_score,
+                    winner,
+                },
+            )
+            .collect())
+    }
+}
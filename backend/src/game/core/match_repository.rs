@@ -0,0 +1,108 @@
+use super::messages::GameEndReason;
+use sqlx::SqlitePool;
+
+/// A finished game, as stored in the `matches` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchRecord {
+    pub game_id: String,
+    pub mode: String,
+    pub player1: String,
+    pub player2: String,
+    pub seed: u64,
+    pub player1_score: u32,
+    pub player2_score: u32,
+    pub winner: Option<String>,
+    pub rounds: u32,
+    /// A forfeit's loser is the player who left
+    pub reason: GameEndReason,
+}
+
+/// Column tuple of a `matches` row, in [`MatchRecord`] field order
+type MatchRow = (
+    String,
+    String,
+    String,
+    String,
+    i64,
+    u32,
+    u32,
+    Option<String>,
+    u32,
+    Option<String>,
+);
+
+#[derive(Clone)]
+pub struct MatchRepository {
+    pool: SqlitePool,
+}
+
+impl MatchRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn record(&self, record: &MatchRecord) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO matches (game_id, mode, player1, player2, seed, player1_score, player2_score, winner, rounds, end_reason)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&record.game_id)
+        .bind(&record.mode)
+        .bind(&record.player1)
+        .bind(&record.player2)
+        .bind(record.seed as i64)
+        .bind(record.player1_score)
+        .bind(record.player2_score)
+        .bind(&record.winner)
+        .bind(record.rounds)
+        .bind(record.reason.name())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Matches played in a game, oldest first (a game has several after rematches)
+    pub async fn for_game(&self, game_id: &str) -> Result<Vec<MatchRecord>, sqlx::Error> {
+        let rows: Vec<MatchRow> = sqlx::query_as(
+                "SELECT game_id, mode, player1, player2, seed, player1_score, player2_score, winner, rounds, end_reason
+                 FROM matches WHERE game_id = ? ORDER BY id",
+            )
+            .bind(game_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(
+                    game_id,
+                    mode,
+                    player1,
+                    player2,
+                    seed,
+                    player1_score,
+                    player2_score,
+                    winner,
+                    rounds,
+                    end_reason,
+                )| {
+                    MatchRecord {
+                        game_id,
+                        mode,
+                        player1,
+                        player2,
+                        seed: seed as u64,
+                        player1_score,
+                        player2_score,
+                        winner,
+                        rounds,
+                        reason: end_reason
+                            .as_deref()
+                            .and_then(GameEndReason::from_name)
+                            .unwrap_or_default(),
+                    }
+                },
+            )
+            .collect())
+    }
+}
@@ -1,8 +1,78 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use ts_rs::TS;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// Current version of the WebSocket protocol
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest protocol version the server still accepts
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// Game modes served, one WebSocket endpoint each
+pub const SUPPORTED_MODES: &[&str] = &["ephemeral", "matchmaking", "daily", "ghost"];
+
+/// Optional protocol features a client can opt into with `hello`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Receive `player_latency` heartbeat updates
+    Latency,
+    /// Receive `resume_token`s for rejoining a game after a server restart
+    Resume,
+}
+
+impl Capability {
+    /// Parse a capability name, returning None for ones this server doesn't know
+    pub fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+}
+
+/// Why a match ended, sent with `game_end`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum GameEndReason {
+    /// A player reached the winning score. Replays recorded before reasons
+    /// were kept read as this.
+    #[default]
+    Score,
+    /// The round limit was reached; the higher score wins, equal scores draw
+    MaxRounds,
+    /// A player left the match and didn't come back; the other one wins
+    Forfeit,
+    /// Both players agreed to a draw
+    DrawAgreed,
+    /// An operator ended the game without a winner
+    Aborted,
+}
+
+impl GameEndReason {
+    /// Name on the wire and in the `matches` table
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameEndReason::Score => "score",
+            GameEndReason::MaxRounds => "max_rounds",
+            GameEndReason::Forfeit => "forfeit",
+            GameEndReason::DrawAgreed => "draw_agreed",
+            GameEndReason::Aborted => "aborted",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, JsonSchema, TS)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
+    // Handshake (optional; clients that skip it are treated as version 1
+    // without optional capabilities)
+    Hello {
+        protocol_version: u32,
+        #[serde(default)]
+        #[ts(as = "Option<Vec<String>>", optional)]
+        capabilities: Vec<String>,
+    },
+
     // Authenticated matchmaking
     Join {
         user_id: String,
@@ -11,23 +81,212 @@ pub enum ClientMessage {
     // Ephemeral create/join
     CreateGame {
         player_name: String,
+        /// Word sequence seed, to replay a previous game's words. Random if omitted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        seed: Option<u64>,
     },
     JoinGame {
         game_id: String,
         player_name: String,
     },
 
+    // Daily challenge
+    StartDaily {
+        player_name: String,
+    },
+
+    // Ghost challenges: record a run, or play against someone's recorded run
+    CreateChallenge {
+        player_name: String,
+    },
+    AcceptChallenge {
+        challenge_id: String,
+        player_name: String,
+    },
+
+    // Ephemeral and matchmaking: rejoin a game restored after a server restart
+    Resume {
+        token: String,
+    },
+
     // Shared
     Answer {
         answer: String,
+        /// Round the answer is meant for. Answers for a round that already
+        /// ended get `stale_answer`; untagged answers count for the current round.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        round: Option<u32>,
     },
     Skip,
     RequestRematch,
+    /// Ask for the current state, e.g. after the tab slept. Two-player games
+    /// answer with `game_state`; solo runs send the current `round_start` again.
+    Sync,
+
+    // Ephemeral games between friends: stop the clock, and carry on once both agree
+    Pause,
+    Unpause,
+
+    // Two-player games: end the match as a draw once both players agree
+    OfferDraw,
+    AcceptDraw,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+impl ClientMessage {
+    /// Whether this message starts a new game or run, which the server
+    /// refuses while draining for a restart
+    pub fn starts_new_game(&self) -> bool {
+        matches!(
+            self,
+            ClientMessage::Join { .. }
+                | ClientMessage::CreateGame { .. }
+                | ClientMessage::JoinGame { .. }
+                | ClientMessage::StartDaily { .. }
+                | ClientMessage::CreateChallenge { .. }
+                | ClientMessage::AcceptChallenge { .. }
+                | ClientMessage::RequestRematch
+        )
+    }
+
+    /// Whether this message adds a game or run to the server, which it
+    /// refuses when full. Joining a pending game or a rematch reuses one.
+    pub fn creates_game(&self) -> bool {
+        matches!(
+            self,
+            ClientMessage::Join { .. }
+                | ClientMessage::CreateGame { .. }
+                | ClientMessage::StartDaily { .. }
+                | ClientMessage::CreateChallenge { .. }
+                | ClientMessage::AcceptChallenge { .. }
+        )
+    }
+}
+
+/// Wire format for client messages: the message itself plus an optional
+/// `request_id` that is echoed back on any error the message causes
+#[derive(Debug, Deserialize, PartialEq, JsonSchema, TS)]
+pub struct ClientEnvelope {
+    #[serde(default)]
+    #[ts(optional)]
+    pub request_id: Option<String>,
+    #[serde(flatten)]
+    pub message: ClientMessage,
+}
+
+/// Machine-readable reason attached to `ServerMessage::Error`.
+/// The serialized names are part of the protocol and must stay stable.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, JsonSchema, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Message could not be parsed
+    BadRequest,
+    /// Game action sent while not in a game
+    NotInGame,
+    /// Matchmaking action sent before `join`
+    Unauthenticated,
+    RateLimited,
+    /// Message belongs to the other WebSocket endpoint
+    WrongEndpoint,
+    /// Client's `hello` asked for a protocol version the server can't speak
+    UnsupportedProtocolVersion,
+    InvalidName,
+    InvalidGameCode,
+    /// Matchmaking `join` with an empty, overlong or malformed user id
+    InvalidUserId,
+    InvalidAnswer,
+    InvalidSeed,
+    /// The player already has a result for today's daily challenge
+    AlreadyPlayed,
+    /// Something failed on the server side
+    Internal,
+    /// `resume` token doesn't match a game waiting for this player
+    InvalidResumeToken,
+    /// The server is draining for a restart, or an operator turned on
+    /// maintenance, and starts no new games
+    Maintenance,
+    /// An operator closed this connection
+    Kicked,
+    /// The client's address already has as many connections open as allowed
+    TooManyConnections,
+    /// The server is at its limit of games and starts no new ones for now
+    ServerFull,
+    /// `pause` sent outside a casual game between friends
+    PauseNotAllowed,
+    /// The player left too many ranked matches recently and has to wait
+    /// before queueing again
+    QueueCooldown,
+    /// Another player is queued or playing under this name
+    NameTaken,
+    /// `answer` or `skip` sent while the game is paused or waiting for a
+    /// player to reconnect
+    GamePaused,
+    /// `offer_draw` or `accept_draw` sent outside a running two-player match
+    DrawNotAllowed,
+    /// `accept_draw` sent while the opponent has no draw offer standing
+    NoDrawOffer,
+}
+
+impl ErrorCode {
+    /// Default human-readable text for this code
+    pub fn description(&self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "Malformed message",
+            ErrorCode::NotInGame => "You are not in a game",
+            ErrorCode::Unauthenticated => "Join matchmaking first",
+            ErrorCode::RateLimited => "Too many requests",
+            ErrorCode::WrongEndpoint => "Message not supported on this endpoint",
+            ErrorCode::UnsupportedProtocolVersion => "Unsupported protocol version",
+            ErrorCode::InvalidName => "Invalid name",
+            ErrorCode::InvalidGameCode => "Invalid game code",
+            ErrorCode::InvalidUserId => "Invalid user id",
+            ErrorCode::InvalidAnswer => "Invalid answer",
+            ErrorCode::InvalidSeed => "Invalid seed",
+            ErrorCode::AlreadyPlayed => "You have already played today's challenge",
+            ErrorCode::Internal => "Internal server error",
+            ErrorCode::InvalidResumeToken => "Unknown or expired resume token",
+            ErrorCode::Maintenance => "The server is under maintenance, try again in a few minutes",
+            ErrorCode::Kicked => "You were disconnected by an operator",
+            ErrorCode::TooManyConnections => "Too many connections from your address",
+            ErrorCode::ServerFull => "The server is full, try again in a few minutes",
+            ErrorCode::PauseNotAllowed => "Only casual games can be paused",
+            ErrorCode::QueueCooldown => "You left too many matches recently, try again later",
+            ErrorCode::NameTaken => "That name is already in use",
+            ErrorCode::GamePaused => "The game is paused, answers count again once it carries on",
+            ErrorCode::DrawNotAllowed => "Draws can only be agreed during a two-player match",
+            ErrorCode::NoDrawOffer => "Your opponent hasn't offered a draw",
+        }
+    }
+}
+
+/// The round in progress, as one player sees it in `game_state`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema, TS)]
+pub struct RoundState {
+    pub round: u32,
+    pub kanji: String,
+    pub readings: Vec<String>,
+    /// Time left to answer
+    pub remaining_ms: u32,
+    pub you_skipped: bool,
+    pub opponent_skipped: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema, TS)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ServerMessage {
+    // Handshake
+    Welcome {
+        protocol_version: u32,
+        min_protocol_version: u32,
+        /// Mode served by this endpoint
+        mode: String,
+        /// All modes offered by the server
+        modes: Vec<String>,
+        /// Capabilities enabled for this connection
+        capabilities: Vec<Capability>,
+    },
+
     // Authenticated matchmaking
     Waiting,
 
@@ -42,9 +301,41 @@ pub enum ServerMessage {
     GameFull,
     GameNotFound,
 
+    // Daily challenge; rounds then use the shared round messages
+    DailyStart {
+        /// UTC date of the challenge, YYYY-MM-DD
+        date: String,
+        rounds: u32,
+    },
+    DailyEnd {
+        correct: u32,
+        #[ts(type = "number")]
+        time_ms: u64,
+        /// Position on today's leaderboard
+        rank: u32,
+    },
+
+    // Ghost challenges; rounds then use the shared round messages, so the
+    // words are only revealed one round at a time
+    ChallengeStart {
+        rounds: u32,
+        /// Name of the recorded player being raced, None while recording
+        ghost: Option<String>,
+    },
+    /// The recorded run was saved; others can now play it with this code
+    ChallengeCreated {
+        challenge_id: String,
+        correct: u32,
+        #[ts(type = "number")]
+        time_ms: u64,
+    },
+
     // Shared game flow
     GameStart {
         opponent: String,
+        /// Seed the game's words are drawn from
+        #[ts(type = "number")]
+        seed: u64,
     },
     RoundStart {
         kanji: String,
@@ -56,18 +347,146 @@ pub enum ServerMessage {
         correct_reading: String,
     },
     WrongAnswer,
+    /// The answer targeted a round that has already ended
+    StaleAnswer {
+        round: u32,
+    },
     SkipWaiting,
     RematchWaiting,
     OpponentDisconnected,
+    /// The opponent's connection dropped or came back. While they are away the
+    /// round timer is stopped; the game ends if they aren't back within
+    /// `grace_remaining` seconds.
+    OpponentConnectionChanged {
+        connected: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        grace_remaining: Option<u32>,
+    },
+    /// `player` paused the game; the round timer is stopped until both
+    /// players send `unpause`
+    GamePaused {
+        player: String,
+    },
+    /// `player` wants to carry on; waiting for the other player's `unpause`
+    UnpauseRequested {
+        player: String,
+    },
+    /// Both players agreed to carry on; the round timer runs again once both
+    /// are connected
+    GameUnpaused {
+        remaining_ms: u32,
+    },
+    /// `player` offered a draw. It stands until the round ends; the opponent
+    /// takes it with `accept_draw` (or `offer_draw`).
+    DrawOffered {
+        player: String,
+    },
+    /// Heartbeat round-trip time of one of the players
+    PlayerLatency {
+        player: String,
+        latency_ms: u32,
+    },
+    /// The match is over; `winner` is None for a draw
     GameEnd {
         winner: Option<String>,
+        reason: GameEndReason,
+    },
+    /// Answer to `sync`: everything needed to redraw a two-player game
+    GameState {
+        game_id: String,
+        opponent: String,
+        your_score: u32,
+        opponent_score: u32,
+        /// False once the match is over (see the rematch votes) or while a
+        /// restored game waits for its players
+        match_running: bool,
+        /// None between rounds
+        round: Option<RoundState>,
+        /// Whether the round timer is stopped by a pause or an absent player
+        paused: bool,
+        you_want_rematch: bool,
+        opponent_wants_rematch: bool,
+        /// Whether a draw offer stands, and from whom
+        you_offered_draw: bool,
+        opponent_offered_draw: bool,
+        opponent_connected: bool,
+    },
+
+    // Restarts
+    /// Secret for rejoining this game with `resume` if the server restarts
+    ResumeToken {
+        token: String,
+    },
+    /// Rejoined a game after a restart or a dropped connection. After a
+    /// restart the round in progress starts over once both players are back;
+    /// mid-match a `game_state` follows.
+    Resumed {
+        game_id: String,
+        opponent: String,
+        #[ts(type = "number")]
+        seed: u64,
+        your_score: u32,
+        opponent_score: u32,
+    },
+    /// The server restarts soon. No new games start; games still running
+    /// after `seconds_left` are saved and can be resumed after the restart.
+    ServerDraining {
+        seconds_left: u32,
+    },
+    /// The server is going down; games can be resumed once it is back
+    ServerShutdown,
+    /// A notice from the operators, sent to everyone connected
+    Announcement {
+        message: String,
+    },
+
+    // Backpressure
+    /// The client fell behind and `dropped` messages to it were skipped. The
+    /// current state follows, as for `sync`.
+    Resync {
+        dropped: u32,
     },
-    #[allow(dead_code)]
+
     Error {
+        code: ErrorCode,
         message: String,
+        /// `request_id` of the client message that caused this error, if it had one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        request_id: Option<String>,
     },
 }
 
+impl ServerMessage {
+    /// Capability a client must have negotiated to receive this message
+    pub fn required_capability(&self) -> Option<Capability> {
+        match self {
+            ServerMessage::PlayerLatency { .. } => Some(Capability::Latency),
+            ServerMessage::ResumeToken { .. } => Some(Capability::Resume),
+            _ => None,
+        }
+    }
+
+    /// Whether this message may be skipped for a client that has fallen
+    /// behind: transient feedback, errors (a client flooding requests it
+    /// doesn't read replies to gets no more), or a round start that a resync
+    /// sends again
+    pub fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            ServerMessage::RoundStart { .. }
+                | ServerMessage::WrongAnswer
+                | ServerMessage::StaleAnswer { .. }
+                | ServerMessage::SkipWaiting
+                | ServerMessage::RematchWaiting
+                | ServerMessage::PlayerLatency { .. }
+                | ServerMessage::Announcement { .. }
+                | ServerMessage::Error { .. }
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,7 +498,8 @@ mod tests {
         assert_eq!(
             msg,
             ClientMessage::CreateGame {
-                player_name: "Alice".to_string()
+                player_name: "Alice".to_string(),
+                seed: None,
             }
         );
     }
@@ -97,6 +517,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn deserialize_envelope_with_request_id() {
+        let json = r#"{"type": "answer", "answer": "にほん", "request_id": "r1"}"#;
+        let envelope: ClientEnvelope = serde_json::from_str(json).unwrap();
+        assert_eq!(envelope.request_id, Some("r1".to_string()));
+        assert_eq!(
+            envelope.message,
+            ClientMessage::Answer {
+                answer: "にほん".to_string(),
+                round: None,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_answer_with_round() {
+        let json = r#"{"type": "answer", "answer": "にほん", "round": 3}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            msg,
+            ClientMessage::Answer {
+                answer: "にほん".to_string(),
+                round: Some(3),
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_envelope_without_request_id() {
+        let json = r#"{"type": "skip"}"#;
+        let envelope: ClientEnvelope = serde_json::from_str(json).unwrap();
+        assert_eq!(envelope.request_id, None);
+        assert_eq!(envelope.message, ClientMessage::Skip);
+    }
+
+    #[test]
+    fn serialize_error_with_request_id() {
+        let msg = ServerMessage::Error {
+            code: ErrorCode::NotInGame,
+            message: "You are not in a game".to_string(),
+            request_id: Some("r1".to_string()),
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.contains(r#""code":"not_in_game""#));
+        assert!(json.contains(r#""request_id":"r1""#));
+    }
+
+    #[test]
+    fn deserialize_hello_with_capabilities() {
+        let json = r#"{"type": "hello", "protocol_version": 1, "capabilities": ["latency", "future"]}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            msg,
+            ClientMessage::Hello {
+                protocol_version: 1,
+                capabilities: vec!["latency".to_string(), "future".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn capability_names_round_trip() {
+        assert_eq!(Capability::from_name("latency"), Some(Capability::Latency));
+        assert_eq!(Capability::from_name("future"), None);
+    }
+
     #[test]
     fn serialize_game_created() {
         let msg = ServerMessage::GameCreated {
@@ -121,10 +607,42 @@ mod tests {
         assert_eq!(json, r#"{"type":"game_full"}"#);
     }
 
+    #[test]
+    fn serialize_error_with_code() {
+        let msg = ServerMessage::Error {
+            code: ErrorCode::InvalidGameCode,
+            message: "bad code".to_string(),
+            request_id: None,
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"error","code":"invalid_game_code","message":"bad code"}"#
+        );
+    }
+
     #[test]
     fn serialize_game_not_found() {
         let msg = ServerMessage::GameNotFound;
         let json = serde_json::to_string(&msg).unwrap();
         assert_eq!(json, r#"{"type":"game_not_found"}"#);
     }
+
+    #[test]
+    fn round_results_and_game_ends_are_never_droppable() {
+        let result = ServerMessage::RoundResult {
+            winner: None,
+            correct_reading: "やま".to_string(),
+        };
+        assert!(!result.is_droppable());
+        assert!(
+            !ServerMessage::GameEnd {
+                winner: None,
+                reason: GameEndReason::Forfeit,
+            }
+            .is_droppable()
+        );
+        assert!(!ServerMessage::Resync { dropped: 1 }.is_droppable());
+        assert!(ServerMessage::WrongAnswer.is_droppable());
+    }
 }
@@ -1,7 +1,23 @@
+mod daily_repository;
+pub mod event_log;
+mod game_log_repository;
+mod ghost_repository;
+mod match_repository;
 pub mod messages;
 pub mod session;
+mod snapshot_repository;
+pub mod solo;
+pub mod validation;
 mod word;
 mod word_repository;
+pub mod word_sequence;
 
-pub use word::Word;
-pub use word_repository::WordRepository;
+pub use daily_repository::{DailyRepository, DailyResult};
+pub use game_log_repository::{GameLog, GameLogRepository, GameLogSummary};
+pub use ghost_repository::{GhostChallenge, GhostRepository, GhostResult};
+pub use match_repository::{MatchRecord, MatchRepository};
+pub use snapshot_repository::{
+    GameProgress, GameSnapshot, ModeSnapshot, PendingSnapshot, RoundSnapshot, SnapshotRepository,
+};
+pub use word::{FrequencyBand, Word};
+pub use word_repository::{WordEntry, WordRepository};
@@ -32,8 +32,6 @@ pub enum SkipResult {
     BothSkipped(RoundOutcome),
 }
 
-const WINS_NEEDED: u32 = 10;
-
 /// A game session between two players (pure logic, no I/O)
 pub struct GameSession {
     pub player1: String,
@@ -56,6 +54,14 @@ impl GameSession {
         }
     }
 
+    /// A session part-way through a match, as saved before a restart
+    pub fn restore(player1: String, player2: String, scores: (u32, u32)) -> Self {
+        Self {
+            scores,
+            ..Self::new(player1, player2)
+        }
+    }
+
     pub fn scores(&self) -> (u32, u32) {
         self.scores
     }
@@ -68,10 +74,11 @@ impl GameSession {
         }
     }
 
-    pub fn game_winner(&self) -> Option<&str> {
+    /// The player who has reached `wins_needed`, if either has
+    pub fn game_winner(&self, wins_needed: u32) -> Option<&str> {
         match self.scores {
-            (p1, _) if p1 >= WINS_NEEDED => Some(&self.player1),
-            (_, p2) if p2 >= WINS_NEEDED => Some(&self.player2),
+            (p1, _) if p1 >= wins_needed => Some(&self.player1),
+            (_, p2) if p2 >= wins_needed => Some(&self.player2),
             _ => None,
         }
     }
@@ -161,11 +168,26 @@ impl GameSession {
         }
     }
 
+    /// The round in progress, if any
+    pub fn current_round(&self) -> Option<&Round> {
+        self.current_round.as_ref()
+    }
+
+    /// Whether (player1, player2) have asked for a rematch
+    pub fn rematch_votes(&self) -> (bool, bool) {
+        (self.player1_wants_rematch, self.player2_wants_rematch)
+    }
+
     /// Get the current round number, if any
     pub fn current_round_number(&self) -> Option<u32> {
         self.current_round.as_ref().map(|r| r.number)
     }
 
+    /// Get the current word, if there's an active round
+    pub fn current_word(&self) -> Option<&Word> {
+        self.current_round.as_ref().map(|r| &r.word)
+    }
+
     /// Get the current kanji being tested, if there's an active round
     pub fn current_kanji(&self) -> Option<&str> {
         self.current_round.as_ref().map(|r| r.word.kanji.as_str())
@@ -309,23 +331,35 @@ mod tests {
         let mut session = GameSession::new("alice".to_string(), "bob".to_string());
 
         assert_eq!(session.scores(), (0, 0));
-        assert_eq!(session.game_winner(), None);
+        assert_eq!(session.game_winner(10), None);
 
         // Record 9 wins for alice - should not trigger game end yet
         for i in 1..=9 {
             session.record_win("alice");
             assert_eq!(session.scores(), (i, 0));
-            assert_eq!(session.game_winner(), None);
+            assert_eq!(session.game_winner(10), None);
         }
 
         // Bob gets some wins but alice is still ahead
         session.record_win("bob");
         assert_eq!(session.scores(), (9, 1));
-        assert_eq!(session.game_winner(), None);
+        assert_eq!(session.game_winner(10), None);
 
         // 10th win for alice triggers game end
         session.record_win("alice");
         assert_eq!(session.scores(), (10, 1));
-        assert_eq!(session.game_winner(), Some("alice"));
+        assert_eq!(session.game_winner(10), Some("alice"));
+    }
+
+    #[test]
+    fn test_rematch_votes_are_tracked_until_reset() {
+        let mut session = GameSession::new("alice".to_string(), "bob".to_string());
+        assert_eq!(session.rematch_votes(), (false, false));
+
+        assert_eq!(session.request_rematch("bob"), Some(false));
+        assert_eq!(session.rematch_votes(), (false, true));
+
+        session.reset_for_rematch();
+        assert_eq!(session.rematch_votes(), (false, false));
     }
 }
@@ -0,0 +1,118 @@
+use super::word::Word;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+/// A round that was in progress when a game was saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundSnapshot {
+    pub number: u32,
+    pub word: Word,
+}
+
+/// How far a game has got, as reported by its task
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameProgress {
+    pub scores: (u32, u32),
+    pub seed: u64,
+    /// Position of the game's word sequence
+    pub sequence_state: u64,
+    /// Round in progress, restarted from the beginning on restore
+    pub round: Option<RoundSnapshot>,
+}
+
+/// A running game, with what its players need to rejoin it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub game_id: String,
+    pub player1: String,
+    pub player2: String,
+    pub player1_token: String,
+    pub player2_token: String,
+    #[serde(flatten)]
+    pub progress: GameProgress,
+}
+
+/// An ephemeral game waiting for its guest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingSnapshot {
+    pub game_id: String,
+    pub host: String,
+    pub host_token: String,
+    pub seed: u64,
+    /// How long the game had been waiting when it was saved
+    #[serde(default)]
+    pub age_ms: u64,
+}
+
+/// Everything saved for one game mode
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModeSnapshot {
+    pub games: Vec<GameSnapshot>,
+    pub pending: Vec<PendingSnapshot>,
+}
+
+#[derive(Clone)]
+pub struct SnapshotRepository {
+    pool: SqlitePool,
+}
+
+impl SnapshotRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Replace a mode's saved games with `snapshot`
+    pub async fn save(&self, mode: &str, snapshot: &ModeSnapshot) -> Result<(), sqlx::Error> {
+        let mut rows = Vec::new();
+        for game in &snapshot.games {
+            rows.push(("game", &game.game_id, to_json(game)?));
+        }
+        for pending in &snapshot.pending {
+            rows.push(("pending", &pending.game_id, to_json(pending)?));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM game_snapshots WHERE mode = ?")
+            .bind(mode)
+            .execute(&mut *tx)
+            .await?;
+        for (kind, game_id, data) in rows {
+            sqlx::query(
+                "INSERT INTO game_snapshots (mode, kind, game_id, data) VALUES (?, ?, ?, ?)",
+            )
+            .bind(mode)
+            .bind(kind)
+            .bind(game_id)
+            .bind(data)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn load(&self, mode: &str) -> Result<ModeSnapshot, sqlx::Error> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT kind, data FROM game_snapshots WHERE mode = ?")
+                .bind(mode)
+                .fetch_all(&self.pool)
+                .await?;
+
+        let mut snapshot = ModeSnapshot::default();
+        for (kind, data) in rows {
+            match kind.as_str() {
+                "game" => snapshot.games.push(from_json(&data)?),
+                "pending" => snapshot.pending.push(from_json(&data)?),
+                _ => {}
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+fn to_json(value: &impl Serialize) -> Result<String, sqlx::Error> {
+    serde_json::to_string(value).map_err(|e| sqlx::Error::Encode(Box::new(e)))
+}
+
+fn from_json<T: for<'de> Deserialize<'de>>(data: &str) -> Result<T, sqlx::Error> {
+    serde_json::from_str(data).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
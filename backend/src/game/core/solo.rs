@@ -0,0 +1,81 @@
+/// How one round of a solo run went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundRecord {
+    pub correct: bool,
+    /// Time from the round starting to it ending for this player
+    pub time_ms: u64,
+}
+
+/// Per-round results of one solo run, in round order.
+/// A run that was left early has fewer rounds than it was meant to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunRecord {
+    pub rounds: Vec<RoundRecord>,
+}
+
+impl RunRecord {
+    pub fn correct(&self) -> u32 {
+        self.rounds.iter().filter(|r| r.correct).count() as u32
+    }
+
+    pub fn time_ms(&self) -> u64 {
+        self.rounds.iter().map(|r| r.time_ms).sum()
+    }
+
+    /// Round wins of this run and of `ghost` over `total_rounds` rounds, as in a
+    /// live game: each round goes to whoever answered correctly first.
+    /// Rounds missing from either record count as unanswered.
+    pub fn score_against(&self, ghost: &RunRecord, total_rounds: u32) -> (u32, u32) {
+        let mut scores = (0, 0);
+        for i in 0..total_rounds as usize {
+            let mine = self.rounds.get(i).filter(|r| r.correct);
+            let theirs = ghost.rounds.get(i).filter(|r| r.correct);
+            match (mine, theirs) {
+                (Some(mine), Some(theirs)) if mine.time_ms < theirs.time_ms => scores.0 += 1,
+                (Some(_), None) => scores.0 += 1,
+                (_, Some(_)) => scores.1 += 1,
+                (None, None) => {}
+            }
+        }
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(rounds: &[(bool, u64)]) -> RunRecord {
+        RunRecord {
+            rounds: rounds
+                .iter()
+                .map(|&(correct, time_ms)| RoundRecord { correct, time_ms })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn totals_count_correct_rounds_and_all_time() {
+        let run = record(&[(true, 1200), (false, 5000), (true, 800)]);
+        assert_eq!(run.correct(), 2);
+        assert_eq!(run.time_ms(), 7000);
+    }
+
+    #[test]
+    fn faster_correct_answer_wins_the_round() {
+        let ghost = record(&[(true, 2000), (true, 2000), (false, 3000), (true, 500)]);
+        let run = record(&[(true, 1500), (false, 2000), (true, 4000), (false, 500)]);
+        assert_eq!(run.score_against(&ghost, 4), (2, 2));
+    }
+
+    #[test]
+    fn unplayed_rounds_count_as_unanswered() {
+        let ghost = record(&[(true, 1000), (true, 1000), (true, 1000)]);
+        let left_early = record(&[(true, 500)]);
+        assert_eq!(left_early.score_against(&ghost, 3), (1, 2));
+
+        let short_ghost = record(&[(true, 1000)]);
+        let run = record(&[(false, 1000), (true, 9000), (true, 9000)]);
+        assert_eq!(run.score_against(&short_ghost, 3), (2, 1));
+    }
+}
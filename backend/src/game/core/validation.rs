@@ -0,0 +1,338 @@
+use super::messages::ErrorCode;
+use super::word_sequence::MAX_SEED;
+use crate::game::ephemeral::game_id;
+use std::fmt;
+use unicode_normalization::UnicodeNormalization;
+
+pub const MAX_NAME_CHARS: usize = 20;
+pub const MAX_ANSWER_CHARS: usize = 32;
+pub const MAX_USER_ID_CHARS: usize = 64;
+
+/// Punctuation allowed in matchmaking user ids besides ASCII letters and digits
+const USER_ID_PUNCTUATION: &[u8] = b"-_.:@";
+
+/// Punctuation allowed in player names besides letters, digits and spaces
+const NAME_PUNCTUATION: &[char] = &['-', '_', '.', '\''];
+
+/// Words rejected in player names. Matched against whole words of the name,
+/// so names that merely contain one ("Scunthorpe", "Matsushita") are fine.
+const BLOCKED_NAME_WORDS: &[&str] = &[
+    "fuck",
+    "shit",
+    "cunt",
+    "bitch",
+    "whore",
+    "nazi",
+    "hitler",
+    "ちんこ",
+    "まんこ",
+];
+
+/// Reason a piece of client input was rejected
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    NameEmpty,
+    NameTooLong,
+    NameInvalidCharacters,
+    NameNotAllowed,
+    GameCodeInvalid,
+    UserIdInvalid,
+    AnswerEmpty,
+    AnswerTooLong,
+    SeedTooLarge,
+}
+
+impl ValidationError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ValidationError::NameEmpty
+            | ValidationError::NameTooLong
+            | ValidationError::NameInvalidCharacters
+            | ValidationError::NameNotAllowed => ErrorCode::InvalidName,
+            ValidationError::GameCodeInvalid => ErrorCode::InvalidGameCode,
+            ValidationError::UserIdInvalid => ErrorCode::InvalidUserId,
+            ValidationError::AnswerEmpty | ValidationError::AnswerTooLong => {
+                ErrorCode::InvalidAnswer
+            }
+            ValidationError::SeedTooLarge => ErrorCode::InvalidSeed,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NameEmpty => write!(f, "Name must not be empty"),
+            ValidationError::NameTooLong => {
+                write!(f, "Name must be at most {} characters", MAX_NAME_CHARS)
+            }
+            ValidationError::NameInvalidCharacters => write!(
+                f,
+                "Name may only contain letters, numbers, spaces and - _ . '"
+            ),
+            ValidationError::NameNotAllowed => write!(f, "Please choose a different name"),
+            ValidationError::GameCodeInvalid => write!(
+                f,
+                "Game code must be {} characters from {}",
+                game_id::ID_LENGTH,
+                String::from_utf8_lossy(game_id::CHARSET)
+            ),
+            ValidationError::UserIdInvalid => write!(
+                f,
+                "User id must be 1 to {} ASCII letters, digits or {}",
+                MAX_USER_ID_CHARS,
+                String::from_utf8_lossy(USER_ID_PUNCTUATION)
+            ),
+            ValidationError::AnswerEmpty => write!(f, "Answer must not be empty"),
+            ValidationError::AnswerTooLong => {
+                write!(f, "Answer must be at most {} characters", MAX_ANSWER_CHARS)
+            }
+            ValidationError::SeedTooLarge => write!(f, "Seed must be at most {}", MAX_SEED),
+        }
+    }
+}
+
+/// Normalize (NFKC, trimmed, single spaces) and validate a display name
+pub fn validate_player_name(raw: &str) -> Result<String, ValidationError> {
+    let normalized: String = raw.nfkc().collect();
+    let name = normalized.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if name.is_empty() {
+        return Err(ValidationError::NameEmpty);
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(ValidationError::NameTooLong);
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || NAME_PUNCTUATION.contains(&c))
+    {
+        return Err(ValidationError::NameInvalidCharacters);
+    }
+
+    if has_blocked_word(&name) {
+        return Err(ValidationError::NameNotAllowed);
+    }
+
+    Ok(name)
+}
+
+/// Lowercase a game code and check it could have come from `generate_game_id`
+pub fn validate_game_code(raw: &str) -> Result<String, ValidationError> {
+    let code = raw.trim().to_ascii_lowercase();
+
+    let valid =
+        code.len() == game_id::ID_LENGTH && code.bytes().all(|b| game_id::CHARSET.contains(&b));
+
+    if valid {
+        Ok(code)
+    } else {
+        Err(ValidationError::GameCodeInvalid)
+    }
+}
+
+/// Check a matchmaking user id. Ids are opaque keys, so unlike names they
+/// are taken as given: no normalization and no word filter.
+pub fn validate_user_id(raw: &str) -> Result<&str, ValidationError> {
+    let valid = (1..=MAX_USER_ID_CHARS).contains(&raw.len())
+        && raw
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || USER_ID_PUNCTUATION.contains(&b));
+
+    if valid {
+        Ok(raw)
+    } else {
+        Err(ValidationError::UserIdInvalid)
+    }
+}
+
+/// Normalize (NFKC, trimmed) and bound an answer before it reaches the database
+pub fn validate_answer(raw: &str) -> Result<String, ValidationError> {
+    let normalized: String = raw.nfkc().collect();
+    let answer = normalized.trim();
+
+    if answer.is_empty() {
+        return Err(ValidationError::AnswerEmpty);
+    }
+    if answer.chars().count() > MAX_ANSWER_CHARS {
+        return Err(ValidationError::AnswerTooLong);
+    }
+
+    Ok(answer.to_string())
+}
+
+/// Check a client-chosen seed fits in a JavaScript number
+pub fn validate_seed(seed: u64) -> Result<u64, ValidationError> {
+    if seed <= MAX_SEED {
+        Ok(seed)
+    } else {
+        Err(ValidationError::SeedTooLarge)
+    }
+}
+
+/// Whether any word of the name is on the blocklist. Letters spelled out one
+/// word at a time ("F.u_c k") are read as a single word.
+fn has_blocked_word(name: &str) -> bool {
+    let words: Vec<String> = name_words(name)
+        .iter()
+        .map(|word| name_skeleton(word))
+        .collect();
+    let single_letter = |word: &String| word.chars().count() == 1;
+    words
+        .chunk_by(|a, b| single_letter(a) && single_letter(b))
+        .map(|run| run.concat())
+        .any(|word| BLOCKED_NAME_WORDS.contains(&word.as_str()))
+}
+
+/// Split a name at spaces, punctuation and lower-to-upper case changes
+/// ("BigShit" is two words)
+fn name_words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        let separator = c == ' ' || NAME_PUNCTUATION.contains(&c);
+        if (separator || (previous_lower && c.is_uppercase())) && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        if !separator {
+            current.push(c);
+        }
+        previous_lower = c.is_lowercase();
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Lowercase and undo common digit substitutions, so "sh1t" is caught by
+/// the blocklist
+fn name_skeleton(word: &str) -> String {
+    word.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            '0' => 'o',
+            '1' => 'i',
+            '3' => 'e',
+            '4' => 'a',
+            '5' => 's',
+            '7' => 't',
+            _ => c,
+        })
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_and_normalizes_names() {
+        assert_eq!(validate_player_name("  Alice  "), Ok("Alice".to_string()));
+        assert_eq!(
+            validate_player_name("Mary   Jane"),
+            Ok("Mary Jane".to_string())
+        );
+        assert_eq!(validate_player_name("たろう"), Ok("たろう".to_string()));
+        // Full-width letters fold to ASCII under NFKC
+        assert_eq!(validate_player_name("Ｂｏｂ"), Ok("Bob".to_string()));
+    }
+
+    #[test]
+    fn rejects_empty_and_long_names() {
+        assert_eq!(validate_player_name("   "), Err(ValidationError::NameEmpty));
+        assert_eq!(
+            validate_player_name(&"a".repeat(MAX_NAME_CHARS + 1)),
+            Err(ValidationError::NameTooLong)
+        );
+    }
+
+    #[test]
+    fn rejects_markup_and_control_characters() {
+        assert_eq!(
+            validate_player_name("<img src=x>"),
+            Err(ValidationError::NameInvalidCharacters)
+        );
+        assert_eq!(
+            validate_player_name("Bob\u{200b}"),
+            Err(ValidationError::NameInvalidCharacters)
+        );
+    }
+
+    #[test]
+    fn rejects_blocked_words_with_obfuscation() {
+        assert_eq!(
+            validate_player_name("sh1t.head"),
+            Err(ValidationError::NameNotAllowed)
+        );
+        assert_eq!(
+            validate_player_name("ＦＵＣＫ"),
+            Err(ValidationError::NameNotAllowed)
+        );
+        for name in ["F.u_c k", "BigShit", "ちんこ"] {
+            assert_eq!(
+                validate_player_name(name),
+                Err(ValidationError::NameNotAllowed),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn blocked_words_inside_other_words_are_fine() {
+        for name in ["Scunthorpe", "Matsushita", "Nazir", "shitake fan", "a.shitty"] {
+            assert_eq!(validate_player_name(name), Ok(name.to_string()), "{name}");
+        }
+    }
+
+    #[test]
+    fn game_code_must_match_charset() {
+        assert_eq!(validate_game_code("abc234"), Ok("abc234".to_string()));
+        assert_eq!(validate_game_code(" ABC234 "), Ok("abc234".to_string()));
+        // 'l', '0' and '1' are excluded from the charset
+        assert_eq!(
+            validate_game_code("abc10l"),
+            Err(ValidationError::GameCodeInvalid)
+        );
+        assert_eq!(
+            validate_game_code("abc23"),
+            Err(ValidationError::GameCodeInvalid)
+        );
+    }
+
+    #[test]
+    fn user_ids_are_checked_but_not_rewritten() {
+        for id in [
+            "user-1",
+            "3f2b8c4e-9d1a-4e7b-8c2d-5a6f7e8d9c0b",
+            "sh1t.head",
+            "alice@example.com",
+        ] {
+            assert_eq!(validate_user_id(id), Ok(id), "{id}");
+        }
+        let too_long = "a".repeat(MAX_USER_ID_CHARS + 1);
+        for id in ["", " user-1", "ｕｓｅｒ", "<b>", too_long.as_str()] {
+            assert_eq!(validate_user_id(id), Err(ValidationError::UserIdInvalid), "{id}");
+        }
+    }
+
+    #[test]
+    fn seed_must_fit_in_a_javascript_number() {
+        assert_eq!(validate_seed(MAX_SEED), Ok(MAX_SEED));
+        assert_eq!(validate_seed(MAX_SEED + 1), Err(ValidationError::SeedTooLarge));
+    }
+
+    #[test]
+    fn answers_are_bounded_and_normalized() {
+        assert_eq!(validate_answer(" にほん "), Ok("にほん".to_string()));
+        // Decomposed dakuten composes to a single character
+        assert_eq!(validate_answer("か\u{3099}"), Ok("が".to_string()));
+        assert_eq!(validate_answer(""), Err(ValidationError::AnswerEmpty));
+        assert_eq!(
+            validate_answer(&"あ".repeat(MAX_ANSWER_CHARS + 1)),
+            Err(ValidationError::AnswerTooLong)
+        );
+    }
+}
@@ -1,7 +1,47 @@
+use serde::{Deserialize, Serialize};
+
 /// A Japanese word with kanji and reading
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Word {
     pub kanji: String,
     pub reading: String,
 }
+
+/// Difficulty band derived from a word's frequency rank (lower rank = more common)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrequencyBand {
+    /// Rank 1 to 5,000
+    Common,
+    /// Rank 5,001 to 20,000
+    Intermediate,
+    /// Rarer words, and words without a rank
+    Advanced,
+}
+
+impl FrequencyBand {
+    pub const ALL: [FrequencyBand; 3] = [
+        FrequencyBand::Common,
+        FrequencyBand::Intermediate,
+        FrequencyBand::Advanced,
+    ];
+
+    const COMMON_MAX_RANK: i64 = 5_000;
+    const INTERMEDIATE_MAX_RANK: i64 = 20_000;
+
+    pub fn for_rank(rank: Option<i64>) -> Self {
+        match rank {
+            Some(rank) if rank <= Self::COMMON_MAX_RANK => FrequencyBand::Common,
+            Some(rank) if rank <= Self::INTERMEDIATE_MAX_RANK => FrequencyBand::Intermediate,
+            _ => FrequencyBand::Advanced,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrequencyBand::Common => "common",
+            FrequencyBand::Intermediate => "intermediate",
+            FrequencyBand::Advanced => "advanced",
+        }
+    }
+}
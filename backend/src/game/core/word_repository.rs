@@ -1,48 +1,183 @@
-use super::word::Word;
+use super::word::{FrequencyBand, Word};
+use arc_swap::ArcSwap;
+use rand::Rng;
 use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
 
+/// A row of the `words` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordEntry {
+    pub id: i64,
+    pub kanji: String,
+    pub reading: String,
+    pub frequency_rank: Option<i64>,
+}
+
+impl WordEntry {
+    fn word(&self) -> Word {
+        Word {
+            kanji: self.kanji.clone(),
+            reading: self.reading.clone(),
+        }
+    }
+}
+
+/// Immutable snapshot of the word table, indexed for the lookups games need
+#[derive(Default)]
+struct WordIndex {
+    entries: Vec<WordEntry>,
+    by_id: HashMap<i64, usize>,
+    by_kanji: HashMap<String, Vec<usize>>,
+    by_band: HashMap<FrequencyBand, Vec<usize>>,
+}
+
+impl WordIndex {
+    fn build(entries: Vec<WordEntry>) -> Self {
+        let mut index = WordIndex::default();
+        for (i, entry) in entries.iter().enumerate() {
+            index.by_id.insert(entry.id, i);
+            index
+                .by_kanji
+                .entry(entry.kanji.clone())
+                .or_default()
+                .push(i);
+            index
+                .by_band
+                .entry(FrequencyBand::for_rank(entry.frequency_rank))
+                .or_default()
+                .push(i);
+        }
+        index.entries = entries;
+        index
+    }
+
+    fn pick(&self, candidates: &[usize]) -> Option<&WordEntry> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let i = candidates[rand::rng().random_range(0..candidates.len())];
+        self.entries.get(i)
+    }
+}
+
+/// The word dictionary, held in memory. Loaded from SQLite at startup and
+/// swapped atomically on [`WordRepository::reload`], so lookups never lock
+/// or touch the database.
 #[derive(Clone)]
 pub struct WordRepository {
     pool: SqlitePool,
+    index: Arc<ArcSwap<WordIndex>>,
 }
 
 impl WordRepository {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    /// Load the whole word table into memory
+    pub async fn load(pool: SqlitePool) -> Result<Self, sqlx::Error> {
+        let repo = Self {
+            pool,
+            index: Arc::new(ArcSwap::from_pointee(WordIndex::default())),
+        };
+        repo.reload().await?;
+        Ok(repo)
     }
 
-    pub async fn get_random(&self) -> Option<Word> {
-        let row: (String, String) =
-            sqlx::query_as("SELECT kanji, reading FROM words ORDER BY RANDOM() LIMIT 1")
-                .fetch_optional(&self.pool)
-                .await
-                .ok()??;
+    /// Re-read the word table and swap in a fresh index.
+    /// Returns the number of words now loaded.
+    pub async fn reload(&self) -> Result<usize, sqlx::Error> {
+        let rows: Vec<(i64, String, String, Option<i64>)> =
+            sqlx::query_as("SELECT id, kanji, reading, frequency_rank FROM words ORDER BY id")
+                .fetch_all(&self.pool)
+                .await?;
 
-        Some(Word {
-            kanji: row.0,
-            reading: row.1,
-        })
+        let entries = rows
+            .into_iter()
+            .map(|(id, kanji, reading, frequency_rank)| WordEntry {
+                id,
+                kanji,
+                reading,
+                frequency_rank,
+            })
+            .collect();
+        let index = WordIndex::build(entries);
+        let count = index.entries.len();
+        self.index.store(Arc::new(index));
+
+        info!(count, "Loaded word index");
+        Ok(count)
+    }
+
+    /// Number of (kanji, reading) entries loaded
+    pub fn len(&self) -> usize {
+        self.index.load().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of entries in a frequency band
+    pub fn band_len(&self, band: FrequencyBand) -> usize {
+        self.index.load().by_band.get(&band).map_or(0, Vec::len)
+    }
+
+    /// A uniformly random entry from the whole dictionary
+    pub fn get_random(&self) -> Option<Word> {
+        let index = self.index.load();
+        if index.entries.is_empty() {
+            return None;
+        }
+        let i = rand::rng().random_range(0..index.entries.len());
+        Some(index.entries[i].word())
+    }
+
+    /// Entry at position `n` modulo the dictionary size, in id order.
+    /// Stable for a given dictionary, which makes seeded games reproducible.
+    pub fn get_nth(&self, n: u64) -> Option<Word> {
+        let index = self.index.load();
+        if index.entries.is_empty() {
+            return None;
+        }
+        let i = (n % index.entries.len() as u64) as usize;
+        Some(index.entries[i].word())
+    }
+
+    /// A uniformly random entry from one frequency band
+    pub fn get_random_in_band(&self, band: FrequencyBand) -> Option<Word> {
+        let index = self.index.load();
+        let candidates = index.by_band.get(&band)?;
+        index.pick(candidates).map(WordEntry::word)
+    }
+
+    pub fn get_by_id(&self, id: i64) -> Option<WordEntry> {
+        let index = self.index.load();
+        let i = *index.by_id.get(&id)?;
+        index.entries.get(i).cloned()
     }
 
     /// Check if the given reading is valid for the given kanji.
     /// Returns true if there's a word entry with this kanji/reading pair.
-    pub async fn is_valid_reading(&self, kanji: &str, reading: &str) -> bool {
-        sqlx::query_scalar::<_, i32>("SELECT 1 FROM words WHERE kanji = ? AND reading = ?")
-            .bind(kanji)
-            .bind(reading)
-            .fetch_optional(&self.pool)
-            .await
-            .ok()
-            .flatten()
-            .is_some()
+    pub fn is_valid_reading(&self, kanji: &str, reading: &str) -> bool {
+        let index = self.index.load();
+        index.by_kanji.get(kanji).is_some_and(|entries| {
+            entries
+                .iter()
+                .any(|&i| index.entries[i].reading == reading)
+        })
     }
 
     /// Get all valid readings for a given kanji.
-    pub async fn get_readings_for_kanji(&self, kanji: &str) -> Vec<String> {
-        sqlx::query_scalar::<_, String>("SELECT reading FROM words WHERE kanji = ?")
-            .bind(kanji)
-            .fetch_all(&self.pool)
-            .await
+    pub fn get_readings_for_kanji(&self, kanji: &str) -> Vec<String> {
+        let index = self.index.load();
+        index
+            .by_kanji
+            .get(kanji)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .map(|&i| index.entries[i].reading.clone())
+                    .collect()
+            })
             .unwrap_or_default()
     }
 }
@@ -0,0 +1,102 @@
+use super::word::Word;
+use super::word_repository::WordRepository;
+use rand::Rng;
+
+/// Largest accepted seed. Seeds travel as JSON numbers, which JavaScript
+/// only represents exactly up to 2^53 - 1.
+pub const MAX_SEED: u64 = (1 << 53) - 1;
+
+pub fn random_seed() -> u64 {
+    rand::rng().random_range(0..=MAX_SEED)
+}
+
+/// Deterministic stream of words derived from a seed, so a game's rounds can
+/// be reproduced. Uses SplitMix64 rather than a `rand` generator because its
+/// output must never change between dependency upgrades. The same seed gives
+/// the same words as long as the dictionary is unchanged.
+pub struct WordSequence {
+    seed: u64,
+    state: u64,
+}
+
+impl WordSequence {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Position in the stream, to continue it later with [`WordSequence::resume`]
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// Continue a sequence from a saved position
+    pub fn resume(seed: u64, state: u64) -> Self {
+        Self { seed, state }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Draw the next word. Returns None if the dictionary is empty.
+    pub fn next_word(&mut self, words: &WordRepository) -> Option<Word> {
+        let n = self.next_u64();
+        words.get_nth(n)
+    }
+
+    /// Seed for a follow-up game (a rematch), so the whole series is reproducible
+    pub fn next_seed(&mut self) -> u64 {
+        self.next_u64() & MAX_SEED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(seed: u64) -> Vec<u64> {
+        let mut sequence = WordSequence::new(seed);
+        (0..5).map(|_| sequence.next_u64()).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        assert_eq!(draws(42), draws(42));
+        assert_ne!(draws(42), draws(43));
+    }
+
+    #[test]
+    fn output_is_stable() {
+        // Reference SplitMix64 values; changing these breaks recorded games
+        let mut sequence = WordSequence::new(0);
+        assert_eq!(sequence.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(sequence.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+    }
+
+    #[test]
+    fn derived_seeds_are_in_range_and_deterministic() {
+        let mut a = WordSequence::new(7);
+        let mut b = WordSequence::new(7);
+        let seed = a.next_seed();
+        assert!(seed <= MAX_SEED);
+        assert_eq!(seed, b.next_seed());
+        assert!(random_seed() <= MAX_SEED);
+    }
+
+    #[test]
+    fn resumed_sequence_continues_where_it_left_off() {
+        let mut original = WordSequence::new(42);
+        original.next_u64();
+        let mut resumed = WordSequence::resume(original.seed(), original.state());
+        assert_eq!(resumed.seed(), 42);
+        assert_eq!(resumed.next_u64(), original.next_u64());
+    }
+}
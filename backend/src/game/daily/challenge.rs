@@ -0,0 +1,151 @@
+use crate::game::core::word_sequence::MAX_SEED;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Words in each daily challenge
+pub const DAILY_ROUNDS: u32 = 20;
+
+/// A UTC calendar day, stored as days since 1970-01-01
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChallengeDate(i64);
+
+impl ChallengeDate {
+    pub fn today() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self((secs / 86_400) as i64)
+    }
+
+    /// Parse a `YYYY-MM-DD` date
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.splitn(3, '-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        let year: i64 = year.parse().ok()?;
+        let month: u32 = month.parse().ok()?;
+        let day: u32 = day.parse().ok()?;
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Self(days_from_civil(year, month, day)))
+    }
+}
+
+/// Derives each day's seed from a server-side secret. Without the secret
+/// nobody can work out a day's seed, so a private game can't be opened on it
+/// to see the words before playing the challenge.
+#[derive(Clone)]
+pub struct DailySeeds {
+    secret: Arc<[u8]>,
+}
+
+impl DailySeeds {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.into(),
+        }
+    }
+
+    /// Seed of the day's word sequence, the same for every player
+    pub fn seed(&self, date: ChallengeDate) -> u64 {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .expect("HMAC takes keys of any length");
+        mac.update(date.to_string().as_bytes());
+        let digest = mac.finalize().into_bytes();
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        u64::from_le_bytes(bytes) & MAX_SEED
+    }
+}
+
+impl std::fmt::Display for ChallengeDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (year, month, day) = civil_from_days(self.0);
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between proleptic Gregorian dates and days since the Unix epoch,
+// after Howard Hinnant's `days_from_civil` / `civil_from_days`
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_round_trip() {
+        for s in ["1970-01-01", "2000-02-29", "2024-12-31", "2025-03-01"] {
+            assert_eq!(ChallengeDate::parse(s).unwrap().to_string(), s);
+        }
+        assert_eq!(ChallengeDate::parse("1970-01-02"), Some(ChallengeDate(1)));
+    }
+
+    #[test]
+    fn rejects_malformed_dates() {
+        for s in ["", "2024-1-01", "2024-13-01", "2023-02-29", "2024-04-31", "20240101", "yyyy-mm-dd"] {
+            assert_eq!(ChallengeDate::parse(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn seed_is_stable_per_day() {
+        let seeds = DailySeeds::new(b"secret");
+        let day = ChallengeDate::parse("2024-06-01").unwrap();
+        let next = ChallengeDate::parse("2024-06-02").unwrap();
+
+        assert_eq!(seeds.seed(day), DailySeeds::new(b"secret").seed(day));
+        assert_ne!(seeds.seed(day), seeds.seed(next));
+        assert!(seeds.seed(day) <= MAX_SEED);
+    }
+
+    #[test]
+    fn seed_depends_on_the_secret() {
+        let day = ChallengeDate::parse("2024-06-01").unwrap();
+        assert_ne!(
+            DailySeeds::new(b"secret").seed(day),
+            DailySeeds::new(b"other secret").seed(day)
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use ts_rs::TS;
+
+/// One player's result on a daily leaderboard
+#[derive(Debug, Serialize, JsonSchema, TS)]
+pub struct DailyLeaderboardEntry {
+    /// Equal results share a rank
+    pub rank: u32,
+    pub player_name: String,
+    pub correct: u32,
+    /// Total time spent on the challenge's rounds
+    #[ts(type = "number")]
+    pub time_ms: u64,
+}
+
+/// Best results of one day's challenge, best first
+#[derive(Debug, Serialize, JsonSchema, TS)]
+pub struct DailyLeaderboard {
+    /// UTC date of the challenge, YYYY-MM-DD
+    pub date: String,
+    /// Words in the challenge
+    pub rounds: u32,
+    pub entries: Vec<DailyLeaderboardEntry>,
+}
@@ -0,0 +1,9 @@
+mod challenge;
+pub mod leaderboard;
+mod state;
+mod ws_handler;
+
+pub use challenge::{ChallengeDate, DailySeeds};
+pub use leaderboard::DailyLeaderboard;
+pub use state::DailyState;
+pub use ws_handler::handle_connection;
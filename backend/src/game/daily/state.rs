@@ -0,0 +1,212 @@
+use super::challenge::{ChallengeDate, DAILY_ROUNDS, DailySeeds};
+use super::leaderboard::{DailyLeaderboard, DailyLeaderboardEntry};
+use crate::game::core::messages::{ErrorCode, ServerMessage};
+use crate::game::core::solo::RunRecord;
+use crate::game::core::{DailyRepository, DailyResult, WordRepository};
+use crate::game::engine::active_game::DEFAULT_ROUND_TIMEOUT;
+use crate::game::engine::solo_run::{RunCommand, RunHandle, SoloRun};
+use crate::game::engine::outbox::Outbox;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Most results returned by the leaderboard
+pub const LEADERBOARD_SIZE: u32 = 50;
+
+/// Daily challenge: every player gets the same date-seeded words, solo.
+/// Players are identified by name and get one attempt per day.
+pub struct DailyState {
+    pub words: WordRepository,
+    pub results: DailyRepository,
+    /// The seed is never sent to players, who would otherwise replay it in a
+    /// private game to see the words in advance
+    pub seeds: DailySeeds,
+    pub round_timeout: Duration,
+    /// Runs in progress, by player name
+    pub runs: Arc<DashMap<String, RunHandle>>,
+}
+
+impl DailyState {
+    pub fn new(
+        words: WordRepository,
+        results: DailyRepository,
+        seeds: DailySeeds,
+        round_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            words,
+            results,
+            seeds,
+            round_timeout: round_timeout.unwrap_or(DEFAULT_ROUND_TIMEOUT),
+            runs: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Start today's challenge for a player, unless they already played it
+    pub async fn start(
+        &self,
+        player_name: String,
+        tx: Outbox,
+    ) -> Result<(), ErrorCode> {
+        let date = ChallengeDate::today();
+        match self
+            .results
+            .has_played(&date.to_string(), &player_name)
+            .await
+        {
+            Ok(false) => {}
+            Ok(true) => return Err(ErrorCode::AlreadyPlayed),
+            Err(e) => {
+                error!(player_name, "Failed to look up daily result: {}", e);
+                return Err(ErrorCode::Internal);
+            }
+        }
+
+        let Entry::Vacant(entry) = self.runs.entry(player_name.clone()) else {
+            return Err(ErrorCode::AlreadyPlayed);
+        };
+        info!(player_name, %date, "Starting daily challenge");
+
+        let _ = tx.send(ServerMessage::DailyStart {
+            date: date.to_string(),
+            rounds: DAILY_ROUNDS,
+        });
+        let run = SoloRun::new(
+            player_name.clone(),
+            self.seeds.seed(date),
+            DAILY_ROUNDS,
+            tx.clone(),
+            self.words.clone(),
+            self.round_timeout,
+        );
+        let results = self.results.clone();
+        let runs = self.runs.clone();
+        entry.insert(run.spawn(move |record| async move {
+            submit(&results, date, &player_name, record, &tx).await;
+            runs.remove(&player_name);
+        }));
+        Ok(())
+    }
+
+    /// Send a server notice to every player in a run
+    pub fn announce(&self, msg: &ServerMessage) {
+        for handle in self.runs.iter() {
+            handle.send(RunCommand::Announce(msg.clone()));
+        }
+    }
+
+    fn send(&self, player_name: &str, command: RunCommand) -> Result<(), ErrorCode> {
+        let handle = self.runs.get(player_name).ok_or(ErrorCode::NotInGame)?;
+        if handle.send(command) {
+            Ok(())
+        } else {
+            Err(ErrorCode::NotInGame)
+        }
+    }
+
+    pub fn handle_answer(
+        &self,
+        player_name: &str,
+        answer: &str,
+        round: Option<u32>,
+    ) -> Result<(), ErrorCode> {
+        self.send(
+            player_name,
+            RunCommand::Answer {
+                answer: answer.to_string(),
+                round,
+            },
+        )
+    }
+
+    pub fn handle_skip(&self, player_name: &str) -> Result<(), ErrorCode> {
+        self.send(player_name, RunCommand::Skip)
+    }
+
+    /// Send a player the current round again, after they fell behind or asked with `sync`
+    pub fn resync(&self, player_name: &str) -> Result<(), ErrorCode> {
+        self.send(player_name, RunCommand::Resync)
+    }
+
+    /// A player who leaves mid-run keeps the score they had; the run task stores it
+    pub fn handle_disconnect(&self, player_name: &str) {
+        info!(player_name, "Player disconnected");
+        let _ = self.send(player_name, RunCommand::Abandon);
+    }
+
+    /// Leaderboard for the given day
+    pub async fn leaderboard(&self, date: ChallengeDate) -> Result<DailyLeaderboard, sqlx::Error> {
+        let results = self
+            .results
+            .leaderboard(&date.to_string(), LEADERBOARD_SIZE)
+            .await?;
+
+        let mut entries: Vec<DailyLeaderboardEntry> = Vec::with_capacity(results.len());
+        for (i, result) in results.into_iter().enumerate() {
+            let rank = match entries.last() {
+                Some(prev) if (prev.correct, prev.time_ms) == (result.correct, result.time_ms) => {
+                    prev.rank
+                }
+                _ => i as u32 + 1,
+            };
+            entries.push(DailyLeaderboardEntry {
+                rank,
+                player_name: result.player_name,
+                correct: result.correct,
+                time_ms: result.time_ms,
+            });
+        }
+
+        Ok(DailyLeaderboard {
+            date: date.to_string(),
+            rounds: DAILY_ROUNDS,
+            entries,
+        })
+    }
+}
+
+/// Store a run's result (finished or not, so a retry can't preview the words) and report the rank
+async fn submit(
+    results: &DailyRepository,
+    date: ChallengeDate,
+    player_name: &str,
+    record: RunRecord,
+    tx: &Outbox,
+) {
+    let result = DailyResult {
+        date: date.to_string(),
+        player_name: player_name.to_string(),
+        correct: record.correct(),
+        time_ms: record.time_ms(),
+    };
+    info!(
+        player_name,
+        date = result.date,
+        correct = result.correct,
+        time_ms = result.time_ms,
+        rounds_played = record.rounds.len(),
+        "Daily challenge finished"
+    );
+
+    if let Err(e) = results.record(&result).await {
+        error!(player_name, "Failed to record daily result: {}", e);
+        let _ = tx.send(ServerMessage::Error {
+            code: ErrorCode::Internal,
+            message: "Could not save your result".to_string(),
+            request_id: None,
+        });
+        return;
+    }
+    match results.rank(&result).await {
+        Ok(rank) => {
+            let _ = tx.send(ServerMessage::DailyEnd {
+                correct: result.correct,
+                time_ms: result.time_ms,
+                rank,
+            });
+        }
+        Err(e) => error!(player_name, "Failed to rank daily result: {}", e),
+    }
+}
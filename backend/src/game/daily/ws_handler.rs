@@ -0,0 +1,129 @@
+use super::state::DailyState;
+use crate::game::core::messages::{ClientMessage, ErrorCode};
+use crate::game::core::validation::{validate_answer, validate_player_name};
+use crate::game::engine::ws::{run_connection, ConnectionContext, ConnectionHandler, ConnectionOptions};
+use crate::game::engine::outbox::Outbox;
+use axum::extract::ws::WebSocket;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+impl ConnectionHandler for DailyState {
+    async fn handle_message(
+        self: Arc<Self>,
+        msg: ClientMessage,
+        tx: Outbox,
+        ctx: &mut ConnectionContext,
+    ) {
+        match msg {
+            // Answered by the connection loop before dispatch
+            ClientMessage::Hello { .. } => {}
+            ClientMessage::StartDaily { player_name } => {
+                let player_name = match validate_player_name(&player_name) {
+                    Ok(name) => name,
+                    Err(err) => {
+                        let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                        return;
+                    }
+                };
+                // One run per connection
+                if ctx.user_id.is_some() {
+                    let _ = tx.send(ctx.error_code(ErrorCode::AlreadyPlayed));
+                    return;
+                }
+                match self.start(player_name.clone(), tx.clone()).await {
+                    Ok(()) => ctx.user_id = Some(player_name),
+                    Err(code) => {
+                        let _ = tx.send(ctx.error_code(code));
+                    }
+                }
+            }
+            ClientMessage::Answer { answer, round } => {
+                let Some(user_id) = &ctx.user_id else {
+                    warn!("Received answer from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                let answer = match validate_answer(&answer) {
+                    Ok(answer) => answer,
+                    Err(err) => {
+                        let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                        return;
+                    }
+                };
+                if let Err(code) = self.handle_answer(user_id, &answer, round) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Skip => {
+                let Some(user_id) = &ctx.user_id else {
+                    warn!("Received skip from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.handle_skip(user_id) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Sync => {
+                let Some(user_id) = &ctx.user_id else {
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.resync(user_id) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Pause | ClientMessage::Unpause => {
+                let _ = tx.send(ctx.error_code(ErrorCode::PauseNotAllowed));
+            }
+            // Solo runs have no opponent to agree with
+            ClientMessage::OfferDraw | ClientMessage::AcceptDraw => {
+                let _ = tx.send(ctx.error_code(ErrorCode::DrawNotAllowed));
+            }
+            ClientMessage::Join { .. }
+            | ClientMessage::CreateGame { .. }
+            | ClientMessage::JoinGame { .. }
+            | ClientMessage::Resume { .. }
+            | ClientMessage::RequestRematch => {
+                warn!("Received game message on daily endpoint");
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/ephemeral or /ws/matchmaking to play against someone",
+                ));
+            }
+            ClientMessage::CreateChallenge { .. } | ClientMessage::AcceptChallenge { .. } => {
+                warn!("Received ghost challenge message on daily endpoint");
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/ghost for ghost challenges",
+                ));
+            }
+        }
+    }
+
+    fn handle_disconnect(&self, user_id: &str, _tx: &Outbox, _can_resume: bool) {
+        self.handle_disconnect(user_id);
+    }
+
+    // Solo runs have no opponent to share latency with
+    fn record_latency(&self, _user_id: &str, _rtt: Duration) {}
+
+    fn resync(&self, user_id: &str) {
+        let _ = self.resync(user_id);
+    }
+
+    fn name(&self) -> &'static str {
+        "daily"
+    }
+}
+
+pub async fn handle_connection(
+    socket: WebSocket,
+    state: Arc<DailyState>,
+    options: ConnectionOptions,
+) {
+    run_connection(socket, state, options).await;
+}
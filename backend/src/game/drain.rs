@@ -0,0 +1,118 @@
+use crate::game::core::messages::ServerMessage;
+use crate::game::daily::DailyState;
+use crate::game::engine::drain::DrainSwitch;
+use crate::game::ephemeral::EphemeralState;
+use crate::game::ghost::GhostState;
+use crate::game::matchmaking::MatchmakingState;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::info;
+
+/// How long running games get to finish once draining starts
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often draining checks whether every game has finished
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Winds the server down for a deploy: refuses new games, warns players and
+/// waits for running games to finish before the server exits
+pub struct Drain {
+    switch: DrainSwitch,
+    timeout: Duration,
+    ephemeral: Arc<EphemeralState>,
+    matchmaking: Arc<MatchmakingState>,
+    daily: Arc<DailyState>,
+    ghost: Arc<GhostState>,
+}
+
+impl Drain {
+    pub fn new(
+        timeout: Duration,
+        ephemeral: Arc<EphemeralState>,
+        matchmaking: Arc<MatchmakingState>,
+        daily: Arc<DailyState>,
+        ghost: Arc<GhostState>,
+    ) -> Self {
+        Self {
+            switch: DrainSwitch::new(),
+            timeout,
+            ephemeral,
+            matchmaking,
+            daily,
+            ghost,
+        }
+    }
+
+    /// Checked by connections to refuse new games
+    pub fn switch(&self) -> DrainSwitch {
+        self.switch.clone()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.switch.is_draining()
+    }
+
+    /// Refuse new games, as when draining, but keep the server up
+    pub fn set_maintenance(&self, on: bool) {
+        info!(on, "Maintenance mode");
+        self.switch.set_maintenance(on);
+    }
+
+    pub fn in_maintenance(&self) -> bool {
+        self.switch.in_maintenance()
+    }
+
+    /// Draining or in maintenance
+    pub fn refuses_new_games(&self) -> bool {
+        self.switch.refuses_new_games()
+    }
+
+    /// Stop starting games and warn everyone playing.
+    /// Returns false if the server was already draining.
+    pub fn start(&self) -> bool {
+        if !self.switch.start(self.timeout) {
+            return false;
+        }
+        info!(timeout = ?self.timeout, "Draining: no new games from now on");
+        let warning = ServerMessage::ServerDraining {
+            seconds_left: u32::try_from(self.timeout.as_secs()).unwrap_or(u32::MAX),
+        };
+        self.ephemeral.announce(&warning);
+        self.matchmaking.announce(&warning);
+        self.daily.announce(&warning);
+        self.ghost.announce(&warning);
+        true
+    }
+
+    /// Wait until draining starts, by signal or admin request
+    pub async fn started(&self) {
+        self.switch.started().await;
+    }
+
+    /// Wait until draining starts, then until every match and solo run has
+    /// finished or the deadline passes
+    pub async fn finished(&self) {
+        let deadline = self.switch.started().await;
+        loop {
+            let running = self.running();
+            if running == 0 {
+                info!("Drained: no games left running");
+                return;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                info!(running, "Drain deadline reached");
+                return;
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
+    fn running(&self) -> usize {
+        self.ephemeral.registry.running_matches()
+            + self.matchmaking.registry.running_matches()
+            + self.daily.runs.len()
+            + self.ghost.runs.len()
+    }
+}
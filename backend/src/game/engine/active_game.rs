@@ -1,206 +1,1139 @@
-use crate::game::core::WordRepository;
-use crate::game::core::messages::ServerMessage;
-use crate::game::core::session::GameSession;
-use dashmap::DashMap;
+use crate::game::core::event_log::{EventLog, GameEvent};
+use crate::game::core::messages::{ErrorCode, GameEndReason, RoundState, ServerMessage};
+use crate::game::core::session::{GameSession, RoundOutcome, SkipResult};
+use crate::game::core::word_sequence::WordSequence;
+use super::forfeits::Forfeits;
+use super::metrics::{Metrics, RoundEndKind};
+use super::outbox::Outbox;
+use crate::game::core::{
+    GameLog, GameLogRepository, GameProgress, GameSnapshot, MatchRecord, MatchRepository,
+    RoundSnapshot, Word, WordRepository,
+};
+use std::future;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
-use tokio::sync::broadcast;
-use tracing::info;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Instant, sleep_until};
+use tracing::{debug, error, info, warn};
 
 pub const DEFAULT_ROUND_TIMEOUT: Duration = Duration::from_secs(30);
-pub const MAX_ROUNDS: u32 = 30;
+pub const DEFAULT_WINS_NEEDED: u32 = 10;
+pub const DEFAULT_MAX_ROUNDS: u32 = 30;
+/// How long a restored game waits for its players to come back by default
+pub const DEFAULT_RESUME_GRACE: Duration = Duration::from_secs(120);
+/// How long a running match waits for a player whose connection dropped by default
+pub const DEFAULT_RECONNECT_GRACE: Duration = Duration::from_secs(30);
 
-/// An active game: combines pure game logic with transport channels
+/// How two-player matches are played
+#[derive(Debug, Clone, Copy)]
+pub struct MatchRules {
+    pub round_timeout: Duration,
+    /// Round wins that take the match
+    pub wins_needed: u32,
+    /// The match ends after this many rounds, whatever the score
+    pub max_rounds: u32,
+    /// How long a restored game waits for its players to come back
+    pub resume_grace: Duration,
+    /// How long a running match waits, timer stopped, for a player whose
+    /// connection dropped to `resume`. Zero ends the game right away.
+    pub reconnect_grace: Duration,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            round_timeout: DEFAULT_ROUND_TIMEOUT,
+            wins_needed: DEFAULT_WINS_NEEDED,
+            max_rounds: DEFAULT_MAX_ROUNDS,
+            resume_grace: DEFAULT_RESUME_GRACE,
+            reconnect_grace: DEFAULT_RECONNECT_GRACE,
+        }
+    }
+}
+
+/// A (player1, player2) pair reordered so the given player's value comes first
+fn ours_first<T>(is_player1: bool, (p1, p2): (T, T)) -> (T, T) {
+    if is_player1 { (p1, p2) } else { (p2, p1) }
+}
+
+fn duration_ms(duration: Duration) -> u32 {
+    u32::try_from(duration.as_millis()).unwrap_or(u32::MAX)
+}
+
+/// Everything that can happen to a running game. Commands are applied one at a
+/// time by the game's own task, so each game sees a single, deterministic order.
+#[derive(Debug)]
+pub enum GameCommand {
+    Answer {
+        player_id: String,
+        answer: String,
+        /// Round the client meant to answer, if it said
+        round: Option<u32>,
+        /// Echoed back on an error reply
+        request_id: Option<String>,
+    },
+    Skip {
+        player_id: String,
+        request_id: Option<String>,
+    },
+    Rematch { player_id: String },
+    Latency { player_id: String, rtt: Duration },
+    /// The player's connection dropped messages; send them the game's state
+    Resync { player_id: String },
+    /// Reply with the game as the player sees it
+    Sync {
+        player_id: String,
+        reply: oneshot::Sender<ServerMessage>,
+    },
+    /// The player's connection closed. If they `can_return`, a running match
+    /// waits for them; otherwise the game task notifies the opponent and stops.
+    Disconnect { player_id: String, can_return: bool },
+    /// Stop the round timer until both players unpause
+    Pause { player_id: String },
+    /// The player agrees to carry on after a pause
+    Unpause { player_id: String },
+    /// Offer a draw, or agree to the opponent's offer
+    OfferDraw {
+        player_id: String,
+        request_id: Option<String>,
+    },
+    /// Take the opponent's standing draw offer
+    AcceptDraw {
+        player_id: String,
+        request_id: Option<String>,
+    },
+    /// A player who was away (after a restart or a dropped connection) is
+    /// back on a new connection. Replies false if the player wasn't waited for.
+    Reconnect {
+        player_id: String,
+        tx: Outbox,
+        reply: oneshot::Sender<bool>,
+    },
+    /// Report the game's progress so it can be saved
+    Snapshot { reply: oneshot::Sender<GameProgress> },
+    /// Report the game for operators
+    Status { reply: oneshot::Sender<GameReport> },
+    /// The server is going down: report progress, tell the players and stop
+    Shutdown { reply: oneshot::Sender<GameProgress> },
+    /// Send a server notice to both players
+    Announce(ServerMessage),
+    /// An operator ended the game: it finishes without a winner and stops
+    End,
+}
+
+impl GameCommand {
+    /// Player who sent the command, None for server-side requests
+    pub fn player_id(&self) -> Option<&str> {
+        match self {
+            GameCommand::Answer { player_id, .. }
+            | GameCommand::Skip { player_id, .. }
+            | GameCommand::Rematch { player_id }
+            | GameCommand::Latency { player_id, .. }
+            | GameCommand::Resync { player_id }
+            | GameCommand::Sync { player_id, .. }
+            | GameCommand::Disconnect { player_id, .. }
+            | GameCommand::Pause { player_id }
+            | GameCommand::Unpause { player_id }
+            | GameCommand::OfferDraw { player_id, .. }
+            | GameCommand::AcceptDraw { player_id, .. }
+            | GameCommand::Reconnect { player_id, .. } => Some(player_id),
+            GameCommand::Snapshot { .. }
+            | GameCommand::Status { .. }
+            | GameCommand::Shutdown { .. }
+            | GameCommand::Announce(_)
+            | GameCommand::End => None,
+        }
+    }
+}
+
+/// A game as operators see it, see [`GameCommand::Status`]
+#[derive(Debug)]
+pub struct GameReport {
+    pub progress: GameProgress,
+    /// Last heartbeat round-trip time per player, as the server measured it
+    pub latencies: (Option<Duration>, Option<Duration>),
+}
+
+/// Registry's view of a running game: its players and command channel
+#[derive(Clone)]
+pub struct GameHandle {
+    pub player1: String,
+    pub player2: String,
+    commands: mpsc::UnboundedSender<GameCommand>,
+    match_running: Arc<AtomicBool>,
+}
+
+impl GameHandle {
+    /// Queue a command. Returns false if the game task has already stopped.
+    pub fn send(&self, command: GameCommand) -> bool {
+        self.commands.send(command).is_ok()
+    }
+
+    /// Whether the game task has stopped
+    pub fn is_finished(&self) -> bool {
+        self.commands.is_closed()
+    }
+
+    /// Whether a match is being played, as opposed to over (waiting for a
+    /// rematch) or waiting for restored players to come back
+    pub fn is_match_running(&self) -> bool {
+        !self.is_finished() && self.match_running.load(Ordering::Relaxed)
+    }
+}
+
+/// Shared dependencies and settings handed to every game task
+#[derive(Clone)]
+pub struct GameServices {
+    pub words: WordRepository,
+    pub matches: MatchRepository,
+    pub logs: GameLogRepository,
+    pub rules: MatchRules,
+    /// Game mode name, recorded with each match
+    pub mode: &'static str,
+    pub metrics: Arc<Metrics>,
+    /// Set where leaving a running match forfeits it (ranked games); the
+    /// leavers are counted here
+    pub forfeits: Option<Arc<Forfeits>>,
+}
+
+/// An active game: combines pure game logic with transport channels.
+/// Owned by its own task (see [`ActiveGame::spawn`]), never shared.
 pub struct ActiveGame {
     pub session: GameSession,
-    pub player1_tx: broadcast::Sender<ServerMessage>,
-    pub player2_tx: broadcast::Sender<ServerMessage>,
+    pub player1_tx: Outbox,
+    pub player2_tx: Outbox,
+    /// Last heartbeat round-trip time per player
+    pub player1_latency: Option<Duration>,
+    pub player2_latency: Option<Duration>,
+    services: GameServices,
+    /// Source of this match's words
+    sequence: WordSequence,
+    /// When the current round times out, if one is running
+    round_deadline: Option<Instant>,
+    /// Replay id of this game's event log
+    log_id: String,
+    log: EventLog,
+    /// Players of a restored game who haven't reconnected yet, or whose
+    /// connection dropped mid-match
+    away: Vec<String>,
+    /// Round to start over once every player is back
+    paused_round: Option<RoundSnapshot>,
+    /// When the game gives up on players who haven't reconnected
+    resume_deadline: Option<Instant>,
+    /// Player who paused the game, if it is paused
+    paused_by: Option<String>,
+    /// Players who want to carry on after a pause
+    unpause_votes: Vec<String>,
+    /// Time left in the round while its timer is stopped
+    paused_remaining: Option<Duration>,
+    /// Player whose draw offer stands until the round ends
+    draw_offered_by: Option<String>,
+    /// Shared with the handle, see [`GameHandle::is_match_running`]
+    match_running: Arc<AtomicBool>,
 }
 
 impl ActiveGame {
     pub fn new(
         session: GameSession,
-        player1_tx: broadcast::Sender<ServerMessage>,
-        player2_tx: broadcast::Sender<ServerMessage>,
+        player1_tx: Outbox,
+        player2_tx: Outbox,
+        services: GameServices,
+        seed: u64,
     ) -> Self {
         Self {
             session,
             player1_tx,
             player2_tx,
+            player1_latency: None,
+            player2_latency: None,
+            services,
+            sequence: WordSequence::new(seed),
+            round_deadline: None,
+            log_id: uuid::Uuid::new_v4().to_string(),
+            log: EventLog::new(),
+            away: Vec::new(),
+            paused_round: None,
+            resume_deadline: None,
+            paused_by: None,
+            unpause_votes: Vec::new(),
+            paused_remaining: None,
+            draw_offered_by: None,
+            match_running: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// A game saved before a restart. It waits for both players to reconnect
+    /// before carrying on, and ends if they don't within [`MatchRules::resume_grace`].
+    pub fn restore(snapshot: GameSnapshot, services: GameServices) -> Self {
+        let GameSnapshot {
+            player1,
+            player2,
+            progress,
+            ..
+        } = snapshot;
+        let away = vec![player1.clone(), player2.clone()];
+        let session = GameSession::restore(player1, player2, progress.scores);
+        // Nobody is listening until the players reconnect
+        let player1_tx = Outbox::detached();
+        let player2_tx = Outbox::detached();
+        let mut game = Self::new(session, player1_tx, player2_tx, services, progress.seed);
+        game.sequence = WordSequence::resume(progress.seed, progress.sequence_state);
+        game.away = away;
+        game.paused_round = progress.round;
+        game.resume_deadline = Some(Instant::now() + game.services.rules.resume_grace);
+        game
+    }
+
+    /// Start the game on its own task and return the handle for sending it commands
+    pub fn spawn(self, game_id: String) -> GameHandle {
+        let (commands, rx) = mpsc::unbounded_channel();
+        let handle = GameHandle {
+            player1: self.session.player1.clone(),
+            player2: self.session.player2.clone(),
+            commands,
+            match_running: self.match_running.clone(),
+        };
+        tokio::spawn(self.run(game_id, rx));
+        handle
+    }
+
+    async fn run(mut self, game_id: String, mut commands: mpsc::UnboundedReceiver<GameCommand>) {
+        // A restored game carries on once its players reconnect
+        if self.away.is_empty() {
+            self.start_game(&game_id);
+        }
+
+        loop {
+            let deadline = [self.round_deadline, self.resume_deadline]
+                .into_iter()
+                .flatten()
+                .min();
+            let timeout = async move {
+                match deadline {
+                    Some(deadline) => sleep_until(deadline).await,
+                    None => future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                command = commands.recv() => {
+                    let Some(command) = command else {
+                        break;
+                    };
+                    if !self.handle_command(&game_id, command) {
+                        break;
+                    }
+                }
+                _ = timeout => {
+                    if self.resume_deadline.is_some_and(|at| at <= Instant::now()) {
+                        self.handle_resume_timeout(&game_id);
+                        break;
+                    }
+                    self.handle_round_timeout(&game_id);
+                }
+            }
+        }
+
+        debug!(game_id, "Game task finished");
+    }
+
+    /// Apply one command. Returns false when the game is over for good.
+    fn handle_command(&mut self, game_id: &str, command: GameCommand) -> bool {
+        if let Some(player_id) = command.player_id()
+            && !self.session.has_player(player_id)
+        {
+            warn!(game_id, ?command, "Ignoring command from player not in this game");
+            return true;
+        }
+        if let GameCommand::Answer {
+            player_id,
+            request_id,
+            ..
+        }
+        | GameCommand::Skip {
+            player_id,
+            request_id,
+        } = &command
+            && self.is_paused()
+        {
+            debug!(game_id, ?command, "Refusing play while the game is paused");
+            self.send_error(player_id, ErrorCode::GamePaused, request_id.clone());
+            return true;
+        }
+
+        match command {
+            GameCommand::Answer {
+                player_id,
+                answer,
+                round,
+                ..
+            } => {
+                self.handle_answer(game_id, &player_id, &answer, round);
+            }
+            GameCommand::Skip { player_id, .. } => self.handle_skip(game_id, &player_id),
+            GameCommand::Rematch { player_id } => self.handle_rematch(game_id, &player_id),
+            GameCommand::Latency { player_id, rtt } => {
+                if self.record_latency(&player_id, rtt) {
+                    self.broadcast(ServerMessage::PlayerLatency {
+                        player: player_id,
+                        latency_ms: u32::try_from(rtt.as_millis()).unwrap_or(u32::MAX),
+                    });
+                }
+            }
+            GameCommand::Resync { player_id } => {
+                self.send_to(&player_id, self.state_for(game_id, &player_id));
+            }
+            GameCommand::Sync { player_id, reply } => {
+                let _ = reply.send(self.state_for(game_id, &player_id));
+            }
+            GameCommand::Disconnect {
+                player_id,
+                can_return,
+            } => {
+                if can_return && self.match_running.load(Ordering::Relaxed) {
+                    self.handle_drop(game_id, &player_id);
+                    return true;
+                }
+                info!(game_id, player_id, "Player left game");
+                self.log.record(GameEvent::Disconnect {
+                    player: player_id.clone(),
+                });
+                if let Some(opponent_id) = self.session.opponent_of(&player_id)
+                    && let Some(tx) = self.player_tx(opponent_id)
+                {
+                    let _ = tx.send(ServerMessage::OpponentDisconnected);
+                }
+                if self.match_running.load(Ordering::Relaxed) && self.services.forfeits.is_some() {
+                    self.forfeit(game_id, &player_id);
+                } else {
+                    self.save_log(game_id);
+                }
+                return false;
+            }
+            GameCommand::Pause { player_id } => self.handle_pause(game_id, &player_id),
+            GameCommand::Unpause { player_id } => self.handle_unpause(game_id, &player_id),
+            GameCommand::OfferDraw {
+                player_id,
+                request_id,
+            } => self.handle_offer_draw(game_id, &player_id, request_id),
+            GameCommand::AcceptDraw {
+                player_id,
+                request_id,
+            } => self.handle_accept_draw(game_id, &player_id, request_id),
+            GameCommand::Reconnect {
+                player_id,
+                tx,
+                reply,
+            } => {
+                let _ = reply.send(self.handle_reconnect(game_id, &player_id, tx));
+            }
+            GameCommand::Snapshot { reply } => {
+                let _ = reply.send(self.progress());
+            }
+            GameCommand::Status { reply } => {
+                let _ = reply.send(GameReport {
+                    progress: self.progress(),
+                    latencies: (self.player1_latency, self.player2_latency),
+                });
+            }
+            GameCommand::Announce(msg) => self.broadcast(msg),
+            GameCommand::Shutdown { reply } => {
+                info!(game_id, "Saving game for shutdown");
+                let _ = reply.send(self.progress());
+                self.broadcast(ServerMessage::ServerShutdown);
+                self.save_log(game_id);
+                return false;
+            }
+            GameCommand::End => {
+                info!(game_id, "Game ended by an operator");
+                let reason = GameEndReason::Aborted;
+                if self.match_running.swap(false, Ordering::Relaxed) {
+                    self.log.record(GameEvent::GameEnd {
+                        winner: None,
+                        reason,
+                    });
+                    self.save_log(game_id);
+                }
+                self.broadcast(ServerMessage::GameEnd {
+                    winner: None,
+                    reason,
+                });
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Store a heartbeat round-trip time. Returns false if the player isn't in this game.
+    pub fn record_latency(&mut self, player_id: &str, rtt: Duration) -> bool {
+        if player_id == self.session.player1 {
+            self.player1_latency = Some(rtt);
+        } else if player_id == self.session.player2 {
+            self.player2_latency = Some(rtt);
+        } else {
+            return false;
+        }
+        true
+    }
+
     pub fn broadcast(&self, msg: ServerMessage) {
         let _ = self.player1_tx.send(msg.clone());
         let _ = self.player2_tx.send(msg);
     }
-}
 
-/// Result of a correct answer submission
-pub struct AnswerResult {
-    pub round_result: ServerMessage,
-    pub game_winner: Option<String>,
-    pub round_number: u32,
-}
+    fn player_tx(&self, player_id: &str) -> Option<&Outbox> {
+        if player_id == self.session.player1 {
+            Some(&self.player1_tx)
+        } else if player_id == self.session.player2 {
+            Some(&self.player2_tx)
+        } else {
+            None
+        }
+    }
 
-/// Trait for cleanup functions that can be used across async boundaries
-pub trait CleanupGame: Send + Sync + 'static {
-    fn cleanup(&self, game_id: &str);
-}
+    /// Reply to a player's command with an error, correlated by `request_id`
+    fn send_error(&self, player_id: &str, code: ErrorCode, request_id: Option<String>) {
+        self.send_to(
+            player_id,
+            ServerMessage::Error {
+                code,
+                message: code.description().to_string(),
+                request_id,
+            },
+        );
+    }
 
-/// Implementation for closures
-impl<F> CleanupGame for F
-where
-    F: Fn(&str) + Send + Sync + 'static,
-{
-    fn cleanup(&self, game_id: &str) {
-        self(game_id)
+    fn send_to(&self, player_id: &str, msg: ServerMessage) {
+        if let Some(tx) = self.player_tx(player_id) {
+            let _ = tx.send(msg);
+        }
     }
-}
 
-/// Spawns a timeout task for the current round
-pub fn spawn_round_timeout(
-    timeout: Duration,
-    games: Arc<DashMap<String, ActiveGame>>,
-    words: WordRepository,
-    game_id: String,
-    round_number: u32,
-    player_games: Arc<DashMap<String, String>>,
-    cleanup_game: Arc<dyn CleanupGame>,
-) {
-    tokio::spawn(async move {
-        tokio::time::sleep(timeout).await;
-        handle_round_timeout(
-            games,
-            words,
-            timeout,
+    /// Announce the opponents and start round 1
+    fn start_game(&mut self, game_id: &str) {
+        let seed = self.sequence.seed();
+        info!(game_id, seed, "Game starting");
+        self.log.record(GameEvent::GameStart {
+            player1: self.session.player1.clone(),
+            player2: self.session.player2.clone(),
+            seed,
+        });
+        let _ = self.player1_tx.send(ServerMessage::GameStart {
+            opponent: self.session.player2.clone(),
+            seed,
+        });
+        let _ = self.player2_tx.send(ServerMessage::GameStart {
+            opponent: self.session.player1.clone(),
+            seed,
+        });
+        self.match_running.store(true, Ordering::Relaxed);
+        self.start_round(game_id, 1);
+    }
+
+    fn start_round(&mut self, game_id: &str, round_number: u32) {
+        let Some(word) = self.sequence.next_word(&self.services.words) else {
+            warn!(game_id, "No words available, cannot start round");
+            return;
+        };
+        self.begin_round(game_id, round_number, word);
+    }
+
+    fn begin_round(&mut self, game_id: &str, round_number: u32, word: Word) {
+        let readings = self.services.words.get_readings_for_kanji(&word.kanji);
+        info!(
             game_id,
-            round_number,
-            player_games,
-            cleanup_game,
-        )
-        .await;
-    });
-}
+            round = round_number,
+            kanji = word.kanji,
+            "Starting round"
+        );
+
+        self.log.record(GameEvent::RoundStart {
+            round: round_number,
+            kanji: word.kanji.clone(),
+            readings: readings.clone(),
+        });
+        self.broadcast(ServerMessage::RoundStart {
+            kanji: word.kanji.clone(),
+            round: round_number,
+            readings,
+        });
+        self.session.start_round(round_number, word);
+        self.round_deadline = Some(Instant::now() + self.services.rules.round_timeout);
+    }
+
+    /// The game as `player_id` sees it
+    fn state_for(&self, game_id: &str, player_id: &str) -> ServerMessage {
+        let is_player1 = player_id == self.session.player1;
+        let (your_score, opponent_score) = ours_first(is_player1, self.session.scores());
+        let (you_want_rematch, opponent_wants_rematch) =
+            ours_first(is_player1, self.session.rematch_votes());
+        let you_offered_draw = self.draw_offered_by.as_deref() == Some(player_id);
+        let opponent_offered_draw = self.draw_offered_by.is_some() && !you_offered_draw;
+        let opponent = self.session.opponent_of(player_id).unwrap_or_default();
+
+        let round = self.session.current_round().map(|round| {
+            let (you_skipped, opponent_skipped) =
+                ours_first(is_player1, (round.player1_skipped, round.player2_skipped));
+            RoundState {
+                round: round.number,
+                kanji: round.word.kanji.clone(),
+                readings: self.services.words.get_readings_for_kanji(&round.word.kanji),
+                remaining_ms: duration_ms(self.remaining_round_time()),
+                you_skipped,
+                opponent_skipped,
+            }
+        });
+
+        ServerMessage::GameState {
+            game_id: game_id.to_string(),
+            opponent: opponent.to_string(),
+            your_score,
+            opponent_score,
+            match_running: self.match_running.load(Ordering::Relaxed),
+            round,
+            paused: self.is_paused(),
+            you_want_rematch,
+            opponent_wants_rematch,
+            you_offered_draw,
+            opponent_offered_draw,
+            opponent_connected: !self.away.iter().any(|p| p == opponent),
+        }
+    }
+
+    /// Attach a returning player of a restored game to their new connection.
+    /// Once both are back, the saved round starts over.
+    fn handle_reconnect(
+        &mut self,
+        game_id: &str,
+        player_id: &str,
+        tx: Outbox,
+    ) -> bool {
+        let Some(index) = self.away.iter().position(|p| p == player_id) else {
+            return false;
+        };
+        self.away.remove(index);
+        info!(game_id, player_id, "Player resumed game");
+
+        let (p1_score, p2_score) = self.session.scores();
+        let (your_score, opponent_score, opponent) = if player_id == self.session.player1 {
+            self.player1_tx = tx;
+            (p1_score, p2_score, self.session.player2.clone())
+        } else {
+            self.player2_tx = tx;
+            (p2_score, p1_score, self.session.player1.clone())
+        };
+        self.send_to(
+            player_id,
+            ServerMessage::Resumed {
+                game_id: game_id.to_string(),
+                opponent,
+                seed: self.sequence.seed(),
+                your_score,
+                opponent_score,
+            },
+        );
+
+        // Games restored after a restart never told anyone who was missing
+        if self.paused_round.is_none()
+            && let Some(opponent) = self.session.opponent_of(player_id)
+            && !self.away.iter().any(|p| p == opponent)
+        {
+            self.send_to(
+                opponent,
+                ServerMessage::OpponentConnectionChanged {
+                    connected: true,
+                    grace_remaining: None,
+                },
+            );
+        }
+
+        if self.away.is_empty() {
+            self.resume_deadline = None;
+            if let Some(round) = self.paused_round.take() {
+                self.match_running.store(true, Ordering::Relaxed);
+                self.begin_round(game_id, round.number, round.word);
+                return true;
+            }
+            self.restart_clock();
+        }
+        // Mid-match, the player needs to see where the game is
+        if self.match_running.load(Ordering::Relaxed) {
+            self.send_to(player_id, self.state_for(game_id, player_id));
+        }
+        true
+    }
+
+    /// A player's connection dropped mid-match: stop the round timer and give
+    /// them [`MatchRules::reconnect_grace`] to `resume`
+    fn handle_drop(&mut self, game_id: &str, player_id: &str) {
+        info!(game_id, player_id, "Player dropped, waiting for them to resume");
+        if !self.away.iter().any(|p| p == player_id) {
+            self.away.push(player_id.to_string());
+        }
+        self.stop_clock();
+        let deadline = Instant::now() + self.services.rules.reconnect_grace;
+        self.resume_deadline = Some(self.resume_deadline.map_or(deadline, |at| at.min(deadline)));
+
+        if let Some(opponent) = self.session.opponent_of(player_id) {
+            self.send_to(
+                opponent,
+                ServerMessage::OpponentConnectionChanged {
+                    connected: false,
+                    grace_remaining: self.grace_remaining(),
+                },
+            );
+        }
+    }
+
+    /// Whole seconds until the game gives up on absent players
+    fn grace_remaining(&self) -> Option<u32> {
+        self.resume_deadline.map(|at| {
+            let millis = at.saturating_duration_since(Instant::now()).as_millis();
+            u32::try_from(millis.div_ceil(1000)).unwrap_or(u32::MAX)
+        })
+    }
 
-async fn handle_round_timeout(
-    games: Arc<DashMap<String, ActiveGame>>,
-    words: WordRepository,
-    timeout: Duration,
-    game_id: String,
-    round_number: u32,
-    player_games: Arc<DashMap<String, String>>,
-    cleanup_game: Arc<dyn CleanupGame>,
-) {
-    // Check if the round is still active and timeout it
-    let timeout_result = {
-        let Some(mut game) = games.get_mut(&game_id) else {
+    fn handle_pause(&mut self, game_id: &str, player_id: &str) {
+        if !self.match_running.load(Ordering::Relaxed) || self.paused_by.is_some() {
             return;
+        }
+        info!(game_id, player_id, "Game paused");
+        self.paused_by = Some(player_id.to_string());
+        self.unpause_votes.clear();
+        self.stop_clock();
+        self.broadcast(ServerMessage::GamePaused {
+            player: player_id.to_string(),
+        });
+    }
+
+    /// Both players must agree before the round timer runs again
+    fn handle_unpause(&mut self, game_id: &str, player_id: &str) {
+        if self.paused_by.is_none() || self.unpause_votes.iter().any(|p| p == player_id) {
+            return;
+        }
+        self.unpause_votes.push(player_id.to_string());
+        if self.unpause_votes.len() < 2 {
+            debug!(game_id, player_id, "Player wants to unpause, waiting for opponent");
+            self.broadcast(ServerMessage::UnpauseRequested {
+                player: player_id.to_string(),
+            });
+            return;
+        }
+
+        info!(game_id, "Game unpaused");
+        self.paused_by = None;
+        self.unpause_votes.clear();
+        self.restart_clock();
+        self.broadcast(ServerMessage::GameUnpaused {
+            remaining_ms: duration_ms(self.remaining_round_time()),
+        });
+    }
+
+    /// Offer a draw. If the opponent already offered one, that is agreement.
+    fn handle_offer_draw(&mut self, game_id: &str, player_id: &str, request_id: Option<String>) {
+        if !self.match_running.load(Ordering::Relaxed) {
+            self.send_error(player_id, ErrorCode::DrawNotAllowed, request_id);
+            return;
+        }
+        match self.draw_offered_by.as_deref() {
+            Some(offered_by) if offered_by == player_id => {}
+            Some(_) => self.agree_draw(game_id),
+            None => {
+                info!(game_id, player_id, "Draw offered");
+                self.draw_offered_by = Some(player_id.to_string());
+                self.log.record(GameEvent::DrawOffered {
+                    player: player_id.to_string(),
+                });
+                self.broadcast(ServerMessage::DrawOffered {
+                    player: player_id.to_string(),
+                });
+            }
+        }
+    }
+
+    fn handle_accept_draw(&mut self, game_id: &str, player_id: &str, request_id: Option<String>) {
+        if !self.match_running.load(Ordering::Relaxed) {
+            self.send_error(player_id, ErrorCode::DrawNotAllowed, request_id);
+            return;
+        }
+        if self
+            .draw_offered_by
+            .as_deref()
+            .is_none_or(|offered_by| offered_by == player_id)
+        {
+            self.send_error(player_id, ErrorCode::NoDrawOffer, request_id);
+            return;
+        }
+        self.agree_draw(game_id);
+    }
+
+    /// Both players want a draw: the match ends there without a winner
+    fn agree_draw(&mut self, game_id: &str) {
+        info!(game_id, "Draw agreed");
+        let rounds = self.rounds_played();
+        // The round in progress is abandoned, not scored
+        self.session.timeout_round();
+        self.round_deadline = None;
+        self.end_game(game_id, None, rounds, GameEndReason::DrawAgreed);
+    }
+
+    /// Whether the round timer is stopped, by a pause or an absent player
+    fn is_paused(&self) -> bool {
+        self.paused_by.is_some() || !self.away.is_empty()
+    }
+
+    fn stop_clock(&mut self) {
+        if let Some(deadline) = self.round_deadline.take() {
+            self.paused_remaining = Some(deadline.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    /// Run the round timer again, unless something still holds it
+    fn restart_clock(&mut self) {
+        if self.is_paused() {
+            return;
+        }
+        if let Some(remaining) = self.paused_remaining.take() {
+            self.round_deadline = Some(Instant::now() + remaining);
+        }
+    }
+
+    fn remaining_round_time(&self) -> Duration {
+        match (self.round_deadline, self.paused_remaining) {
+            (Some(deadline), _) => deadline.saturating_duration_since(Instant::now()),
+            (None, Some(remaining)) => remaining,
+            (None, None) => Duration::ZERO,
+        }
+    }
+
+    /// Players didn't come back in time, after a restart or a dropped connection
+    fn handle_resume_timeout(&mut self, game_id: &str) {
+        // A match with one player still there goes to them
+        let mid_match =
+            self.match_running.load(Ordering::Relaxed) || self.paused_round.is_some();
+        let leaver = match self.away.as_slice() {
+            [leaver] if mid_match && self.services.forfeits.is_some() => Some(leaver.clone()),
+            _ => None,
+        };
+        for player in std::mem::take(&mut self.away) {
+            info!(game_id, player, "Player did not resume game");
+            self.log.record(GameEvent::Disconnect {
+                player: player.clone(),
+            });
+            if let Some(opponent) = self.session.opponent_of(&player) {
+                self.send_to(opponent, ServerMessage::OpponentDisconnected);
+            }
+        }
+        match leaver {
+            Some(leaver) => self.forfeit(game_id, &leaver),
+            None => self.save_log(game_id),
+        }
+    }
+
+    /// `leaver` walked out of a running match: it ends there and their
+    /// opponent wins
+    fn forfeit(&mut self, game_id: &str, leaver: &str) {
+        let Some(winner) = self.session.opponent_of(leaver).map(str::to_string) else {
+            return;
+        };
+        info!(game_id, leaver, winner, "Match forfeited");
+        if let Some(forfeits) = &self.services.forfeits {
+            forfeits.record(leaver, std::time::Instant::now());
+        }
+        let rounds = self.rounds_played();
+        self.round_deadline = None;
+        self.resume_deadline = None;
+        self.end_game(game_id, Some(winner), rounds, GameEndReason::Forfeit);
+    }
+
+    /// Number of the round in progress (or waiting to start over), for a
+    /// match that ends early
+    fn rounds_played(&self) -> u32 {
+        self.session
+            .current_round_number()
+            .or(self.paused_round.as_ref().map(|round| round.number))
+            .unwrap_or(0)
+    }
+
+    fn progress(&self) -> GameProgress {
+        let round = match (self.session.current_round_number(), self.session.current_word()) {
+            (Some(number), Some(word)) => Some(RoundSnapshot {
+                number,
+                word: word.clone(),
+            }),
+            _ => self.paused_round.clone(),
         };
+        GameProgress {
+            scores: self.session.scores(),
+            seed: self.sequence.seed(),
+            sequence_state: self.sequence.state(),
+            round,
+        }
+    }
+
+    /// Check an answer against the dictionary (supports multiple readings per kanji)
+    fn handle_answer(
+        &mut self,
+        game_id: &str,
+        player_id: &str,
+        answer: &str,
+        round: Option<u32>,
+    ) {
+        if let Some(round) = round
+            && self.session.current_round_number() != Some(round)
+        {
+            debug!(game_id, player_id, round, "Stale answer");
+            self.send_to(player_id, ServerMessage::StaleAnswer { round });
+            return;
+        }
 
-        // Only timeout if we're still on the same round
-        if game.session.current_round_number() != Some(round_number) {
+        let (Some(kanji), Some(round_number)) = (
+            self.session.current_kanji().map(str::to_string),
+            self.session.current_round_number(),
+        ) else {
+            self.send_to(player_id, ServerMessage::WrongAnswer);
             return;
+        };
+
+        debug!(game_id, player_id, answer, kanji, "Player submitting answer");
+
+        // The drawn reading needs no lookup; other readings are checked in the dictionary
+        let validation_started = std::time::Instant::now();
+        let drawn = self.session.submit_answer(player_id, answer);
+        let valid = drawn.is_some() || self.services.words.is_valid_reading(&kanji, answer);
+        self.services
+            .metrics
+            .answer_validated(validation_started.elapsed());
+
+        let outcome = match drawn {
+            Some(outcome) => outcome,
+            None => {
+                if !valid {
+                    debug!(player_id, answer, "Wrong answer");
+                    self.log.record(GameEvent::WrongAnswer {
+                        player: player_id.to_string(),
+                        answer: answer.to_string(),
+                    });
+                    self.send_to(player_id, ServerMessage::WrongAnswer);
+                    return;
+                }
+                let Some(outcome) = self.session.accept_correct_answer(player_id) else {
+                    return;
+                };
+                outcome
+            }
+        };
+        if let Some(winner) = &outcome.winner {
+            self.session.record_win(winner);
+            self.log.record(GameEvent::CorrectAnswer {
+                player: winner.clone(),
+                round: round_number,
+                correct_reading: outcome.correct_reading.clone(),
+            });
         }
 
-        let Some(outcome) = game.session.timeout_round() else {
+        info!(
+            game_id,
+            player_id,
+            round_winner = ?outcome.winner,
+            scores = ?self.session.scores(),
+            "Round ended"
+        );
+
+        self.finish_round(game_id, outcome, round_number, RoundEndKind::Answer);
+    }
+
+    /// A player doesn't know the answer. Both players must skip for the round to end.
+    fn handle_skip(&mut self, game_id: &str, player_id: &str) {
+        // Capture round number before record_skip potentially ends the round
+        let Some(round_number) = self.session.current_round_number() else {
+            return;
+        };
+
+        match self.session.record_skip(player_id) {
+            None => {}
+            Some(SkipResult::AlreadySkipped) => {
+                debug!(player_id, "Player already skipped");
+            }
+            Some(SkipResult::WaitingForOpponent) => {
+                info!(game_id, player_id, "Player skipped, waiting for opponent");
+                self.log.record(GameEvent::SkipVote {
+                    player: player_id.to_string(),
+                });
+                self.send_to(player_id, ServerMessage::SkipWaiting);
+            }
+            Some(SkipResult::BothSkipped(outcome)) => {
+                info!(game_id, "Both players skipped, ending round");
+                self.log.record(GameEvent::SkipVote {
+                    player: player_id.to_string(),
+                });
+                self.log.record(GameEvent::RoundSkipped {
+                    round: round_number,
+                    correct_reading: outcome.correct_reading.clone(),
+                });
+                self.finish_round(game_id, outcome, round_number, RoundEndKind::Skip);
+            }
+        }
+    }
+
+    fn handle_round_timeout(&mut self, game_id: &str) {
+        self.round_deadline = None;
+
+        let Some(round_number) = self.session.current_round_number() else {
+            return;
+        };
+        let Some(outcome) = self.session.timeout_round() else {
             return;
         };
 
         info!(game_id, round_number, "Round timed out");
+        self.log.record(GameEvent::Timeout {
+            round: round_number,
+            correct_reading: outcome.correct_reading.clone(),
+        });
+        self.finish_round(game_id, outcome, round_number, RoundEndKind::Timeout);
+    }
 
-        let game_winner = game.session.game_winner().map(|s| s.to_string());
+    fn handle_rematch(&mut self, game_id: &str, player_id: &str) {
+        let wants_rematch = self.session.request_rematch(player_id);
+        if wants_rematch.is_some() {
+            self.log.record(GameEvent::RematchRequested {
+                player: player_id.to_string(),
+            });
+        }
+        match wants_rematch {
+            Some(true) => {
+                info!(game_id, player_id, "Both players want rematch, starting new game");
+                self.session.reset_for_rematch();
+                self.round_deadline = None;
+                self.sequence = WordSequence::new(self.sequence.next_seed());
+                // GameStart resets the frontend state
+                self.start_game(game_id);
+            }
+            Some(false) => {
+                info!(game_id, player_id, "Player wants rematch, waiting for opponent");
+                self.send_to(player_id, ServerMessage::RematchWaiting);
+            }
+            None => {}
+        }
+    }
 
-        game.broadcast(ServerMessage::RoundResult {
+    /// Announce the round result, then end the game or start the next round.
+    /// The game stays around after it ends to allow a rematch; it is only
+    /// torn down when a player disconnects.
+    fn finish_round(
+        &mut self,
+        game_id: &str,
+        outcome: RoundOutcome,
+        round_number: u32,
+        kind: RoundEndKind,
+    ) {
+        self.round_deadline = None;
+        // Draw offers only stand for the round they were made in
+        self.draw_offered_by = None;
+        self.services.metrics.round_finished(self.services.mode, kind);
+        self.broadcast(ServerMessage::RoundResult {
             winner: outcome.winner,
             correct_reading: outcome.correct_reading,
         });
 
-        Some(game_winner)
-    };
-
-    let Some(game_winner) = timeout_result else {
-        return;
-    };
-
-    continue_or_end_game(
-        &games,
-        &words,
-        timeout,
-        &game_id,
-        game_winner,
-        round_number,
-        &player_games,
-        cleanup_game,
-    )
-    .await;
-}
-
-pub async fn continue_or_end_game(
-    games: &Arc<DashMap<String, ActiveGame>>,
-    words: &WordRepository,
-    timeout: Duration,
-    game_id: &str,
-    game_winner: Option<String>,
-    round_number: u32,
-    player_games: &Arc<DashMap<String, String>>,
-    cleanup_game: Arc<dyn CleanupGame>,
-) {
-    // Check for winner or max rounds reached
-    // Note: We don't cleanup here to allow rematch. Cleanup happens on disconnect.
-    if let Some(winner) = game_winner {
-        info!(winner, "Game ended - winner by score");
-        if let Some(game) = games.get(game_id) {
-            game.broadcast(ServerMessage::GameEnd {
-                winner: Some(winner),
-            });
+        if let Some(winner) = self.session.game_winner(self.services.rules.wins_needed) {
+            info!(game_id, winner, "Game ended - winner by score");
+            let winner = Some(winner.to_string());
+            self.end_game(game_id, winner, round_number, GameEndReason::Score);
+            return;
         }
-        return;
-    }
 
-    if round_number >= MAX_ROUNDS {
-        info!(round_number, "Game ended - max rounds reached");
-        if let Some(game) = games.get(game_id) {
-            let (p1_score, p2_score) = game.session.scores();
+        if round_number >= self.services.rules.max_rounds {
+            info!(game_id, round_number, "Game ended - max rounds reached");
+            let (p1_score, p2_score) = self.session.scores();
             let winner = match p1_score.cmp(&p2_score) {
-                std::cmp::Ordering::Greater => Some(game.session.player1.clone()),
-                std::cmp::Ordering::Less => Some(game.session.player2.clone()),
+                std::cmp::Ordering::Greater => Some(self.session.player1.clone()),
+                std::cmp::Ordering::Less => Some(self.session.player2.clone()),
                 std::cmp::Ordering::Equal => None, // Draw
             };
-            game.broadcast(ServerMessage::GameEnd { winner });
+            self.end_game(game_id, winner, round_number, GameEndReason::MaxRounds);
+            return;
         }
-        return;
+
+        self.start_round(game_id, round_number + 1);
     }
 
-    // Start next round
-    let Some(word) = words.get_random().await else {
-        return;
-    };
+    fn end_game(
+        &mut self,
+        game_id: &str,
+        winner: Option<String>,
+        rounds: u32,
+        reason: GameEndReason,
+    ) {
+        self.match_running.store(false, Ordering::Relaxed);
+        self.paused_by = None;
+        self.unpause_votes.clear();
+        self.paused_remaining = None;
+        self.draw_offered_by = None;
+        self.record_match(game_id, winner.clone(), rounds, reason);
+        self.log.record(GameEvent::GameEnd {
+            winner: winner.clone(),
+            reason,
+        });
+        self.save_log(game_id);
+        self.broadcast(ServerMessage::GameEnd { winner, reason });
+    }
 
-    let next_round = round_number + 1;
-    let readings = words.get_readings_for_kanji(&word.kanji).await;
-    info!(
-        round = next_round,
-        kanji = word.kanji,
-        "Starting next round"
-    );
+    /// Store the finished match in the background so the game task never waits on the database
+    fn record_match(
+        &self,
+        game_id: &str,
+        winner: Option<String>,
+        rounds: u32,
+        reason: GameEndReason,
+    ) {
+        let (player1_score, player2_score) = self.session.scores();
+        let record = MatchRecord {
+            game_id: game_id.to_string(),
+            mode: self.services.mode.to_string(),
+            player1: self.session.player1.clone(),
+            player2: self.session.player2.clone(),
+            seed: self.sequence.seed(),
+            player1_score,
+            player2_score,
+            winner,
+            rounds,
+            reason,
+        };
+        let matches = self.services.matches.clone();
+        tokio::spawn(async move {
+            if let Err(e) = matches.record(&record).await {
+                error!(game_id = record.game_id, "Failed to record match: {}", e);
+            }
+        });
+    }
 
-    if let Some(mut game) = games.get_mut(game_id) {
-        game.broadcast(ServerMessage::RoundStart {
-            kanji: word.kanji.clone(),
-            round: next_round,
-            readings,
+    /// Persist the event log so far in the background. Saved when a match ends
+    /// and again when a player leaves, so rematches extend the same replay.
+    fn save_log(&self, game_id: &str) {
+        let has_rounds = self
+            .log
+            .events()
+            .iter()
+            .any(|e| matches!(e.event, GameEvent::RoundStart { .. }));
+        if !has_rounds {
+            return;
+        }
+        let log = GameLog {
+            id: self.log_id.clone(),
+            game_id: game_id.to_string(),
+            mode: self.services.mode.to_string(),
+            player1: self.session.player1.clone(),
+            player2: self.session.player2.clone(),
+            events: self.log.events().to_vec(),
+        };
+        let logs = self.services.logs.clone();
+        tokio::spawn(async move {
+            if let Err(e) = logs.save(&log).await {
+                error!(game_id = log.game_id, "Failed to save game log: {}", e);
+            }
         });
-        game.session.start_round(next_round, word);
-    }
-
-    spawn_round_timeout(
-        timeout,
-        games.clone(),
-        words.clone(),
-        game_id.to_string(),
-        next_round,
-        player_games.clone(),
-        cleanup_game,
-    );
+    }
 }
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+/// Caps how many games (pending, running and solo runs) exist at once, so a
+/// flood of new games can't exhaust the server. Games are counted on demand
+/// from the live game maps rather than tracked separately.
+#[derive(Clone)]
+pub struct GameCapacity {
+    max_games: usize,
+    count: Arc<dyn Fn() -> usize + Send + Sync>,
+}
+
+impl GameCapacity {
+    pub fn new(max_games: usize, count: impl Fn() -> usize + Send + Sync + 'static) -> Self {
+        Self {
+            max_games,
+            count: Arc::new(count),
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        (self.count)()
+    }
+
+    /// Whether new games should be refused
+    pub fn is_full(&self) -> bool {
+        self.games() >= self.max_games
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn full_once_the_count_reaches_the_cap() {
+        let games = Arc::new(AtomicUsize::new(0));
+        let counted = games.clone();
+        let capacity = GameCapacity::new(2, move || counted.load(Ordering::Relaxed));
+
+        games.store(1, Ordering::Relaxed);
+        assert!(!capacity.is_full());
+        games.store(2, Ordering::Relaxed);
+        assert!(capacity.is_full());
+    }
+}
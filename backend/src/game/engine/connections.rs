@@ -0,0 +1,240 @@
+use super::outbox::Outbox;
+use crate::game::core::messages::{ErrorCode, ServerMessage};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::oneshot;
+use tracing::info;
+
+/// A connected client as seen by operators
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectedClient {
+    pub connection_id: u64,
+    /// Name of the handler serving the connection
+    pub mode: &'static str,
+    /// Client address, if known
+    pub ip: Option<IpAddr>,
+    /// Player id once the client has created, joined or resumed something
+    pub player: Option<String>,
+    pub connected_secs: u64,
+}
+
+struct Entry {
+    mode: &'static str,
+    ip: Option<IpAddr>,
+    player: Option<String>,
+    connected_at: Instant,
+    tx: Outbox,
+    /// Taken when the connection is kicked
+    kick: Option<oneshot::Sender<()>>,
+}
+
+/// Every open WebSocket connection, so operators can see who is online,
+/// message everyone and close a connection. Also caps how many connections
+/// one address can hold open.
+pub struct Connections {
+    next_id: AtomicU64,
+    entries: DashMap<u64, Entry>,
+    per_ip: DashMap<IpAddr, usize>,
+    max_per_ip: usize,
+}
+
+impl Connections {
+    pub fn new(max_per_ip: usize) -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            entries: DashMap::new(),
+            per_ip: DashMap::new(),
+            max_per_ip,
+        }
+    }
+
+    /// Track a new connection until the returned entry is dropped.
+    /// Returns None if `ip` already has as many connections as it may open.
+    pub fn register(
+        self: &Arc<Self>,
+        mode: &'static str,
+        ip: Option<IpAddr>,
+        tx: Outbox,
+    ) -> Option<ConnectionEntry> {
+        if let Some(ip) = ip {
+            let mut count = self.per_ip.entry(ip).or_insert(0);
+            if *count >= self.max_per_ip {
+                return None;
+            }
+            *count += 1;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (kick, kicked) = oneshot::channel();
+        let connected_at = Instant::now();
+        self.entries.insert(
+            id,
+            Entry {
+                mode,
+                ip,
+                player: None,
+                connected_at,
+                tx,
+                kick: Some(kick),
+            },
+        );
+        Some(ConnectionEntry {
+            id,
+            ip,
+            connected_at,
+            connections: self.clone(),
+            kicked,
+        })
+    }
+
+    /// Open connections, oldest first
+    pub fn list(&self) -> Vec<ConnectedClient> {
+        let mut clients: Vec<ConnectedClient> = self
+            .entries
+            .iter()
+            .map(|entry| ConnectedClient {
+                connection_id: *entry.key(),
+                mode: entry.mode,
+                ip: entry.ip,
+                player: entry.player.clone(),
+                connected_secs: entry.connected_at.elapsed().as_secs(),
+            })
+            .collect();
+        clients.sort_by_key(|client| client.connection_id);
+        clients
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Send a message to every open connection. Returns how many were sent.
+    pub fn broadcast(&self, msg: &ServerMessage) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.tx.send(msg.clone()).is_ok())
+            .count()
+    }
+
+    /// Tell the client why, then close its connection. The player leaves
+    /// their game as on any disconnect. Returns false if there is no such connection.
+    pub fn kick(&self, connection_id: u64) -> bool {
+        let Some(mut entry) = self.entries.get_mut(&connection_id) else {
+            return false;
+        };
+        info!(connection_id, player = ?entry.player, "Kicking connection");
+        let _ = entry.tx.send(ServerMessage::Error {
+            code: ErrorCode::Kicked,
+            message: ErrorCode::Kicked.description().to_string(),
+            request_id: None,
+        });
+        if let Some(kick) = entry.kick.take() {
+            let _ = kick.send(());
+        }
+        true
+    }
+}
+
+/// A connection's place in [`Connections`]; removes it when dropped
+pub struct ConnectionEntry {
+    id: u64,
+    ip: Option<IpAddr>,
+    connected_at: Instant,
+    connections: Arc<Connections>,
+    kicked: oneshot::Receiver<()>,
+}
+
+impl ConnectionEntry {
+    pub fn connected_at(&self) -> Instant {
+        self.connected_at
+    }
+
+    pub fn set_player(&self, player: Option<&str>) {
+        if let Some(mut entry) = self.connections.entries.get_mut(&self.id)
+            && entry.player.as_deref() != player
+        {
+            entry.player = player.map(str::to_string);
+        }
+    }
+
+    /// Resolves once an operator kicks this connection
+    pub async fn kicked(&mut self) {
+        if (&mut self.kicked).await.is_err() {
+            // Only dropped along with the entry, so never in practice
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+impl Drop for ConnectionEntry {
+    fn drop(&mut self) {
+        self.connections.entries.remove(&self.id);
+        if let Some(ip) = self.ip {
+            self.connections.per_ip.remove_if_mut(&ip, |_, count| {
+                *count -= 1;
+                *count == 0
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::engine::outbox;
+
+    #[tokio::test]
+    async fn kick_notifies_the_client_and_its_entry() {
+        let connections = Arc::new(Connections::new(10));
+        let (tx, mut rx) = outbox::channel(4);
+        let mut entry = connections.register("ephemeral", None, tx).unwrap();
+        entry.set_player(Some("Alice"));
+
+        let listed = connections.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].player.as_deref(), Some("Alice"));
+
+        let id = listed[0].connection_id;
+        assert!(connections.kick(id));
+        entry.kicked().await;
+        assert!(matches!(
+            rx.recv().await,
+            Some(ServerMessage::Error {
+                code: ErrorCode::Kicked,
+                ..
+            })
+        ));
+
+        drop(entry);
+        assert!(connections.is_empty());
+        assert!(!connections.kick(id));
+    }
+
+    #[test]
+    fn limits_connections_per_address() {
+        let connections = Arc::new(Connections::new(2));
+        let (tx, _rx) = outbox::channel(4);
+        let ip: IpAddr = "198.51.100.7".parse().unwrap();
+
+        let first = connections
+            .register("ephemeral", Some(ip), tx.clone())
+            .unwrap();
+        let _second = connections.register("daily", Some(ip), tx.clone()).unwrap();
+        assert!(
+            connections
+                .register("ghost", Some(ip), tx.clone())
+                .is_none()
+        );
+        assert!(connections.register("ghost", None, tx.clone()).is_some());
+
+        drop(first);
+        assert!(connections.register("ghost", Some(ip), tx).is_some());
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// Flipped once the server starts draining for a deploy, which never switches
+/// back, or while an operator has turned on maintenance. Connections check it
+/// to refuse new games.
+#[derive(Clone)]
+pub struct DrainSwitch {
+    deadline: Arc<watch::Sender<Option<Instant>>>,
+    maintenance: Arc<AtomicBool>,
+}
+
+impl DrainSwitch {
+    pub fn new() -> Self {
+        Self {
+            deadline: Arc::new(watch::Sender::new(None)),
+            maintenance: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Refuse new games without shutting down, until switched off again
+    pub fn set_maintenance(&self, on: bool) {
+        self.maintenance.store(on, Ordering::Relaxed);
+    }
+
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::Relaxed)
+    }
+
+    /// Whether new games should be refused
+    pub fn refuses_new_games(&self) -> bool {
+        self.is_draining() || self.in_maintenance()
+    }
+
+    /// Start draining, giving running games `timeout` to finish.
+    /// Returns false if the server was already draining.
+    pub fn start(&self, timeout: Duration) -> bool {
+        self.deadline.send_if_modified(|deadline| {
+            if deadline.is_some() {
+                return false;
+            }
+            *deadline = Some(Instant::now() + timeout);
+            true
+        })
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.deadline.borrow().is_some()
+    }
+
+    /// When running games are cut off, once draining
+    pub fn deadline(&self) -> Option<Instant> {
+        *self.deadline.borrow()
+    }
+
+    /// Wait until draining starts and return the deadline
+    pub async fn started(&self) -> Instant {
+        let mut rx = self.deadline.subscribe();
+        loop {
+            if let Some(deadline) = *rx.borrow_and_update() {
+                return deadline;
+            }
+            if rx.changed().await.is_err() {
+                // The sender lives in `self`, so this can't happen
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+impl Default for DrainSwitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn starts_once_and_wakes_waiters() {
+        let switch = DrainSwitch::new();
+        assert!(!switch.is_draining());
+
+        let waiter = tokio::spawn({
+            let switch = switch.clone();
+            async move { switch.started().await }
+        });
+        assert!(switch.start(Duration::from_secs(60)));
+        let deadline = waiter.await.unwrap();
+        assert_eq!(switch.deadline(), Some(deadline));
+
+        // A second request keeps the first deadline
+        assert!(!switch.start(Duration::from_secs(1)));
+        assert_eq!(switch.deadline(), Some(deadline));
+    }
+
+    #[test]
+    fn maintenance_refuses_new_games_until_switched_off() {
+        let switch = DrainSwitch::new();
+        switch.set_maintenance(true);
+        assert!(switch.refuses_new_games());
+        assert!(!switch.is_draining());
+
+        switch.set_maintenance(false);
+        assert!(!switch.refuses_new_games());
+    }
+}
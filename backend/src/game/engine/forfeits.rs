@@ -0,0 +1,249 @@
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// How forfeits are punished with queue cooldowns
+#[derive(Debug, Clone)]
+pub struct ForfeitConfig {
+    /// How long a forfeit counts against the player
+    pub window: Duration,
+    /// Forfeits within the window that carry no cooldown
+    pub free_forfeits: u32,
+    /// Forfeits within the window from one address, under any names, that
+    /// carry no cooldown for that address. Players behind a shared NAT or
+    /// carrier-grade NAT share an address, so this is kept well above
+    /// `free_forfeits`: set too low, one leaver locks out their neighbours.
+    pub free_address_forfeits: u32,
+    /// Cooldown for the first forfeit past the free ones, doubled for each further one
+    pub cooldown_base: Duration,
+    /// Upper bound for the escalating cooldown
+    pub cooldown_max: Duration,
+}
+
+impl Default for ForfeitConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60 * 60),
+            free_forfeits: 1,
+            free_address_forfeits: 10,
+            cooldown_base: Duration::from_secs(60),
+            cooldown_max: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+/// Recent forfeits per player, to keep repeat leavers out of the queue for a
+/// while. Shared by a mode's game tasks, which record forfeits, and its queue,
+/// which turns the leavers away.
+///
+/// Names are chosen by the client, so forfeits also count against the address
+/// of the connection that held the seat: a leaver can't shed the cooldown by
+/// queueing under a new name. Addresses get far more free forfeits than
+/// names (see [`ForfeitConfig::free_address_forfeits`]), since one address can
+/// be many players.
+pub struct Forfeits {
+    config: ForfeitConfig,
+    by_player: DashMap<String, Vec<Instant>>,
+    by_address: DashMap<IpAddr, Vec<Instant>>,
+    /// Address of the connection each queued or playing player joined from
+    seats: DashMap<String, IpAddr>,
+}
+
+impl Forfeits {
+    pub fn new(config: ForfeitConfig) -> Self {
+        Self {
+            config,
+            by_player: DashMap::new(),
+            by_address: DashMap::new(),
+            seats: DashMap::new(),
+        }
+    }
+
+    /// Remember which address the player joined from, so a forfeit of theirs
+    /// counts against it too
+    pub fn take_seat(&self, player: &str, ip: Option<IpAddr>) {
+        match ip {
+            Some(ip) => {
+                self.seats.insert(player.to_string(), ip);
+            }
+            None => self.leave_seat(player),
+        }
+    }
+
+    /// The player is gone without a forfeit to record
+    pub fn leave_seat(&self, player: &str) {
+        self.seats.remove(player);
+    }
+
+    pub fn record(&self, player: &str, now: Instant) {
+        let window = self.config.window;
+        let current = |times: &mut Vec<Instant>| {
+            times
+                .last()
+                .is_some_and(|&at| now.duration_since(at) < window)
+        };
+        self.by_player.retain(|_, times| current(times));
+        self.by_address.retain(|_, times| current(times));
+        self.by_player.entry(player.to_string()).or_default().push(now);
+        if let Some((_, ip)) = self.seats.remove(player) {
+            self.by_address.entry(ip).or_default().push(now);
+        }
+    }
+
+    /// Time left before the player, joining from `ip`, may queue again, None
+    /// if they may now
+    pub fn cooldown(&self, player: &str, ip: Option<IpAddr>, now: Instant) -> Option<Duration> {
+        let by_player = self
+            .by_player
+            .get(player)
+            .and_then(|times| self.remaining(&times, self.config.free_forfeits, now));
+        let by_address = ip
+            .and_then(|ip| self.by_address.get(&ip))
+            .and_then(|times| {
+                self.remaining(&times, self.config.free_address_forfeits, now)
+            });
+        by_player.max(by_address)
+    }
+
+    /// Cooldown left after `times`, of which the first `free` cost nothing
+    fn remaining(&self, times: &[Instant], free: u32, now: Instant) -> Option<Duration> {
+        let recent: Vec<Instant> = times
+            .iter()
+            .copied()
+            .filter(|&at| now.duration_since(at) < self.config.window)
+            .collect();
+        let penalized = u32::try_from(recent.len())
+            .unwrap_or(u32::MAX)
+            .checked_sub(free)
+            .filter(|&n| n > 0)?;
+        let cooldown = self
+            .config
+            .cooldown_base
+            .saturating_mul(2u32.saturating_pow(penalized - 1))
+            .min(self.config.cooldown_max);
+        let last = *recent.last()?;
+        cooldown
+            .checked_sub(now.duration_since(last))
+            .filter(|left| !left.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forfeits() -> Forfeits {
+        Forfeits::new(ForfeitConfig {
+            window: Duration::from_secs(3600),
+            free_forfeits: 1,
+            free_address_forfeits: 2,
+            cooldown_base: Duration::from_secs(60),
+            cooldown_max: Duration::from_secs(200),
+        })
+    }
+
+    #[test]
+    fn first_forfeit_is_free() {
+        let forfeits = forfeits();
+        let now = Instant::now();
+        forfeits.record("alice", now);
+        assert_eq!(forfeits.cooldown("alice", None, now), None);
+        assert_eq!(forfeits.cooldown("bob", None, now), None);
+    }
+
+    #[test]
+    fn repeat_forfeits_escalate_up_to_the_cap() {
+        let forfeits = forfeits();
+        let now = Instant::now();
+        forfeits.record("alice", now);
+        forfeits.record("alice", now);
+        assert_eq!(
+            forfeits.cooldown("alice", None, now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            forfeits.cooldown("alice", None, now + Duration::from_secs(45)),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            forfeits.cooldown("alice", None, now + Duration::from_secs(60)),
+            None
+        );
+
+        forfeits.record("alice", now);
+        assert_eq!(
+            forfeits.cooldown("alice", None, now),
+            Some(Duration::from_secs(120))
+        );
+        forfeits.record("alice", now);
+        assert_eq!(
+            forfeits.cooldown("alice", None, now),
+            Some(Duration::from_secs(200))
+        );
+    }
+
+    #[test]
+    fn forfeits_outside_the_window_are_forgotten() {
+        let forfeits = forfeits();
+        let start = Instant::now();
+        forfeits.record("alice", start);
+        forfeits.record("alice", start);
+        let later = start + Duration::from_secs(3600);
+        forfeits.record("bob", later);
+        assert_eq!(forfeits.cooldown("alice", None, later), None);
+        assert!(forfeits.by_player.get("alice").is_none());
+    }
+
+    #[test]
+    fn forfeits_count_against_the_seat_address() {
+        let forfeits = forfeits();
+        let now = Instant::now();
+        let home: IpAddr = "198.51.100.1".parse().unwrap();
+        let elsewhere: IpAddr = "198.51.100.2".parse().unwrap();
+        forfeits.take_seat("alice", Some(home));
+        forfeits.record("alice", now);
+        forfeits.take_seat("alicia", Some(home));
+        forfeits.record("alicia", now);
+        forfeits.take_seat("alyssa", Some(home));
+        forfeits.record("alyssa", now);
+
+        assert_eq!(
+            forfeits.cooldown("alina", Some(home), now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(forfeits.cooldown("alina", Some(elsewhere), now), None);
+        // Each name on its own is still within its free forfeit
+        assert_eq!(forfeits.cooldown("alice", Some(elsewhere), now), None);
+    }
+
+    #[test]
+    fn one_leaver_does_not_lock_out_others_on_the_same_address() {
+        let forfeits = forfeits();
+        let now = Instant::now();
+        let office: IpAddr = "198.51.100.1".parse().unwrap();
+        for _ in 0..2 {
+            forfeits.take_seat("alice", Some(office));
+            forfeits.record("alice", now);
+        }
+
+        // Alice has used up her free forfeit, the office has not
+        assert_eq!(
+            forfeits.cooldown("alice", Some(office), now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(forfeits.cooldown("bob", Some(office), now), None);
+    }
+
+    #[test]
+    fn leaving_a_seat_keeps_the_address_out_of_later_forfeits() {
+        let forfeits = forfeits();
+        let now = Instant::now();
+        let home: IpAddr = "198.51.100.1".parse().unwrap();
+        forfeits.take_seat("alice", Some(home));
+        forfeits.leave_seat("alice");
+        forfeits.record("alice", now);
+        forfeits.record("alice", now);
+        assert!(forfeits.by_address.is_empty());
+        assert_eq!(forfeits.cooldown("bob", Some(home), now), None);
+    }
+}
@@ -0,0 +1,251 @@
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{Histogram, exponential_buckets};
+use prometheus_client::registry::Registry;
+use std::time::Duration;
+
+/// Content type of [`Metrics::encode`]'s output
+pub const METRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// How a two-player round ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundEndKind {
+    Answer,
+    Skip,
+    Timeout,
+}
+
+impl RoundEndKind {
+    fn label(self) -> &'static str {
+        match self {
+            RoundEndKind::Answer => "answer",
+            RoundEndKind::Skip => "skip",
+            RoundEndKind::Timeout => "timeout",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct HandlerLabels {
+    handler: &'static str,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ModeLabels {
+    mode: &'static str,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RoundLabels {
+    mode: &'static str,
+    outcome: &'static str,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct BudgetLabels {
+    budget: &'static str,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct LimitLabels {
+    limit: &'static str,
+}
+
+/// Prometheus metrics for `/metrics`. Events are recorded as they happen;
+/// game and queue counts are set from the live state on each scrape.
+pub struct Metrics {
+    registry: Registry,
+    connections: Family<HandlerLabels, Gauge>,
+    pending_games: Gauge,
+    active_games: Family<ModeLabels, Gauge>,
+    queue_depth: Gauge,
+    queue_wait: Histogram,
+    rounds: Family<RoundLabels, Counter>,
+    answer_validation: Histogram,
+    broadcast_lagged: Family<HandlerLabels, Counter>,
+    broadcast_dropped: Family<HandlerLabels, Counter>,
+    rate_limited: Family<BudgetLabels, Counter>,
+    limit_rejections: Family<LimitLabels, Counter>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut metrics = Self {
+            registry: Registry::with_prefix("yomitaisen"),
+            connections: Family::default(),
+            pending_games: Gauge::default(),
+            active_games: Family::default(),
+            queue_depth: Gauge::default(),
+            // 0.5s to about 4 minutes
+            queue_wait: Histogram::new(exponential_buckets(0.5, 2.0, 10)),
+            rounds: Family::default(),
+            // 1µs to about 0.26s
+            answer_validation: Histogram::new(exponential_buckets(0.000_001, 4.0, 10)),
+            broadcast_lagged: Family::default(),
+            broadcast_dropped: Family::default(),
+            rate_limited: Family::default(),
+            limit_rejections: Family::default(),
+        };
+
+        let registry = &mut metrics.registry;
+        registry.register(
+            "connections",
+            "Open WebSocket connections by handler",
+            metrics.connections.clone(),
+        );
+        registry.register(
+            "pending_games",
+            "Ephemeral games waiting for an opponent",
+            metrics.pending_games.clone(),
+        );
+        registry.register(
+            "active_games",
+            "Running games and solo runs by mode",
+            metrics.active_games.clone(),
+        );
+        registry.register(
+            "matchmaking_queue_depth",
+            "Players waiting in the matchmaking queue",
+            metrics.queue_depth.clone(),
+        );
+        registry.register(
+            "matchmaking_wait_seconds",
+            "Time players waited in the matchmaking queue before a match",
+            metrics.queue_wait.clone(),
+        );
+        registry.register(
+            "rounds",
+            "Two-player rounds finished, by how they ended",
+            metrics.rounds.clone(),
+        );
+        registry.register(
+            "answer_validation_seconds",
+            "Time spent checking a submitted answer",
+            metrics.answer_validation.clone(),
+        );
+        registry.register(
+            "broadcast_lagged",
+            "Times a connection fell behind its outgoing message queue and was resynced",
+            metrics.broadcast_lagged.clone(),
+        );
+        registry.register(
+            "broadcast_dropped_messages",
+            "Outgoing messages dropped because a connection fell behind",
+            metrics.broadcast_dropped.clone(),
+        );
+        registry.register(
+            "rate_limited",
+            "Client messages rejected by rate limiting, by budget",
+            metrics.rate_limited.clone(),
+        );
+        registry.register(
+            "limit_rejections",
+            "Connections and new games refused by capacity limits",
+            metrics.limit_rejections.clone(),
+        );
+        metrics
+    }
+
+    pub fn connection_opened(&self, handler: &'static str) {
+        self.connections
+            .get_or_create(&HandlerLabels { handler })
+            .inc();
+    }
+
+    pub fn connection_closed(&self, handler: &'static str) {
+        self.connections
+            .get_or_create(&HandlerLabels { handler })
+            .dec();
+    }
+
+    pub fn set_pending_games(&self, count: usize) {
+        self.pending_games.set(count as i64);
+    }
+
+    pub fn set_active_games(&self, mode: &'static str, count: usize) {
+        self.active_games
+            .get_or_create(&ModeLabels { mode })
+            .set(count as i64);
+    }
+
+    pub fn set_queue_depth(&self, count: usize) {
+        self.queue_depth.set(count as i64);
+    }
+
+    pub fn queue_waited(&self, waited: Duration) {
+        self.queue_wait.observe(waited.as_secs_f64());
+    }
+
+    pub fn round_finished(&self, mode: &'static str, outcome: RoundEndKind) {
+        let labels = RoundLabels {
+            mode,
+            outcome: outcome.label(),
+        };
+        self.rounds.get_or_create(&labels).inc();
+    }
+
+    pub fn answer_validated(&self, took: Duration) {
+        self.answer_validation.observe(took.as_secs_f64());
+    }
+
+    /// A connection's outgoing queue overflowed and `dropped` messages were skipped
+    pub fn broadcast_lagged(&self, handler: &'static str, dropped: u64) {
+        let labels = HandlerLabels { handler };
+        self.broadcast_lagged.get_or_create(&labels).inc();
+        self.broadcast_dropped
+            .get_or_create(&labels)
+            .inc_by(dropped);
+    }
+
+    pub fn rate_limited(&self, budget: &'static str) {
+        self.rate_limited
+            .get_or_create(&BudgetLabels { budget })
+            .inc();
+    }
+
+    /// A connection or new game was refused because `limit` was reached
+    pub fn limit_reached(&self, limit: &'static str) {
+        self.limit_rejections
+            .get_or_create(&LimitLabels { limit })
+            .inc();
+    }
+
+    /// Everything recorded so far, in the OpenMetrics text format
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        prometheus_client::encoding::text::encode(&mut out, &self.registry)
+            .expect("writing to a String cannot fail");
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_labelled_metrics() {
+        let metrics = Metrics::new();
+        metrics.connection_opened("ephemeral");
+        metrics.connection_opened("ephemeral");
+        metrics.connection_closed("ephemeral");
+        metrics.round_finished("matchmaking", RoundEndKind::Timeout);
+        metrics.broadcast_lagged("daily", 3);
+
+        let text = metrics.encode();
+        assert!(text.contains(r#"yomitaisen_connections{handler="ephemeral"} 1"#));
+        assert!(
+            text.contains(r#"yomitaisen_rounds_total{mode="matchmaking",outcome="timeout"} 1"#)
+        );
+        assert!(text.contains(r#"yomitaisen_broadcast_dropped_messages_total{handler="daily"} 3"#));
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
@@ -1,3 +1,11 @@
 pub mod active_game;
+pub mod capacity;
+pub mod connections;
+pub mod drain;
+pub mod forfeits;
+pub mod metrics;
+pub mod outbox;
+pub mod rate_limit;
 pub mod registry;
+pub mod solo_run;
 pub mod ws;
@@ -0,0 +1,326 @@
+use crate::game::core::messages::ServerMessage;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::Notify;
+use tracing::warn;
+
+/// How many times its capacity a queue may grow with messages that can't be
+/// dropped before the client is given up on
+const BACKLOG_FACTOR: usize = 4;
+
+/// The connection behind an [`Outbox`] is gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Closed;
+
+struct Queue {
+    messages: VecDeque<ServerMessage>,
+    /// Messages dropped since the owner last asked, see [`Outbox::lagged`]
+    dropped: u32,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    capacity: usize,
+    /// Queue length past which the connection is closed, see [`BACKLOG_FACTOR`]
+    backlog_limit: usize,
+    /// Wakes the receiver when a message is queued
+    ready: Notify,
+    /// Wakes whoever waits in [`Outbox::lagged`]
+    lagged: Notify,
+}
+
+impl Shared {
+    fn queue(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap()
+    }
+}
+
+/// Sending half of a connection's outgoing message queue.
+///
+/// The queue holds `capacity` messages. When a slow client lets it fill up,
+/// the oldest message that [`ServerMessage::is_droppable`] makes room for the
+/// new one and the drop is reported through [`Outbox::lagged`], so the
+/// connection can resync the client. Messages that aren't droppable, like
+/// round results and game ends, are queued past `capacity`, but only up to
+/// [`BACKLOG_FACTOR`] times it: beyond that the client has stopped reading,
+/// and the outbox closes so the connection is dropped.
+#[derive(Clone)]
+pub struct Outbox {
+    shared: Arc<Shared>,
+}
+
+/// Receiving half, owned by the connection's send task. Dropping it closes
+/// the queue, so later sends fail.
+pub struct OutboxReceiver {
+    shared: Arc<Shared>,
+}
+
+/// Queue for one connection's outgoing messages
+pub fn channel(capacity: usize) -> (Outbox, OutboxReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            messages: VecDeque::with_capacity(capacity),
+            dropped: 0,
+            closed: false,
+        }),
+        capacity: capacity.max(1),
+        backlog_limit: capacity.max(1) * BACKLOG_FACTOR,
+        ready: Notify::new(),
+        lagged: Notify::new(),
+    });
+    (
+        Outbox {
+            shared: shared.clone(),
+        },
+        OutboxReceiver { shared },
+    )
+}
+
+impl Outbox {
+    /// An outbox nobody reads, e.g. for a player who hasn't reconnected yet.
+    /// Every send fails.
+    pub fn detached() -> Self {
+        channel(1).0
+    }
+
+    /// Queue a message for the client, dropping an older droppable one if the
+    /// queue is full
+    pub fn send(&self, msg: ServerMessage) -> Result<(), Closed> {
+        let mut queue = self.shared.queue();
+        if queue.closed {
+            return Err(Closed);
+        }
+
+        if queue.messages.len() >= self.shared.capacity {
+            let oldest_droppable = queue.messages.iter().position(ServerMessage::is_droppable);
+            if let Some(index) = oldest_droppable {
+                queue.messages.remove(index);
+            }
+            if oldest_droppable.is_some() || msg.is_droppable() {
+                queue.dropped = queue.dropped.saturating_add(1);
+                self.shared.lagged.notify_one();
+            }
+            if oldest_droppable.is_none() && msg.is_droppable() {
+                // Nothing older can go, so the new message does
+                return Ok(());
+            }
+        }
+
+        if queue.messages.len() >= self.shared.backlog_limit {
+            warn!(
+                queued = queue.messages.len(),
+                "Client stopped reading, closing its connection"
+            );
+            queue.closed = true;
+            queue.messages.clear();
+            self.shared.ready.notify_one();
+            return Err(Closed);
+        }
+
+        queue.messages.push_back(msg);
+        self.shared.ready.notify_one();
+        Ok(())
+    }
+
+    /// Close the connection once what is already queued has been sent
+    pub fn close(&self) {
+        self.shared.queue().closed = true;
+        self.shared.ready.notify_one();
+    }
+
+    /// Whether both outboxes feed the same connection
+    pub fn same_channel(&self, other: &Outbox) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// Whether the connection is gone
+    pub fn is_closed(&self) -> bool {
+        self.shared.queue().closed
+    }
+
+    /// Wait until messages have been dropped, returning how many since the
+    /// last call
+    pub async fn lagged(&self) -> u32 {
+        loop {
+            {
+                let mut queue = self.shared.queue();
+                if queue.dropped > 0 {
+                    return std::mem::take(&mut queue.dropped);
+                }
+            }
+            self.shared.lagged.notified().await;
+        }
+    }
+}
+
+impl fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Outbox")
+            .field("capacity", &self.shared.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OutboxReceiver {
+    /// Next message to send, waiting until there is one. None once the
+    /// outbox was closed and everything queued before has been sent, or
+    /// because the client stopped reading.
+    pub async fn recv(&mut self) -> Option<ServerMessage> {
+        loop {
+            {
+                let mut queue = self.shared.queue();
+                if let Some(msg) = queue.messages.pop_front() {
+                    return Some(msg);
+                }
+                if queue.closed {
+                    return None;
+                }
+            }
+            self.shared.ready.notified().await;
+        }
+    }
+}
+
+impl Drop for OutboxReceiver {
+    fn drop(&mut self) {
+        let mut queue = self.shared.queue();
+        queue.closed = true;
+        queue.messages.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::core::messages::{ErrorCode, GameEndReason};
+    use std::time::Duration;
+
+    fn round_start(round: u32) -> ServerMessage {
+        ServerMessage::RoundStart {
+            kanji: "山".to_string(),
+            round,
+            readings: vec!["やま".to_string()],
+        }
+    }
+
+    fn round_result() -> ServerMessage {
+        ServerMessage::RoundResult {
+            winner: None,
+            correct_reading: "やま".to_string(),
+        }
+    }
+
+    async fn drain(rx: &mut OutboxReceiver, count: usize) -> Vec<ServerMessage> {
+        let mut received = Vec::new();
+        for _ in 0..count {
+            received.push(rx.recv().await.unwrap());
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn delivers_in_order() {
+        let (tx, mut rx) = channel(4);
+        tx.send(round_start(1)).unwrap();
+        tx.send(round_result()).unwrap();
+
+        assert_eq!(
+            drain(&mut rx, 2).await,
+            vec![round_start(1), round_result()]
+        );
+    }
+
+    #[tokio::test]
+    async fn full_queue_drops_oldest_droppable_message() {
+        let (tx, mut rx) = channel(2);
+        tx.send(round_result()).unwrap();
+        tx.send(round_start(1)).unwrap();
+        tx.send(round_start(2)).unwrap();
+
+        assert_eq!(tx.lagged().await, 1);
+        assert_eq!(
+            drain(&mut rx, 2).await,
+            vec![round_result(), round_start(2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn critical_messages_are_never_dropped() {
+        let (tx, mut rx) = channel(1);
+        tx.send(round_result()).unwrap();
+        tx.send(round_start(1)).unwrap();
+        let game_end = ServerMessage::GameEnd {
+            winner: None,
+            reason: GameEndReason::MaxRounds,
+        };
+        tx.send(game_end.clone()).unwrap();
+
+        // The round start had nowhere to go; both critical messages arrive
+        assert_eq!(tx.lagged().await, 1);
+        assert_eq!(
+            drain(&mut rx, 2).await,
+            vec![round_result(), game_end]
+        );
+    }
+
+    #[tokio::test]
+    async fn lag_is_reported_once() {
+        let (tx, _rx) = channel(1);
+        tx.send(round_start(1)).unwrap();
+        tx.send(round_start(2)).unwrap();
+        tx.send(round_start(3)).unwrap();
+
+        assert_eq!(tx.lagged().await, 2);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), tx.lagged())
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn sends_fail_once_the_receiver_is_gone() {
+        let (tx, rx) = channel(4);
+        assert_eq!(tx.send(round_start(1)), Ok(()));
+        drop(rx);
+
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(round_start(2)), Err(Closed));
+        assert_eq!(Outbox::detached().send(round_start(1)), Err(Closed));
+    }
+
+    fn error() -> ServerMessage {
+        ServerMessage::Error {
+            code: ErrorCode::RateLimited,
+            message: "Too many requests".to_string(),
+            request_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn flooding_errors_at_a_client_that_is_not_reading_stays_bounded() {
+        let (tx, mut rx) = channel(4);
+        for _ in 0..1000 {
+            tx.send(error()).unwrap();
+        }
+
+        assert_eq!(tx.lagged().await, 996);
+        assert_eq!(drain(&mut rx, 4).await, vec![error(); 4]);
+    }
+
+    #[tokio::test]
+    async fn client_that_is_not_reading_is_cut_off_past_the_backlog_limit() {
+        let (tx, mut rx) = channel(4);
+        let mut accepted = 0;
+        while tx.send(round_result()).is_ok() {
+            accepted += 1;
+            assert!(accepted <= 4 * BACKLOG_FACTOR, "Backlog grew without limit");
+        }
+
+        assert_eq!(accepted, 4 * BACKLOG_FACTOR);
+        assert!(tx.is_closed());
+        assert_eq!(rx.recv().await, None);
+    }
+}
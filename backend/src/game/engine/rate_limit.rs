@@ -0,0 +1,429 @@
+use crate::game::core::messages::ClientMessage;
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Per-IP entries idle for longer than this are pruned
+const IP_ENTRY_TTL: Duration = Duration::from_secs(600);
+/// Pruning only kicks in once the per-IP map grows past this size
+const IP_PRUNE_THRESHOLD: usize = 1024;
+
+/// Token bucket parameters: burst size and steady refill rate
+#[derive(Debug, Clone, Copy)]
+pub struct BucketConfig {
+    pub capacity: f64,
+    pub refill_per_sec: f64,
+}
+
+impl BucketConfig {
+    pub const fn new(capacity: f64, refill_per_sec: f64) -> Self {
+        Self {
+            capacity,
+            refill_per_sec,
+        }
+    }
+}
+
+/// Limits applied to every WebSocket connection
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Answer submissions per connection
+    pub answers: BucketConfig,
+    /// Create/join attempts per connection
+    pub joins: BucketConfig,
+    /// All other messages per connection
+    pub messages: BucketConfig,
+    /// Answer submissions shared by all connections from one IP
+    pub ip_answers: BucketConfig,
+    /// Create/join attempts shared by all connections from one IP
+    pub ip_joins: BucketConfig,
+    /// Cooldown after the first rejection, doubled for each further one
+    pub cooldown_base: Duration,
+    /// Upper bound for the escalating cooldown
+    pub cooldown_max: Duration,
+    /// Disconnect after this many unparseable or wrong-endpoint messages
+    pub max_invalid_messages: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            answers: BucketConfig::new(10.0, 5.0),
+            joins: BucketConfig::new(3.0, 0.2),
+            messages: BucketConfig::new(10.0, 5.0),
+            ip_answers: BucketConfig::new(30.0, 15.0),
+            ip_joins: BucketConfig::new(10.0, 5.0 / 60.0),
+            cooldown_base: Duration::from_secs(1),
+            cooldown_max: Duration::from_secs(30),
+            max_invalid_messages: 5,
+        }
+    }
+}
+
+/// Which budget a client message is charged against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Budget {
+    Answer,
+    Join,
+    Other,
+}
+
+impl Budget {
+    pub fn for_message(msg: &ClientMessage) -> Self {
+        match msg {
+            ClientMessage::Answer { .. } => Budget::Answer,
+            ClientMessage::Join { .. }
+            | ClientMessage::CreateGame { .. }
+            | ClientMessage::JoinGame { .. }
+            | ClientMessage::StartDaily { .. }
+            | ClientMessage::CreateChallenge { .. }
+            | ClientMessage::AcceptChallenge { .. }
+            | ClientMessage::Resume { .. } => Budget::Join,
+            ClientMessage::Hello { .. }
+            | ClientMessage::Skip
+            | ClientMessage::RequestRematch
+            | ClientMessage::Sync
+            | ClientMessage::Pause
+            | ClientMessage::Unpause
+            | ClientMessage::OfferDraw
+            | ClientMessage::AcceptDraw => Budget::Other,
+        }
+    }
+
+    /// Label for metrics
+    pub fn name(self) -> &'static str {
+        match self {
+            Budget::Answer => "answer",
+            Budget::Join => "join",
+            Budget::Other => "other",
+        }
+    }
+}
+
+/// Classic token bucket, refilled lazily on each take
+pub struct TokenBucket {
+    config: BucketConfig,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(config: BucketConfig, now: Instant) -> Self {
+        Self {
+            config,
+            tokens: config.capacity,
+            last_refill: now,
+        }
+    }
+
+    /// Take one token. Returns false if the bucket is empty.
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.config.refill_per_sec).min(self.config.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Give back a token taken for something that was refused after all
+    pub fn refund(&mut self) {
+        self.tokens = (self.tokens + 1.0).min(self.config.capacity);
+    }
+}
+
+struct IpBuckets {
+    answers: TokenBucket,
+    joins: TokenBucket,
+    last_seen: Instant,
+}
+
+/// Shared limiter state. Holds the config and the per-IP budgets,
+/// and hands out a [`ConnectionLimiter`] for each new connection.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    ips: DashMap<IpAddr, IpBuckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            ips: DashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Create the limiter for a single connection from the given address
+    pub fn connection(self: &Arc<Self>, ip: Option<IpAddr>) -> ConnectionLimiter {
+        self.prune_idle(Instant::now());
+        ConnectionLimiter::new(self.clone(), ip, Instant::now())
+    }
+
+    fn try_take_ip(&self, ip: IpAddr, budget: Budget, now: Instant) -> bool {
+        let mut entry = self.ips.entry(ip).or_insert_with(|| IpBuckets {
+            answers: TokenBucket::new(self.config.ip_answers, now),
+            joins: TokenBucket::new(self.config.ip_joins, now),
+            last_seen: now,
+        });
+        entry.last_seen = now;
+
+        match budget {
+            Budget::Answer => entry.answers.try_take(now),
+            Budget::Join => entry.joins.try_take(now),
+            Budget::Other => true,
+        }
+    }
+
+    fn prune_idle(&self, now: Instant) {
+        if self.ips.len() > IP_PRUNE_THRESHOLD {
+            self.ips
+                .retain(|_, entry| now.saturating_duration_since(entry.last_seen) < IP_ENTRY_TTL);
+        }
+    }
+}
+
+/// Outcome of checking a message against the limiter
+#[derive(Debug, PartialEq)]
+pub enum RateLimitVerdict {
+    Allowed,
+    /// Rejected; the connection is cooling down for the given duration
+    Limited {
+        retry_after: Duration,
+    },
+}
+
+/// Rate limiting state for a single connection
+pub struct ConnectionLimiter {
+    shared: Arc<RateLimiter>,
+    ip: Option<IpAddr>,
+    answers: TokenBucket,
+    joins: TokenBucket,
+    messages: TokenBucket,
+    strikes: u32,
+    cooldown_until: Option<Instant>,
+    last_violation: Option<Instant>,
+}
+
+impl ConnectionLimiter {
+    fn new(shared: Arc<RateLimiter>, ip: Option<IpAddr>, now: Instant) -> Self {
+        let config = shared.config();
+        Self {
+            answers: TokenBucket::new(config.answers, now),
+            joins: TokenBucket::new(config.joins, now),
+            messages: TokenBucket::new(config.messages, now),
+            shared,
+            ip,
+            strikes: 0,
+            cooldown_until: None,
+            last_violation: None,
+        }
+    }
+
+    pub fn max_invalid_messages(&self) -> u32 {
+        self.shared.config.max_invalid_messages
+    }
+
+    /// Charge a message against its budget
+    pub fn check(&mut self, budget: Budget, now: Instant) -> RateLimitVerdict {
+        if let Some(until) = self.cooldown_until {
+            if now < until {
+                return RateLimitVerdict::Limited {
+                    retry_after: until - now,
+                };
+            }
+            self.cooldown_until = None;
+        }
+
+        // Forgive old violations once the client has behaved for a full max cooldown
+        if let Some(last) = self.last_violation
+            && now.saturating_duration_since(last) >= self.shared.config.cooldown_max
+        {
+            self.strikes = 0;
+            self.last_violation = None;
+        }
+
+        let bucket = match budget {
+            Budget::Answer => &mut self.answers,
+            Budget::Join => &mut self.joins,
+            Budget::Other => &mut self.messages,
+        };
+
+        let allowed = if !bucket.try_take(now) {
+            false
+        } else if self
+            .ip
+            .is_some_and(|ip| !self.shared.try_take_ip(ip, budget, now))
+        {
+            // Refused by the address's budget, so the message doesn't use
+            // up the connection's own
+            bucket.refund();
+            false
+        } else {
+            true
+        };
+
+        if allowed {
+            return RateLimitVerdict::Allowed;
+        }
+
+        let retry_after = self.escalate(now);
+        RateLimitVerdict::Limited { retry_after }
+    }
+
+    fn escalate(&mut self, now: Instant) -> Duration {
+        let config = &self.shared.config;
+        let factor = 2u32.saturating_pow(self.strikes);
+        let cooldown = config
+            .cooldown_base
+            .saturating_mul(factor)
+            .min(config.cooldown_max);
+
+        self.strikes = self.strikes.saturating_add(1);
+        self.cooldown_until = Some(now + cooldown);
+        self.last_violation = Some(now);
+        cooldown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn limiter(config: RateLimitConfig) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(config))
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(BucketConfig::new(2.0, 1.0), start);
+
+        assert!(bucket.try_take(start));
+        assert!(bucket.try_take(start));
+        assert!(!bucket.try_take(start));
+
+        assert!(bucket.try_take(start + Duration::from_secs(1)));
+        assert!(!bucket.try_take(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn budgets_are_independent() {
+        let config = RateLimitConfig {
+            answers: BucketConfig::new(1.0, 0.0),
+            ..RateLimitConfig::default()
+        };
+        let now = Instant::now();
+        let mut conn = limiter(config).connection(None);
+
+        assert_eq!(conn.check(Budget::Answer, now), RateLimitVerdict::Allowed);
+        assert!(matches!(
+            conn.check(Budget::Answer, now),
+            RateLimitVerdict::Limited { .. }
+        ));
+    }
+
+    #[test]
+    fn cooldown_escalates_and_caps() {
+        let config = RateLimitConfig {
+            joins: BucketConfig::new(0.0, 0.0),
+            cooldown_base: Duration::from_secs(1),
+            cooldown_max: Duration::from_secs(3),
+            ..RateLimitConfig::default()
+        };
+        let mut conn = limiter(config).connection(None);
+        let mut now = Instant::now();
+
+        let mut cooldowns = Vec::new();
+        for _ in 0..3 {
+            let RateLimitVerdict::Limited { retry_after } = conn.check(Budget::Join, now) else {
+                panic!("Expected Limited");
+            };
+            cooldowns.push(retry_after);
+            now += retry_after;
+        }
+
+        assert_eq!(
+            cooldowns,
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_everything_during_cooldown() {
+        let config = RateLimitConfig {
+            joins: BucketConfig::new(0.0, 0.0),
+            ..RateLimitConfig::default()
+        };
+        let now = Instant::now();
+        let mut conn = limiter(config).connection(None);
+
+        assert!(matches!(
+            conn.check(Budget::Join, now),
+            RateLimitVerdict::Limited { .. }
+        ));
+        assert!(matches!(
+            conn.check(Budget::Other, now),
+            RateLimitVerdict::Limited { .. }
+        ));
+    }
+
+    #[test]
+    fn ip_budget_is_shared_across_connections() {
+        let config = RateLimitConfig {
+            ip_joins: BucketConfig::new(1.0, 0.0),
+            ..RateLimitConfig::default()
+        };
+        let shared = limiter(config);
+        let ip = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let now = Instant::now();
+
+        let mut first = shared.connection(ip);
+        let mut second = shared.connection(ip);
+
+        assert_eq!(first.check(Budget::Join, now), RateLimitVerdict::Allowed);
+        assert!(matches!(
+            second.check(Budget::Join, now),
+            RateLimitVerdict::Limited { .. }
+        ));
+    }
+
+    #[test]
+    fn ip_refusal_does_not_use_up_the_connection_budget() {
+        let config = RateLimitConfig {
+            joins: BucketConfig::new(2.0, 0.0),
+            ip_joins: BucketConfig::new(1.0, 1.0),
+            cooldown_base: Duration::from_secs(1),
+            ..RateLimitConfig::default()
+        };
+        let mut conn = limiter(config).connection(Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        let now = Instant::now();
+
+        assert_eq!(conn.check(Budget::Join, now), RateLimitVerdict::Allowed);
+        assert_eq!(
+            conn.check(Budget::Join, now),
+            RateLimitVerdict::Limited {
+                retry_after: Duration::from_secs(1)
+            }
+        );
+        // The address has refilled, and the connection still has its second join
+        let later = now + Duration::from_secs(1);
+        assert_eq!(conn.check(Budget::Join, later), RateLimitVerdict::Allowed);
+    }
+}
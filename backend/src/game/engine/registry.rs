@@ -1,321 +1,497 @@
 use super::active_game::{
-    continue_or_end_game, spawn_round_timeout, ActiveGame, AnswerResult, DEFAULT_ROUND_TIMEOUT,
+    ActiveGame, GameCommand, GameHandle, GameReport, GameServices, MatchRules,
+};
+use super::forfeits::Forfeits;
+use super::metrics::Metrics;
+use super::outbox::Outbox;
+use crate::game::core::messages::{ErrorCode, ServerMessage};
+use crate::game::core::session::GameSession;
+use crate::game::core::{
+    GameLogRepository, GameProgress, GameSnapshot, MatchRepository, WordRepository,
 };
-use crate::game::core::messages::ServerMessage;
-use crate::game::core::WordRepository;
 use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::broadcast;
-use tracing::{debug, info};
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+
+/// A running game as seen by operators
+#[derive(Debug, Clone, Serialize)]
+pub struct GameStatus {
+    pub game_id: String,
+    pub mode: &'static str,
+    pub player1: String,
+    pub player2: String,
+    pub player1_score: u32,
+    pub player2_score: u32,
+    /// Round being played, None between matches
+    pub round: Option<u32>,
+    /// False once the match is over (waiting for a rematch) or while
+    /// waiting for restored players to come back
+    pub running: bool,
+    /// Last heartbeat round trip per player, None until their first pong
+    pub player1_latency_ms: Option<u32>,
+    pub player2_latency_ms: Option<u32>,
+}
+
+/// Which game and player a resume token belongs to
+#[derive(Debug, Clone)]
+struct ResumeTarget {
+    game_id: String,
+    player_id: String,
+}
 
-/// Info returned when a player is removed from a game due to disconnect
-pub struct DisconnectInfo {
-    pub game: ActiveGame,
-    pub opponent_id: String,
+pub fn new_resume_token() -> String {
+    uuid::Uuid::new_v4().to_string()
 }
 
 /// Shared game state management used by both ephemeral and matchmaking modes.
-/// Handles active games, player-to-game mapping, answer submission, and cleanup.
+/// Maps players to their game's task and forwards their actions to it;
+/// the game logic itself runs in [`ActiveGame`].
 pub struct GameRegistry {
-    pub words: WordRepository,
-    pub games: Arc<DashMap<String, ActiveGame>>,
-    pub player_games: Arc<DashMap<String, String>>, // player_id -> game_id
-    pub round_timeout: Duration,
+    pub services: GameServices,
+    pub games: DashMap<String, GameHandle>,
+    pub player_games: DashMap<String, String>, // player_id -> game_id
+    resume_tokens: DashMap<String, ResumeTarget>,
 }
 
 impl GameRegistry {
-    pub fn new(words: WordRepository, round_timeout: Option<Duration>) -> Self {
+    pub fn new(
+        words: WordRepository,
+        matches: MatchRepository,
+        logs: GameLogRepository,
+        mode: &'static str,
+        rules: MatchRules,
+        metrics: Arc<Metrics>,
+        forfeits: Option<Arc<Forfeits>>,
+    ) -> Self {
         Self {
-            words,
-            games: Arc::new(DashMap::new()),
-            player_games: Arc::new(DashMap::new()),
-            round_timeout: round_timeout.unwrap_or(DEFAULT_ROUND_TIMEOUT),
+            services: GameServices {
+                words,
+                matches,
+                logs,
+                rules,
+                mode,
+                metrics,
+                forfeits,
+            },
+            games: DashMap::new(),
+            player_games: DashMap::new(),
+            resume_tokens: DashMap::new(),
         }
     }
 
+    /// Register a new game and start it on its own task, drawing words from `seed`.
+    /// The game task sends `GameStart` to both players, then round 1.
+    pub fn start_game(
+        &self,
+        game_id: &str,
+        player1: String,
+        player2: String,
+        player1_tx: Outbox,
+        player2_tx: Outbox,
+        seed: u64,
+    ) {
+        info!(game_id, player1, player2, seed, "Starting game");
+        self.remove_finished_games();
+
+        self.player_games
+            .insert(player1.clone(), game_id.to_string());
+        self.player_games
+            .insert(player2.clone(), game_id.to_string());
+
+        // Sent before the game task starts, so the token precedes GameStart
+        for (player, tx) in [(&player1, &player1_tx), (&player2, &player2_tx)] {
+            let token = new_resume_token();
+            self.resume_tokens.insert(
+                token.clone(),
+                ResumeTarget {
+                    game_id: game_id.to_string(),
+                    player_id: player.clone(),
+                },
+            );
+            let _ = tx.send(ServerMessage::ResumeToken { token });
+        }
 
-    /// Broadcast a message to both players in a game
-    pub fn broadcast_to_game(&self, user_id: &str, msg: ServerMessage) {
-        let Some(game_id) = self.player_games.get(user_id) else {
-            return;
-        };
-        let Some(game) = self.games.get(&*game_id) else {
-            return;
-        };
-        debug!(?game_id, "Broadcasting to game");
-        game.broadcast(msg);
+        let session = GameSession::new(player1, player2);
+        let game = ActiveGame::new(
+            session,
+            player1_tx,
+            player2_tx,
+            self.services.clone(),
+            seed,
+        );
+        self.games
+            .insert(game_id.to_string(), game.spawn(game_id.to_string()));
     }
 
-    /// Clean up game state after game ends
-    pub fn cleanup_game(&self, game_id: &str) {
-        if let Some((_, game)) = self.games.remove(game_id) {
-            self.player_games.remove(&game.session.player1);
-            self.player_games.remove(&game.session.player2);
+    /// Restart a game saved before a restart; it waits for its players to `resume`
+    pub fn restore_game(&self, snapshot: GameSnapshot) {
+        let game_id = snapshot.game_id.clone();
+        info!(game_id, "Restoring game");
+        for (player, token) in [
+            (&snapshot.player1, &snapshot.player1_token),
+            (&snapshot.player2, &snapshot.player2_token),
+        ] {
+            self.player_games.insert(player.clone(), game_id.clone());
+            self.resume_tokens.insert(
+                token.clone(),
+                ResumeTarget {
+                    game_id: game_id.clone(),
+                    player_id: player.clone(),
+                },
+            );
         }
+        let game = ActiveGame::restore(snapshot, self.services.clone());
+        self.games.insert(game_id.clone(), game.spawn(game_id));
     }
 
-    /// Remove a player from their game due to disconnect.
-    /// Returns the game and opponent info so the caller can send notifications.
-    pub fn remove_player_from_game(&self, user_id: &str) -> Option<DisconnectInfo> {
-        let (_, game_id) = self.player_games.remove(user_id)?;
-        let (_, game) = self.games.remove(&game_id)?;
-        let opponent_id = game.session.opponent_of(user_id)?.to_string();
-        self.player_games.remove(&opponent_id);
-        Some(DisconnectInfo { game, opponent_id })
+    /// Reattach a player to their game with a resume token.
+    /// Returns the player's id for the connection.
+    pub async fn resume(
+        &self,
+        token: &str,
+        tx: Outbox,
+    ) -> Result<String, ErrorCode> {
+        let target = self
+            .resume_tokens
+            .get(token)
+            .map(|target| target.clone())
+            .ok_or(ErrorCode::InvalidResumeToken)?;
+        let handle = self
+            .games
+            .get(&target.game_id)
+            .map(|handle| handle.clone())
+            .ok_or(ErrorCode::InvalidResumeToken)?;
+
+        let (reply, resumed) = oneshot::channel();
+        handle.send(GameCommand::Reconnect {
+            player_id: target.player_id.clone(),
+            tx,
+            reply,
+        });
+        match resumed.await {
+            Ok(true) => Ok(target.player_id),
+            _ => Err(ErrorCode::InvalidResumeToken),
+        }
     }
 
-    /// Submit an answer and return the result if correct.
-    /// Validates the answer against the database to support multiple readings per kanji.
-    pub async fn submit_answer(&self, user_id: &str, answer: &str) -> Option<AnswerResult> {
-        let game_id = self.player_games.get(user_id)?;
+    /// Send a server notice to the players of every game
+    pub fn announce(&self, msg: &ServerMessage) {
+        for handle in self.games.iter() {
+            handle.send(GameCommand::Announce(msg.clone()));
+        }
+    }
 
-        // Get the current kanji from the game (need to release lock before async call)
-        let kanji = {
-            let game = self.games.get(&*game_id)?;
-            game.session.current_kanji()?.to_string()
+    /// Scores and current round of every game, asked of each game task
+    pub async fn statuses(&self) -> Vec<GameStatus> {
+        let games: Vec<(String, GameHandle)> = self
+            .games
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        let mut statuses = Vec::new();
+        for (game_id, handle) in games {
+            let (reply, report) = oneshot::channel();
+            if !handle.send(GameCommand::Status { reply }) {
+                continue;
+            }
+            let Ok(GameReport {
+                progress,
+                latencies,
+            }) = report.await
+            else {
+                continue;
+            };
+            let latency_ms = |rtt: Option<Duration>| {
+                rtt.map(|rtt| u32::try_from(rtt.as_millis()).unwrap_or(u32::MAX))
+            };
+            statuses.push(GameStatus {
+                game_id,
+                mode: self.services.mode,
+                running: handle.is_match_running(),
+                player1: handle.player1,
+                player2: handle.player2,
+                player1_score: progress.scores.0,
+                player2_score: progress.scores.1,
+                round: progress.round.map(|round| round.number),
+                player1_latency_ms: latency_ms(latencies.0),
+                player2_latency_ms: latency_ms(latencies.1),
+            });
+        }
+        statuses
+    }
+
+    /// Stop a game without a winner. Its players stay connected but are no
+    /// longer in a game. Returns false if there is no such game.
+    pub fn end_game(&self, game_id: &str) -> bool {
+        let Some((_, handle)) = self.games.remove(game_id) else {
+            return false;
         };
+        info!(game_id, "Ending game");
+        self.forget_players(game_id, &handle);
+        handle.send(GameCommand::End);
+        true
+    }
 
-        debug!(user_id, answer, kanji, "Player submitting answer");
+    /// Matches still being played
+    pub fn running_matches(&self) -> usize {
+        self.games
+            .iter()
+            .filter(|handle| handle.is_match_running())
+            .count()
+    }
+
+    /// Progress of every running game, for saving
+    pub async fn snapshot(&self) -> Vec<GameSnapshot> {
+        self.collect_snapshots(|reply| GameCommand::Snapshot { reply })
+            .await
+    }
 
-        // Validate answer against database (supports multiple readings)
-        if !self.words.is_valid_reading(&kanji, answer).await {
-            return None;
+    /// Save every game and stop it, telling its players the server is going down
+    pub async fn shutdown(&self) -> Vec<GameSnapshot> {
+        self.collect_snapshots(|reply| GameCommand::Shutdown { reply })
+            .await
+    }
+
+    async fn collect_snapshots(
+        &self,
+        command: impl Fn(oneshot::Sender<GameProgress>) -> GameCommand,
+    ) -> Vec<GameSnapshot> {
+        self.remove_finished_games();
+
+        let mut tokens = HashMap::new();
+        for entry in self.resume_tokens.iter() {
+            tokens.insert(
+                (entry.game_id.clone(), entry.player_id.clone()),
+                entry.key().clone(),
+            );
+        }
+        let games: Vec<(String, GameHandle)> = self
+            .games
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        let mut snapshots = Vec::new();
+        for (game_id, handle) in games {
+            let (reply, progress) = oneshot::channel();
+            if !handle.send(command(reply)) {
+                continue;
+            }
+            let Ok(progress) = progress.await else {
+                continue;
+            };
+            let token = |player: &String| tokens.get(&(game_id.clone(), player.clone())).cloned();
+            let (Some(player1_token), Some(player2_token)) =
+                (token(&handle.player1), token(&handle.player2))
+            else {
+                warn!(game_id, "Game has no resume tokens, not saving it");
+                continue;
+            };
+            snapshots.push(GameSnapshot {
+                game_id,
+                player1: handle.player1,
+                player2: handle.player2,
+                player1_token,
+                player2_token,
+                progress,
+            });
         }
+        snapshots
+    }
 
-        // Answer is valid - accept it
-        let mut game = self.games.get_mut(&*game_id)?;
-        let outcome = game.session.accept_correct_answer(user_id)?;
+    /// Forget games whose task has stopped on its own (nobody came back to a
+    /// restored game, or a player who dropped out didn't return)
+    fn remove_finished_games(&self) {
+        let finished: Vec<String> = self
+            .games
+            .iter()
+            .filter(|entry| entry.is_finished())
+            .map(|entry| entry.key().clone())
+            .collect();
+        for game_id in finished {
+            if let Some((_, handle)) = self.games.remove(&game_id) {
+                self.forget_players(&game_id, &handle);
+            }
+        }
+    }
 
-        // Record the win
-        if let Some(winner) = &outcome.winner {
-            game.session.record_win(winner);
+    fn forget_players(&self, game_id: &str, handle: &GameHandle) {
+        for player in [&handle.player1, &handle.player2] {
+            self.player_games.remove_if(player, |_, id| id == game_id);
         }
+        self.resume_tokens.retain(|_, target| target.game_id != game_id);
+    }
 
-        let scores = game.session.scores();
-        let game_winner = game.session.game_winner().map(|s| s.to_string());
+    /// Forward a command to the game the player is in
+    fn send(&self, user_id: &str, command: GameCommand) -> Result<(), ErrorCode> {
+        let game_id = self
+            .player_games
+            .get(user_id)
+            .ok_or(ErrorCode::NotInGame)?;
+        let handle = self.games.get(&*game_id).ok_or(ErrorCode::NotInGame)?;
+        debug!(game_id = ?*game_id, ?command, "Forwarding command to game");
+        if handle.send(command) {
+            Ok(())
+        } else {
+            Err(ErrorCode::NotInGame)
+        }
+    }
 
-        info!(
+    /// Store a player's heartbeat latency and share it with both players
+    pub fn record_latency(&self, user_id: &str, rtt: Duration) {
+        let _ = self.send(
             user_id,
-            round_winner = ?outcome.winner,
-            scores = ?scores,
-            game_winner = ?game_winner,
-            "Round ended"
+            GameCommand::Latency {
+                player_id: user_id.to_string(),
+                rtt,
+            },
         );
+    }
 
-        let round_number = scores.0 + scores.1;
+    /// The player's game as they see it, answering `sync`
+    pub async fn sync(&self, user_id: &str) -> Result<ServerMessage, ErrorCode> {
+        let (reply, state) = oneshot::channel();
+        self.send(
+            user_id,
+            GameCommand::Sync {
+                player_id: user_id.to_string(),
+                reply,
+            },
+        )?;
+        state.await.map_err(|_| ErrorCode::NotInGame)
+    }
 
-        Some(AnswerResult {
-            round_result: ServerMessage::RoundResult {
-                winner: outcome.winner,
-                correct_reading: outcome.correct_reading,
+    /// Send a player who fell behind the state of their game
+    pub fn resync(&self, user_id: &str) {
+        let _ = self.send(
+            user_id,
+            GameCommand::Resync {
+                player_id: user_id.to_string(),
             },
-            game_winner,
-            round_number,
-        })
+        );
     }
 
-    /// Handle answer submission: check answer, broadcast result, continue or end game
-    pub async fn handle_answer(
-        self: &Arc<Self>,
-        user_id: &str,
-        answer: &str,
-        tx: &broadcast::Sender<ServerMessage>,
-    ) {
-        let Some(result) = self.submit_answer(user_id, answer).await else {
-            debug!(user_id, answer, "Wrong answer");
-            let _ = tx.send(ServerMessage::WrongAnswer);
-            return;
+    /// A player's connection closed. If they can `resume` and a match is
+    /// running, the game waits for them with the timer stopped (see
+    /// [`MatchRules::reconnect_grace`]); otherwise the game task notifies the
+    /// opponent and stops. Returns whether the player left a running match.
+    pub fn remove_player_from_game(&self, user_id: &str, can_resume: bool) -> bool {
+        self.remove_finished_games();
+        let Some(game_id) = self.player_games.get(user_id).map(|id| id.clone()) else {
+            return false;
+        };
+        let Some(handle) = self.games.get(&game_id).map(|handle| handle.clone()) else {
+            return false;
         };
 
-        // Broadcast round result to both players
-        self.broadcast_to_game(user_id, result.round_result);
+        let running = handle.is_match_running();
+        let can_return = can_resume
+            && running
+            && !self.services.rules.reconnect_grace.is_zero();
+        if !can_return {
+            self.games.remove(&game_id);
+            self.forget_players(&game_id, &handle);
+        }
+        handle.send(GameCommand::Disconnect {
+            player_id: user_id.to_string(),
+            can_return,
+        });
+        running
+    }
 
-        // Get game_id for continue_or_end_game
-        let Some(game_id) = self.player_games.get(user_id).map(|r| r.clone()) else {
-            return;
-        };
+    /// Whether the player is in a game that hasn't finished, possibly one
+    /// waiting for them to `resume`
+    pub fn has_player(&self, user_id: &str) -> bool {
+        self.remove_finished_games();
+        self.player_games.contains_key(user_id)
+    }
 
-        let registry = self.clone();
-        continue_or_end_game(
-            &self.games,
-            &self.words,
-            self.round_timeout,
-            &game_id,
-            result.game_winner,
-            result.round_number,
-            &self.player_games,
-            Arc::new(move |id: &str| registry.cleanup_game(id)),
+    /// Queue an answer for the given round (or whichever is current);
+    /// the game task checks it and replies, echoing `request_id` on an error
+    pub fn handle_answer(
+        &self,
+        user_id: &str,
+        answer: &str,
+        round: Option<u32>,
+        request_id: Option<String>,
+    ) -> Result<(), ErrorCode> {
+        self.send(
+            user_id,
+            GameCommand::Answer {
+                player_id: user_id.to_string(),
+                answer: answer.to_string(),
+                round,
+                request_id,
+            },
         )
-        .await;
     }
 
     /// Handle a player skipping the current round (they don't know the answer).
     /// Both players must skip for the round to end.
-    pub async fn handle_skip(
-        self: &Arc<Self>,
-        user_id: &str,
-        tx: &broadcast::Sender<ServerMessage>,
-    ) {
-        use crate::game::core::session::SkipResult;
-
-        let game_id = match self.player_games.get(user_id) {
-            Some(id) => id.clone(),
-            None => return,
-        };
-
-        let skip_result = {
-            let Some(mut game) = self.games.get_mut(&game_id) else {
-                return;
-            };
-
-            // Capture round number before record_skip potentially ends the round
-            let current_round_number = game.session.current_round_number();
-
-            let Some(result) = game.session.record_skip(user_id) else {
-                return;
-            };
-
-            match result {
-                SkipResult::AlreadySkipped => {
-                    debug!(user_id, "Player already skipped");
-                    return;
-                }
-                SkipResult::WaitingForOpponent => {
-                    info!(user_id, "Player skipped, waiting for opponent");
-                    let _ = tx.send(ServerMessage::SkipWaiting);
-                    return;
-                }
-                SkipResult::BothSkipped(outcome) => {
-                    info!(user_id, "Both players skipped, ending round");
-                    let game_winner = game.session.game_winner().map(|s| s.to_string());
-                    let round_number = current_round_number.unwrap_or(0);
-
-                    game.broadcast(ServerMessage::RoundResult {
-                        winner: outcome.winner,
-                        correct_reading: outcome.correct_reading,
-                    });
-
-                    (game_winner, round_number)
-                }
-            }
-        };
-
-        let registry = self.clone();
-        continue_or_end_game(
-            &self.games,
-            &self.words,
-            self.round_timeout,
-            &game_id,
-            skip_result.0,
-            skip_result.1,
-            &self.player_games,
-            Arc::new(move |id: &str| registry.cleanup_game(id)),
+    pub fn handle_skip(&self, user_id: &str, request_id: Option<String>) -> Result<(), ErrorCode> {
+        self.send(
+            user_id,
+            GameCommand::Skip {
+                player_id: user_id.to_string(),
+                request_id,
+            },
         )
-        .await;
     }
 
-    /// Start round 1 for a newly created game
-    pub async fn start_first_round(
-        self: &Arc<Self>,
-        game_id: &str,
-        player1_tx: &broadcast::Sender<ServerMessage>,
-        player2_tx: &broadcast::Sender<ServerMessage>,
-    ) {
-        let Some(word) = self.words.get_random().await else {
-            return;
-        };
-
-        let readings = self.words.get_readings_for_kanji(&word.kanji).await;
-
-        info!(
-            game_id,
-            kanji = word.kanji,
-            reading = word.reading,
-            "Round 1 starting"
-        );
+    /// Stop the round timer of the player's game until both players unpause
+    pub fn handle_pause(&self, user_id: &str) -> Result<(), ErrorCode> {
+        self.send(
+            user_id,
+            GameCommand::Pause {
+                player_id: user_id.to_string(),
+            },
+        )
+    }
 
-        let round_msg = ServerMessage::RoundStart {
-            kanji: word.kanji.clone(),
-            round: 1,
-            readings,
-        };
-        let _ = player1_tx.send(round_msg.clone());
-        let _ = player2_tx.send(round_msg);
+    /// Handle a player agreeing to carry on after a pause
+    pub fn handle_unpause(&self, user_id: &str) -> Result<(), ErrorCode> {
+        self.send(
+            user_id,
+            GameCommand::Unpause {
+                player_id: user_id.to_string(),
+            },
+        )
+    }
 
-        if let Some(mut game) = self.games.get_mut(game_id) {
-            game.session.start_round(1, word);
-        }
+    /// Offer a draw in the player's match, or agree to the opponent's offer
+    pub fn handle_offer_draw(&self, user_id: &str, request_id: Option<String>) -> Result<(), ErrorCode> {
+        self.send(
+            user_id,
+            GameCommand::OfferDraw {
+                player_id: user_id.to_string(),
+                request_id,
+            },
+        )
+    }
 
-        let registry = self.clone();
-        spawn_round_timeout(
-            self.round_timeout,
-            self.games.clone(),
-            self.words.clone(),
-            game_id.to_string(),
-            1,
-            self.player_games.clone(),
-            Arc::new(move |id: &str| registry.cleanup_game(id)),
-        );
+    /// Take the opponent's draw offer
+    pub fn handle_accept_draw(&self, user_id: &str, request_id: Option<String>) -> Result<(), ErrorCode> {
+        self.send(
+            user_id,
+            GameCommand::AcceptDraw {
+                player_id: user_id.to_string(),
+                request_id,
+            },
+        )
     }
 
     /// Handle a player requesting a rematch
-    pub async fn handle_rematch(
-        self: &Arc<Self>,
-        user_id: &str,
-        tx: &broadcast::Sender<ServerMessage>,
-    ) {
-        let game_id = match self.player_games.get(user_id) {
-            Some(id) => id.clone(),
-            None => return,
-        };
-
-        let both_want_rematch = {
-            let Some(mut game) = self.games.get_mut(&game_id) else {
-                return;
-            };
-
-            match game.session.request_rematch(user_id) {
-                Some(true) => {
-                    // Both players want rematch - reset and start new game
-                    info!(user_id, "Both players want rematch, starting new game");
-                    game.session.reset_for_rematch();
-                    true
-                }
-                Some(false) => {
-                    // Waiting for opponent
-                    info!(user_id, "Player wants rematch, waiting for opponent");
-                    let _ = tx.send(ServerMessage::RematchWaiting);
-                    false
-                }
-                None => false,
-            }
-        };
-
-        if both_want_rematch {
-            // Start the new game - send GameStart to reset frontend state
-            let (player1, player2, player1_tx, player2_tx) = {
-                let Some(game) = self.games.get(&game_id) else {
-                    return;
-                };
-                (
-                    game.session.player1.clone(),
-                    game.session.player2.clone(),
-                    game.player1_tx.clone(),
-                    game.player2_tx.clone(),
-                )
-            };
-
-            // Send GameStart to both players (with each other as opponent)
-            let _ = player1_tx.send(ServerMessage::GameStart {
-                opponent: player2.clone(),
-            });
-            let _ = player2_tx.send(ServerMessage::GameStart {
-                opponent: player1.clone(),
-            });
-
-            self.start_first_round(&game_id, &player1_tx, &player2_tx)
-                .await;
-        }
+    pub fn handle_rematch(&self, user_id: &str) -> Result<(), ErrorCode> {
+        self.send(
+            user_id,
+            GameCommand::Rematch {
+                player_id: user_id.to_string(),
+            },
+        )
     }
 }
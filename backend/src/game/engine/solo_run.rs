@@ -0,0 +1,272 @@
+use super::outbox::Outbox;
+use crate::game::core::messages::ServerMessage;
+use crate::game::core::solo::{RoundRecord, RunRecord};
+use crate::game::core::word_sequence::WordSequence;
+use crate::game::core::{Word, WordRepository};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, sleep_until};
+use tracing::{debug, info, warn};
+
+/// Everything a player can do during a solo run
+#[derive(Debug)]
+pub enum RunCommand {
+    Answer {
+        answer: String,
+        /// Round the client meant to answer, if it said
+        round: Option<u32>,
+    },
+    Skip,
+    /// The player's connection dropped messages; send the current round again
+    Resync,
+    /// The player left; the run finishes with the rounds played so far
+    Abandon,
+    /// Pass a server notice on to the player
+    Announce(ServerMessage),
+}
+
+/// Owner's view of a running solo run
+pub struct RunHandle {
+    commands: mpsc::UnboundedSender<RunCommand>,
+}
+
+impl RunHandle {
+    /// Queue a command. Returns false if the run has already finished.
+    pub fn send(&self, command: RunCommand) -> bool {
+        self.commands.send(command).is_ok()
+    }
+}
+
+/// A recorded run played back as an opponent: it answers correctly at the
+/// recorded times, ending the round if the player hasn't answered yet
+pub struct Ghost {
+    pub name: String,
+    pub record: RunRecord,
+}
+
+/// How a round ended
+enum RoundEnd {
+    Correct,
+    GhostAnswered,
+    Missed,
+}
+
+struct CurrentRound {
+    number: u32,
+    word: Word,
+    started: Instant,
+    deadline: Instant,
+    /// When the ghost answers correctly, if it does this round
+    ghost_answers_at: Option<Instant>,
+}
+
+/// One player working through a seeded word set alone (or against a ghost),
+/// owned by its own task like [`super::active_game::ActiveGame`].
+/// Every round's time is recorded, so skipping is cheaper than waiting out the timer.
+pub struct SoloRun {
+    player: String,
+    tx: Outbox,
+    words: WordRepository,
+    round_timeout: Duration,
+    sequence: WordSequence,
+    total_rounds: u32,
+    ghost: Option<Ghost>,
+    round: Option<CurrentRound>,
+    record: RunRecord,
+}
+
+impl SoloRun {
+    pub fn new(
+        player: String,
+        seed: u64,
+        total_rounds: u32,
+        tx: Outbox,
+        words: WordRepository,
+        round_timeout: Duration,
+    ) -> Self {
+        Self {
+            player,
+            tx,
+            words,
+            round_timeout,
+            sequence: WordSequence::new(seed),
+            total_rounds,
+            ghost: None,
+            round: None,
+            record: RunRecord::default(),
+        }
+    }
+
+    pub fn with_ghost(mut self, ghost: Ghost) -> Self {
+        self.ghost = Some(ghost);
+        self
+    }
+
+    /// Start the run on its own task. `on_finish` gets the record once the
+    /// last round ends or the player leaves.
+    pub fn spawn<F, Fut>(self, on_finish: F) -> RunHandle
+    where
+        F: FnOnce(RunRecord) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let (commands, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let record = self.run(rx).await;
+            on_finish(record).await;
+        });
+        RunHandle { commands }
+    }
+
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<RunCommand>) -> RunRecord {
+        self.start_round(1);
+
+        while let Some(round) = &self.round {
+            let next_event = round
+                .ghost_answers_at
+                .map_or(round.deadline, |at| at.min(round.deadline));
+            let timer = async move { sleep_until(next_event).await };
+
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(RunCommand::Answer { answer, round }) => self.handle_answer(&answer, round),
+                    Some(RunCommand::Skip) => self.handle_skip(),
+                    Some(RunCommand::Resync) => self.resend_round(),
+                    Some(RunCommand::Announce(msg)) => {
+                        let _ = self.tx.send(msg);
+                    }
+                    Some(RunCommand::Abandon) | None => {
+                        info!(player = self.player, "Player left solo run");
+                        self.abandon();
+                    }
+                },
+                _ = timer => self.handle_timer(),
+            }
+        }
+
+        self.record
+    }
+
+    fn resend_round(&self) {
+        if let Some(round) = &self.round {
+            let _ = self.tx.send(ServerMessage::RoundStart {
+                kanji: round.word.kanji.clone(),
+                round: round.number,
+                readings: self.words.get_readings_for_kanji(&round.word.kanji),
+            });
+        }
+    }
+
+    fn start_round(&mut self, number: u32) {
+        let Some(word) = self.sequence.next_word(&self.words) else {
+            warn!(
+                player = self.player,
+                "No words available, cannot start round"
+            );
+            return;
+        };
+        let readings = self.words.get_readings_for_kanji(&word.kanji);
+        let _ = self.tx.send(ServerMessage::RoundStart {
+            kanji: word.kanji.clone(),
+            round: number,
+            readings,
+        });
+
+        let started = Instant::now();
+        let ghost_answers_at = self
+            .ghost
+            .as_ref()
+            .and_then(|ghost| ghost.record.rounds.get(number as usize - 1))
+            .filter(|r| r.correct)
+            .map(|r| started + Duration::from_millis(r.time_ms));
+        self.round = Some(CurrentRound {
+            number,
+            word,
+            started,
+            deadline: started + self.round_timeout,
+            ghost_answers_at,
+        });
+    }
+
+    fn handle_answer(&mut self, answer: &str, round: Option<u32>) {
+        let Some(current) = &self.round else {
+            return;
+        };
+        if let Some(round) = round
+            && round != current.number
+        {
+            let _ = self.tx.send(ServerMessage::StaleAnswer { round });
+            return;
+        }
+
+        if current.word.reading == answer
+            || self.words.is_valid_reading(&current.word.kanji, answer)
+        {
+            self.finish_round(RoundEnd::Correct);
+        } else {
+            debug!(player = self.player, answer, "Wrong answer");
+            let _ = self.tx.send(ServerMessage::WrongAnswer);
+        }
+    }
+
+    /// Giving up hands the round to the ghost if it was going to answer
+    fn handle_skip(&mut self) {
+        let ghost_answers = self
+            .round
+            .as_ref()
+            .is_some_and(|round| round.ghost_answers_at.is_some());
+        if ghost_answers {
+            self.finish_round(RoundEnd::GhostAnswered);
+        } else {
+            self.finish_round(RoundEnd::Missed);
+        }
+    }
+
+    /// The ghost answered or the round ran out of time
+    fn handle_timer(&mut self) {
+        let ghost_answered = self
+            .round
+            .as_ref()
+            .and_then(|round| round.ghost_answers_at)
+            .is_some_and(|at| at <= Instant::now());
+        if ghost_answered {
+            self.finish_round(RoundEnd::GhostAnswered);
+        } else {
+            self.finish_round(RoundEnd::Missed);
+        }
+    }
+
+    fn finish_round(&mut self, end: RoundEnd) {
+        let Some(round) = self.round.take() else {
+            return;
+        };
+        self.record.rounds.push(RoundRecord {
+            correct: matches!(end, RoundEnd::Correct),
+            time_ms: round.started.elapsed().min(self.round_timeout).as_millis() as u64,
+        });
+
+        let winner = match end {
+            RoundEnd::Correct => Some(self.player.clone()),
+            RoundEnd::GhostAnswered => self.ghost.as_ref().map(|ghost| ghost.name.clone()),
+            RoundEnd::Missed => None,
+        };
+        let _ = self.tx.send(ServerMessage::RoundResult {
+            winner,
+            correct_reading: round.word.reading,
+        });
+
+        if round.number < self.total_rounds {
+            self.start_round(round.number + 1);
+        }
+    }
+
+    /// A round left mid-way is charged the time spent on it
+    fn abandon(&mut self) {
+        if let Some(round) = self.round.take() {
+            self.record.rounds.push(RoundRecord {
+                correct: false,
+                time_ms: round.started.elapsed().min(self.round_timeout).as_millis() as u64,
+            });
+        }
+    }
+}
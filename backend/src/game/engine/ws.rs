@@ -1,35 +1,192 @@
-use crate::game::core::messages::{ClientMessage, ServerMessage};
+use super::capacity::GameCapacity;
+use super::connections::{ConnectionEntry, Connections};
+use super::drain::DrainSwitch;
+use super::metrics::Metrics;
+use super::outbox::{self, Outbox};
+use super::rate_limit::{Budget, ConnectionLimiter, RateLimitVerdict};
+use crate::game::core::messages::{
+    Capability, ClientEnvelope, ClientMessage, ErrorCode, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+    SUPPORTED_MODES, ServerMessage,
+};
 use axum::extract::ws::{Message, WebSocket};
 use futures_util::{SinkExt, StreamExt};
+use std::collections::VecDeque;
 use std::future::Future;
-use std::sync::Arc;
-use tokio::sync::broadcast;
-use tracing::{debug, info, warn};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, watch};
+use tracing::{debug, error, info, warn};
+
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(15);
+pub const DEFAULT_PONG_TIMEOUT: Duration = Duration::from_secs(45);
+/// Outgoing messages a connection can have queued before droppable ones are
+/// skipped, see [`Outbox`]
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 16;
+
+/// Server-side ping schedule and how long a silent connection is kept open
+#[derive(Debug, Clone, Copy)]
+pub struct HeartbeatConfig {
+    pub interval: Duration,
+    /// Disconnect when no ping is answered for this long. Other messages
+    /// don't count: a client can't stay connected without reading.
+    pub timeout: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_PING_INTERVAL,
+            timeout: DEFAULT_PONG_TIMEOUT,
+        }
+    }
+}
+
+/// Per-connection settings passed to [`run_connection`]
+pub struct ConnectionOptions {
+    /// Client address, behind any trusted proxy
+    pub ip: Option<IpAddr>,
+    pub limiter: ConnectionLimiter,
+    pub heartbeat: HeartbeatConfig,
+    /// Size of the outgoing message queue
+    pub channel_capacity: usize,
+    pub drain: DrainSwitch,
+    pub metrics: Arc<Metrics>,
+    pub connections: Arc<Connections>,
+    pub capacity: GameCapacity,
+}
+
+/// Longest client-supplied `request_id` that is echoed back
+const MAX_REQUEST_ID_LEN: usize = 64;
 
 /// Context for a WebSocket connection, tracking the connected user
 pub struct ConnectionContext {
     pub user_id: Option<String>,
+    /// Client address, behind any trusted proxy
+    pub ip: Option<IpAddr>,
+    /// Unparseable or wrong-endpoint messages received so far
+    pub invalid_messages: u32,
+    /// `request_id` of the message currently being handled
+    pub request_id: Option<String>,
+    /// Capabilities the client negotiated with `hello`
+    pub capabilities: Vec<Capability>,
 }
 
 impl ConnectionContext {
-    pub fn new() -> Self {
-        Self { user_id: None }
+    pub fn new(ip: Option<IpAddr>) -> Self {
+        Self {
+            user_id: None,
+            ip,
+            invalid_messages: 0,
+            request_id: None,
+            capabilities: Vec::new(),
+        }
     }
+
+    /// Whether the client can `resume` a game on a new connection
+    pub fn can_resume(&self) -> bool {
+        self.capabilities.contains(&Capability::Resume)
+    }
+
+    /// Record a message that doesn't belong on this connection
+    pub fn record_invalid_message(&mut self) {
+        self.invalid_messages += 1;
+    }
+
+    /// Build an error reply correlated with the message currently being handled
+    pub fn error(&self, code: ErrorCode, message: impl Into<String>) -> ServerMessage {
+        ServerMessage::Error {
+            code,
+            message: message.into(),
+            request_id: self.request_id.clone(),
+        }
+    }
+
+    /// Error reply using the code's default description
+    pub fn error_code(&self, code: ErrorCode) -> ServerMessage {
+        self.error(code, code.description())
+    }
+}
+
+/// Keep a client-supplied request id only if it is reasonably short
+fn sanitize_request_id(request_id: Option<String>) -> Option<String> {
+    request_id.filter(|id| id.len() <= MAX_REQUEST_ID_LEN)
+}
+
+/// Most pings kept waiting for their pong; older ones are given up on
+const MAX_PENDING_PINGS: usize = 8;
+
+/// Pings sent and not yet answered. Each carries a random nonce and is timed
+/// by the server's clock, so a client can neither forge a round trip nor
+/// answer a ping before it has seen it.
+#[derive(Default)]
+struct PendingPings {
+    sent: VecDeque<(u64, Instant)>,
+}
+
+impl PendingPings {
+    /// Remember a new ping, returning its payload
+    fn send(&mut self, now: Instant) -> Vec<u8> {
+        let nonce = rand::random::<u64>();
+        if self.sent.len() >= MAX_PENDING_PINGS {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((nonce, now));
+        nonce.to_be_bytes().to_vec()
+    }
+
+    /// Round-trip time for a pong echoing one of our pings, None for any other
+    /// payload. Pings sent before the answered one are forgotten.
+    fn answer(&mut self, payload: &[u8], now: Instant) -> Option<Duration> {
+        let nonce = u64::from_be_bytes(payload.try_into().ok()?);
+        let index = self.sent.iter().position(|&(sent, _)| sent == nonce)?;
+        let (_, sent_at) = self.sent.drain(..=index).next_back()?;
+        Some(now.saturating_duration_since(sent_at))
+    }
+}
+
+/// What the receive loop shares with the connection's send task
+struct SendTaskLink {
+    /// Fires once the send task has stopped
+    closed: oneshot::Receiver<()>,
+    /// Capabilities negotiated with `hello`, which decide what gets sent
+    capabilities: watch::Sender<Vec<Capability>>,
+    /// Pings the send task is waiting on answers to
+    pings: Arc<Mutex<PendingPings>>,
+}
+
+/// Best-effort extraction of `request_id` from a message that failed to parse
+fn raw_request_id(text: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    sanitize_request_id(value.get("request_id")?.as_str().map(str::to_string))
 }
 
 /// Trait for handling WebSocket messages and disconnections.
-/// Implement this for each game mode (ephemeral, matchmaking).
+/// Implement this for each game mode (ephemeral, matchmaking, daily, ghost).
 pub trait ConnectionHandler: Send + Sync + 'static {
     /// Handle an incoming client message
     fn handle_message(
         self: Arc<Self>,
         msg: ClientMessage,
-        tx: broadcast::Sender<ServerMessage>,
+        tx: Outbox,
         ctx: &mut ConnectionContext,
     ) -> impl Future<Output = ()> + Send;
 
-    /// Handle client disconnection
-    fn handle_disconnect(&self, user_id: &str);
+    /// The connection is open, before any client message is read. Lets a
+    /// handler send on its own, like a replay stream.
+    fn on_connect(self: Arc<Self>, _tx: Outbox) {}
+
+    /// Handle client disconnection. `tx` is the connection's outbox, to tell
+    /// it apart from a newer connection of the same player. `can_resume` says
+    /// whether the client may come back with `resume`, so its game can wait
+    /// for it.
+    fn handle_disconnect(&self, user_id: &str, tx: &Outbox, can_resume: bool);
+
+    /// Record a round-trip time measured by the heartbeat
+    fn record_latency(&self, user_id: &str, rtt: Duration);
+
+    /// The client fell behind and missed messages: send it the current state
+    fn resync(&self, user_id: &str);
 
     /// Name for logging purposes
     fn name(&self) -> &'static str;
@@ -37,62 +194,331 @@ pub trait ConnectionHandler: Send + Sync + 'static {
 
 /// Run a WebSocket connection with the given handler.
 /// This handles the boilerplate of splitting the socket, spawning send/receive tasks,
-/// and coordinating shutdown.
-pub async fn run_connection<H: ConnectionHandler>(socket: WebSocket, handler: Arc<H>) {
-    info!("New {} WebSocket connection", handler.name());
+/// heartbeating, and coordinating shutdown.
+pub async fn run_connection<H: ConnectionHandler>(
+    socket: WebSocket,
+    handler: Arc<H>,
+    options: ConnectionOptions,
+) {
+    let name = handler.name();
+    let metrics = options.metrics.clone();
+    let (tx, mut rx) = outbox::channel(options.channel_capacity);
+    let Some(entry) = options.connections.register(name, options.ip, tx.clone()) else {
+        warn!(ip = ?options.ip, "Refusing {} connection, too many from one address", name);
+        metrics.limit_reached("connections_per_ip");
+        refuse(socket, ErrorCode::TooManyConnections).await;
+        return;
+    };
+    info!(ip = ?options.ip, "New {} WebSocket connection", name);
+    metrics.connection_opened(name);
+    handler.clone().on_connect(tx.clone());
     let (mut sender, receiver) = socket.split();
-    let (tx, mut rx) = broadcast::channel::<ServerMessage>(16);
-
-    // Task to send messages from the broadcast channel to the WebSocket
-    let send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            debug!(?msg, "Sending message to client");
-            let json = serde_json::to_string(&msg).unwrap();
-            if sender.send(Message::Text(json)).await.is_err() {
-                break;
+    let (close_tx, mut close_rx) = oneshot::channel::<()>();
+    let (send_closed_tx, send_closed_rx) = oneshot::channel::<()>();
+    let (capabilities_tx, capabilities_rx) = watch::channel(Vec::<Capability>::new());
+    let heartbeat = options.heartbeat;
+    let pings = Arc::new(Mutex::new(PendingPings::default()));
+    let send_pings = pings.clone();
+
+    // Task to send messages from the outbox to the WebSocket, plus pings
+    tokio::spawn(async move {
+        let mut ping = tokio::time::interval_at(
+            tokio::time::Instant::now() + heartbeat.interval,
+            heartbeat.interval,
+        );
+
+        loop {
+            tokio::select! {
+                // Flush queued messages before honouring a close request
+                biased;
+                msg = rx.recv() => {
+                    let Some(msg) = msg else {
+                        let _ = sender.send(Message::Close(None)).await;
+                        break;
+                    };
+                    if let Some(capability) = msg.required_capability()
+                        && !capabilities_rx.borrow().contains(&capability)
+                    {
+                        continue;
+                    }
+                    debug!(?msg, "Sending message to client");
+                    let json = match serde_json::to_string(&msg) {
+                        Ok(json) => json,
+                        Err(err) => {
+                            error!(?msg, %err, "Failed to serialize message, skipping it");
+                            continue;
+                        }
+                    };
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        break;
+                    }
+                }
+                _ = &mut close_rx => {
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
+                _ = ping.tick() => {
+                    let payload = send_pings.lock().unwrap().send(Instant::now());
+                    if sender.send(Message::Ping(payload)).await.is_err() {
+                        break;
+                    }
+                }
             }
         }
+
+        let _ = send_closed_tx.send(());
     });
 
-    // Task to receive messages from the WebSocket and dispatch to handler
+    // Task to receive messages from the WebSocket and dispatch to handler.
+    // It always finishes (client close, heartbeat timeout or send failure),
+    // so it is the single place that decides when the player is gone.
     let handler_clone = handler.clone();
-    let recv_task = tokio::spawn(receive_loop(receiver, tx, handler_clone));
-
-    // Wait for either task to complete
-    tokio::select! {
-        _ = send_task => {},
-        result = recv_task => {
-            if let Ok(Some(user_id)) = result {
-                handler.handle_disconnect(&user_id);
-            }
+    let recv_task = tokio::spawn(receive_loop(
+        receiver,
+        tx.clone(),
+        handler_clone,
+        options,
+        entry,
+        SendTaskLink {
+            closed: send_closed_rx,
+            capabilities: capabilities_tx,
+            pings,
         },
+    ));
+
+    let result = recv_task.await;
+
+    // The send task flushes what is queued, sends a close frame and exits on its own
+    let _ = close_tx.send(());
+
+    if let Ok(ctx) = result
+        && let Some(user_id) = &ctx.user_id
+    {
+        handler.handle_disconnect(user_id, &tx, ctx.can_resume());
     }
+    metrics.connection_closed(name);
 
     info!("{} WebSocket connection closed", handler.name());
 }
 
+/// Tell a client why it can't connect, then close
+async fn refuse(mut socket: WebSocket, code: ErrorCode) {
+    let error = ServerMessage::Error {
+        code,
+        message: code.description().to_string(),
+        request_id: None,
+    };
+    if let Ok(json) = serde_json::to_string(&error) {
+        let _ = socket.send(Message::Text(json)).await;
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+/// Answer a client's `hello`. Returns false if the client's version is unsupported
+/// and the connection should be closed.
+fn negotiate(
+    protocol_version: u32,
+    requested: &[String],
+    capabilities: &watch::Sender<Vec<Capability>>,
+    mode: &str,
+    tx: &Outbox,
+    ctx: &mut ConnectionContext,
+) -> bool {
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&protocol_version) {
+        warn!(protocol_version, "Client speaks unsupported protocol version");
+        let _ = tx.send(ctx.error(
+            ErrorCode::UnsupportedProtocolVersion,
+            format!(
+                "Protocol version {} is not supported; server accepts {} to {}",
+                protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+        ));
+        return false;
+    }
+
+    let mut enabled = Vec::new();
+    for capability in requested.iter().filter_map(|name| Capability::from_name(name)) {
+        if !enabled.contains(&capability) {
+            enabled.push(capability);
+        }
+    }
+
+    info!(protocol_version, ?enabled, "Client handshake");
+    capabilities.send_replace(enabled.clone());
+    ctx.capabilities = enabled.clone();
+
+    let _ = tx.send(ServerMessage::Welcome {
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
+        mode: mode.to_string(),
+        modes: SUPPORTED_MODES.iter().map(|m| m.to_string()).collect(),
+        capabilities: enabled,
+    });
+    true
+}
+
 async fn receive_loop<H: ConnectionHandler>(
     mut receiver: futures_util::stream::SplitStream<WebSocket>,
-    tx: broadcast::Sender<ServerMessage>,
+    tx: Outbox,
     handler: Arc<H>,
-) -> Option<String> {
-    let mut ctx = ConnectionContext::new();
+    options: ConnectionOptions,
+    mut entry: ConnectionEntry,
+    mut send_task: SendTaskLink,
+) -> ConnectionContext {
+    let ConnectionOptions {
+        ip,
+        mut limiter,
+        heartbeat,
+        channel_capacity: _,
+        drain,
+        metrics,
+        connections: _,
+        capacity,
+    } = options;
+    let mut ctx = ConnectionContext::new(ip);
+    let mut last_pong = Instant::now();
 
-    while let Some(Ok(msg)) = receiver.next().await {
-        let Message::Text(text) = msg else {
-            debug!("Received non-text message, ignoring");
-            continue;
+    loop {
+        let next = tokio::select! {
+            _ = &mut send_task.closed => break,
+            _ = entry.kicked() => break,
+            dropped = tx.lagged() => {
+                warn!(user_id = ?ctx.user_id, dropped, "Client fell behind, resyncing");
+                metrics.broadcast_lagged(handler.name(), dropped.into());
+                let _ = tx.send(ServerMessage::Resync { dropped });
+                if let Some(user_id) = &ctx.user_id {
+                    handler.resync(user_id);
+                }
+                continue;
+            }
+            _ = tokio::time::sleep_until((last_pong + heartbeat.timeout).into()) => {
+                warn!(user_id = ?ctx.user_id, "No heartbeat from client, disconnecting");
+                break;
+            }
+            next = receiver.next() => next,
         };
 
-        debug!(raw = %text, "Received message");
+        let Some(Ok(msg)) = next else {
+            break;
+        };
 
-        let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) else {
-            warn!(raw = %text, "Failed to parse client message");
-            continue;
+        let text = match msg {
+            Message::Text(text) => text,
+            Message::Pong(payload) => {
+                let now = Instant::now();
+                let Some(rtt) = send_task.pings.lock().unwrap().answer(&payload, now) else {
+                    debug!("Ignoring pong that answers none of our pings");
+                    continue;
+                };
+                last_pong = now;
+                if let Some(user_id) = &ctx.user_id {
+                    debug!(user_id, ?rtt, "Heartbeat pong");
+                    handler.record_latency(user_id, rtt);
+                }
+                continue;
+            }
+            _ => {
+                debug!("Received non-text message, ignoring");
+                continue;
+            }
         };
 
-        handler.clone().handle_message(client_msg, tx.clone(), &mut ctx).await;
+        debug!(raw = %text, "Received message");
+
+        match serde_json::from_str::<ClientEnvelope>(&text) {
+            Ok(envelope) => {
+                ctx.request_id = sanitize_request_id(envelope.request_id);
+                let client_msg = envelope.message;
+
+                let budget = Budget::for_message(&client_msg);
+                if let RateLimitVerdict::Limited { retry_after } = limiter.check(budget, Instant::now()) {
+                    warn!(user_id = ?ctx.user_id, ?retry_after, "Rate limited client message");
+                    metrics.rate_limited(budget.name());
+                    let _ = tx.send(ctx.error(
+                        ErrorCode::RateLimited,
+                        format!("Too many requests, retry in {}ms", retry_after.as_millis()),
+                    ));
+                    continue;
+                }
+
+                if let ClientMessage::Hello {
+                    protocol_version,
+                    capabilities: requested,
+                } = &client_msg
+                {
+                    if !negotiate(*protocol_version, requested, &send_task.capabilities, handler.name(), &tx, &mut ctx) {
+                        break;
+                    }
+                    continue;
+                }
+
+                if client_msg.starts_new_game() && drain.refuses_new_games() {
+                    debug!(user_id = ?ctx.user_id, "Refusing new game during maintenance");
+                    let _ = tx.send(ctx.error_code(ErrorCode::Maintenance));
+                    continue;
+                }
+
+                if client_msg.creates_game() && capacity.is_full() {
+                    warn!(user_id = ?ctx.user_id, "Refusing new game, server is full");
+                    metrics.limit_reached("games");
+                    let _ = tx.send(ctx.error_code(ErrorCode::ServerFull));
+                    continue;
+                }
+
+                handler.clone().handle_message(client_msg, tx.clone(), &mut ctx).await;
+                entry.set_player(ctx.user_id.as_deref());
+            }
+            Err(err) => {
+                warn!(raw = %text, %err, "Failed to parse client message");
+                ctx.request_id = raw_request_id(&text);
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(ErrorCode::BadRequest, err.to_string()));
+            }
+        }
+
+        if ctx.invalid_messages >= limiter.max_invalid_messages() {
+            warn!(
+                user_id = ?ctx.user_id,
+                invalid_messages = ctx.invalid_messages,
+                "Too many invalid messages, disconnecting"
+            );
+            break;
+        }
+    }
+
+    ctx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pong_is_timed_from_when_its_ping_was_sent() {
+        let mut pings = PendingPings::default();
+        let start = Instant::now();
+        let first = pings.send(start);
+        let second = pings.send(start + Duration::from_millis(100));
+
+        assert_eq!(
+            pings.answer(&second, start + Duration::from_millis(130)),
+            Some(Duration::from_millis(30))
+        );
+        // Answering the later ping gave up on the earlier one
+        assert_eq!(pings.answer(&first, start + Duration::from_millis(140)), None);
     }
 
-    ctx.user_id
+    #[test]
+    fn pongs_that_answer_no_ping_are_ignored() {
+        let mut pings = PendingPings::default();
+        let now = Instant::now();
+        let sent = pings.send(now);
+
+        assert_eq!(pings.answer(&[], now), None);
+        assert_eq!(pings.answer(&0u64.to_be_bytes(), now), None);
+        // A client-made timestamp is just an unknown nonce
+        assert_eq!(pings.answer(&u64::MAX.to_be_bytes(), now), None);
+        assert!(pings.answer(&sent, now).is_some());
+        assert_eq!(pings.answer(&sent, now), None);
+    }
 }
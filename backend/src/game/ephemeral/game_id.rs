@@ -1,7 +1,7 @@
 use rand::Rng;
 
-const CHARSET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
-const ID_LENGTH: usize = 6;
+pub const CHARSET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+pub const ID_LENGTH: usize = 6;
 
 pub fn generate_game_id() -> String {
     let mut rng = rand::rng();
@@ -1,16 +1,19 @@
+use schemars::JsonSchema;
 use serde::Serialize;
+use ts_rs::TS;
 
 /// A game visible in the lobby (pending, waiting for opponent)
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema, TS)]
 pub struct LobbyGame {
     pub game_id: String,
     pub host_name: String,
     /// Seconds since the game was created
+    #[ts(type = "number")]
     pub created_at_secs: u64,
 }
 
 /// List of games available to join
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema, TS)]
 pub struct LobbyList {
     pub games: Vec<LobbyGame>,
 }
@@ -1,4 +1,4 @@
-mod game_id;
+pub(crate) mod game_id;
 pub mod lobby;
 mod pending_game;
 mod player;
@@ -1,50 +1,111 @@
-use tokio::sync::broadcast;
-
-use crate::game::core::messages::ServerMessage;
+use crate::game::core::PendingSnapshot;
+use crate::game::engine::outbox::Outbox;
+use crate::game::engine::registry::new_resume_token;
 use super::player::EphemeralPlayer;
+use std::time::{Duration, Instant};
 
 pub struct PendingGame {
     pub game_id: String,
     pub host: EphemeralPlayer,
-    pub host_tx: broadcast::Sender<ServerMessage>,
-    pub created_at: std::time::Instant,
+    pub host_tx: Outbox,
+    /// Seed the game's words will be drawn from
+    pub seed: u64,
+    /// Lets the host reclaim the game after a server restart
+    pub host_token: String,
+    pub created_at: Instant,
 }
 
 impl PendingGame {
     pub fn new(
         game_id: impl Into<String>,
         host: EphemeralPlayer,
-        host_tx: broadcast::Sender<ServerMessage>,
+        seed: u64,
+        host_tx: Outbox,
     ) -> Self {
         Self {
             game_id: game_id.into(),
             host,
             host_tx,
-            created_at: std::time::Instant::now(),
+            seed,
+            host_token: new_resume_token(),
+            created_at: Instant::now(),
+        }
+    }
+
+    /// A pending game saved before a restart, waiting for its host to `resume`.
+    /// It keeps the age it had when saved, so it still expires on time.
+    pub fn restore(snapshot: PendingSnapshot) -> Self {
+        let now = Instant::now();
+        let age = Duration::from_millis(snapshot.age_ms);
+        Self {
+            host_token: snapshot.host_token,
+            created_at: now.checked_sub(age).unwrap_or(now),
+            ..Self::new(
+                snapshot.game_id,
+                EphemeralPlayer::new(&snapshot.host),
+                snapshot.seed,
+                Outbox::detached(),
+            )
+        }
+    }
+
+    pub fn snapshot(&self) -> PendingSnapshot {
+        PendingSnapshot {
+            game_id: self.game_id.clone(),
+            host: self.host.display_name.clone(),
+            host_token: self.host_token.clone(),
+            seed: self.seed,
+            age_ms: u64::try_from(self.age().as_millis()).unwrap_or(u64::MAX),
         }
     }
+
+    /// How long the game has been waiting for a guest
+    pub fn age(&self) -> Duration {
+        self.created_at.elapsed()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::game::engine::outbox;
 
     #[test]
     fn pending_game_stores_host_info() {
-        let (tx, _rx) = broadcast::channel(16);
+        let (tx, _rx) = outbox::channel(16);
         let host = EphemeralPlayer::new("Alice");
-        let pending = PendingGame::new("abc123", host, tx);
+        let pending = PendingGame::new("abc123", host, 42, tx);
 
         assert_eq!(pending.game_id, "abc123");
         assert_eq!(pending.host.display_name, "Alice");
+        assert_eq!(pending.seed, 42);
+    }
+
+    #[test]
+    fn restored_pending_game_keeps_host_token_and_age() {
+        let (tx, _rx) = outbox::channel(16);
+        let mut pending = PendingGame::new("abc123", EphemeralPlayer::new("Alice"), 42, tx);
+        pending.created_at -= Duration::from_secs(90);
+        let snapshot = pending.snapshot();
+        let restored = PendingGame::restore(snapshot.clone());
+
+        assert_eq!(restored.host_token, snapshot.host_token);
+        assert_eq!(restored.seed, 42);
+        assert!(restored.age() >= Duration::from_secs(90));
+        assert!(restored.age() < Duration::from_secs(91));
+        assert_ne!(
+            PendingGame::new("abc123", EphemeralPlayer::new("Alice"), 42, Outbox::detached())
+                .host_token,
+            pending.host_token
+        );
     }
 
     #[test]
     fn pending_game_tracks_creation_time() {
-        let (tx, _rx) = broadcast::channel(16);
+        let (tx, _rx) = outbox::channel(16);
         let host = EphemeralPlayer::new("Alice");
         let before = std::time::Instant::now();
-        let pending = PendingGame::new("abc123", host, tx);
+        let pending = PendingGame::new("abc123", host, 42, tx);
         let after = std::time::Instant::now();
 
         assert!(pending.created_at >= before);
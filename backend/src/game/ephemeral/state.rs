@@ -3,14 +3,18 @@ use super::lobby::{LobbyGame, LobbyList};
 use super::pending_game::PendingGame;
 use super::player::EphemeralPlayer;
 use crate::game::core::messages::ServerMessage;
-use crate::game::core::session::GameSession;
-use crate::game::core::WordRepository;
-use crate::game::engine::active_game::ActiveGame;
+use crate::game::core::word_sequence::random_seed;
+use crate::game::core::messages::ErrorCode;
+use crate::game::core::{
+    GameLogRepository, MatchRepository, ModeSnapshot, PendingSnapshot, WordRepository,
+};
+use crate::game::engine::metrics::Metrics;
+use crate::game::engine::active_game::MatchRules;
 use crate::game::engine::registry::GameRegistry;
+use crate::game::engine::outbox::Outbox;
 use dashmap::DashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::broadcast;
 use tracing::info;
 
 /// Result of joining an ephemeral game
@@ -18,42 +22,67 @@ pub struct JoinedGame {
     pub game_id: String,
     pub host_name: String,
     pub guest_name: String,
-    pub host_tx: broadcast::Sender<ServerMessage>,
 }
 
 pub struct EphemeralState {
     pub registry: Arc<GameRegistry>,
     pub pending_games: DashMap<String, PendingGame>,
+    /// Pending games older than this are dropped
+    lobby_max_age: Duration,
 }
 
 impl EphemeralState {
-    pub fn new(words: WordRepository, round_timeout: Option<Duration>) -> Self {
+    pub fn new(
+        words: WordRepository,
+        matches: MatchRepository,
+        logs: GameLogRepository,
+        rules: MatchRules,
+        metrics: Arc<Metrics>,
+        lobby_max_age: Duration,
+    ) -> Self {
         Self {
-            registry: Arc::new(GameRegistry::new(words, round_timeout)),
+            registry: Arc::new(GameRegistry::new(
+                words,
+                matches,
+                logs,
+                "ephemeral",
+                rules,
+                metrics,
+                // Casual games end without a winner when a player leaves
+                None,
+            )),
             pending_games: DashMap::new(),
+            lobby_max_age,
         }
     }
 
-    /// Create a new ephemeral game and return the game ID
+    /// Create a new ephemeral game and return the game ID.
+    /// The game's words are drawn from `seed`, or a random seed if none is given.
     pub fn create_game(
         &self,
         player_name: String,
-        tx: broadcast::Sender<ServerMessage>,
+        seed: Option<u64>,
+        tx: Outbox,
     ) -> String {
+        self.remove_stale_pending_games();
         let game_id = generate_unique_game_id(|id| self.pending_games.contains_key(id));
         let host = EphemeralPlayer::new(&player_name);
-        let pending = PendingGame::new(game_id.clone(), host, tx);
+        let seed = seed.unwrap_or_else(random_seed);
+        let pending = PendingGame::new(game_id.clone(), host, seed, tx.clone());
+        let _ = tx.send(ServerMessage::ResumeToken {
+            token: pending.host_token.clone(),
+        });
         self.pending_games.insert(game_id.clone(), pending);
-        info!(game_id, player_name, "Created pending game");
+        info!(game_id, player_name, seed, "Created pending game");
         game_id
     }
 
-    /// Join an existing pending game. Returns None if game not found.
+    /// Join an existing pending game and start it. Returns None if game not found.
     pub fn join_game(
         &self,
         game_id: &str,
         player_name: String,
-        tx: broadcast::Sender<ServerMessage>,
+        tx: Outbox,
     ) -> Option<JoinedGame> {
         let (_, pending) = self.pending_games.remove(game_id)?;
 
@@ -66,16 +95,18 @@ impl EphemeralState {
             player_name
         };
 
-        let session = GameSession::new(host_name.clone(), guest_name.clone());
-        let game = ActiveGame::new(session, pending.host_tx.clone(), tx);
-
-        self.registry.games.insert(game_id.to_string(), game);
-        self.registry
-            .player_games
-            .insert(host_name.clone(), game_id.to_string());
-        self.registry
-            .player_games
-            .insert(guest_name.clone(), game_id.to_string());
+        // Tell the host before the game task starts sending game messages
+        let _ = pending.host_tx.send(ServerMessage::OpponentJoined {
+            opponent_name: guest_name.clone(),
+        });
+        self.registry.start_game(
+            game_id,
+            host_name.clone(),
+            guest_name.clone(),
+            pending.host_tx,
+            tx,
+            pending.seed,
+        );
 
         info!(
             game_id,
@@ -88,16 +119,128 @@ impl EphemeralState {
             game_id: game_id.to_string(),
             host_name,
             guest_name,
-            host_tx: pending.host_tx,
         })
     }
 
-    pub fn handle_disconnect(&self, user_id: &str) {
-        info!(user_id, "Player disconnected");
+    /// Answer `sync`: the state of the player's game, or a reminder of the
+    /// game they are hosting while it waits for an opponent
+    pub async fn sync(&self, player_name: &str, tx: &Outbox) -> Result<(), ErrorCode> {
+        let hosted = self
+            .pending_games
+            .iter()
+            .find(|pending| pending.host.display_name == player_name)
+            .map(|pending| pending.game_id.clone());
+        if let Some(game_id) = hosted {
+            let _ = tx.send(ServerMessage::GameCreated { game_id });
+            let _ = tx.send(ServerMessage::WaitingForOpponent);
+            return Ok(());
+        }
+        let _ = tx.send(self.registry.sync(player_name).await?);
+        Ok(())
+    }
+
+    /// Reclaim a pending game or rejoin a running one after a server restart.
+    /// Returns the player's name for the connection.
+    pub async fn resume(
+        &self,
+        token: &str,
+        tx: Outbox,
+    ) -> Result<String, ErrorCode> {
+        let reclaimed = self
+            .pending_games
+            .iter_mut()
+            .find(|pending| pending.host_token == token)
+            .map(|mut pending| {
+                pending.host_tx = tx.clone();
+                (pending.game_id.clone(), pending.host.display_name.clone())
+            });
+        let Some((game_id, host_name)) = reclaimed else {
+            return self.registry.resume(token, tx).await;
+        };
+
+        info!(game_id, host_name, "Host resumed pending game");
+        let _ = tx.send(ServerMessage::GameCreated { game_id });
+        let _ = tx.send(ServerMessage::WaitingForOpponent);
+        Ok(host_name)
+    }
 
-        if let Some(info) = self.registry.remove_player_from_game(user_id) {
-            info.game.broadcast(ServerMessage::OpponentDisconnected);
+    /// Send a server notice to every host and player
+    pub fn announce(&self, msg: &ServerMessage) {
+        for pending in self.pending_games.iter() {
+            let _ = pending.host_tx.send(msg.clone());
         }
+        self.registry.announce(msg);
+    }
+
+    /// Running and pending games, for saving
+    pub async fn snapshot(&self) -> ModeSnapshot {
+        ModeSnapshot {
+            games: self.registry.snapshot().await,
+            pending: self.pending_snapshots(),
+        }
+    }
+
+    /// Save every game and tell everyone the server is going down
+    pub async fn shutdown(&self) -> ModeSnapshot {
+        for pending in self.pending_games.iter() {
+            let _ = pending.host_tx.send(ServerMessage::ServerShutdown);
+        }
+        ModeSnapshot {
+            games: self.registry.shutdown().await,
+            pending: self.pending_snapshots(),
+        }
+    }
+
+    fn pending_snapshots(&self) -> Vec<PendingSnapshot> {
+        self.pending_games.iter().map(|p| p.snapshot()).collect()
+    }
+
+    /// Bring back games saved before a restart. Pending games that were
+    /// already past the lobby's max age are dropped.
+    pub fn restore(&self, snapshot: ModeSnapshot) {
+        for pending in snapshot.pending {
+            if Duration::from_millis(pending.age_ms) > self.lobby_max_age {
+                info!(game_id = pending.game_id, "Dropping saved pending game nobody joined");
+                continue;
+            }
+            info!(game_id = pending.game_id, "Restoring pending game");
+            self.pending_games
+                .insert(pending.game_id.clone(), PendingGame::restore(pending));
+        }
+        for game in snapshot.games {
+            self.registry.restore_game(game);
+        }
+    }
+
+    pub fn handle_disconnect(&self, user_id: &str, tx: &Outbox, can_resume: bool) {
+        info!(user_id, "Player disconnected");
+
+        // A host who leaves before anyone joins takes their games with them
+        self.pending_games.retain(|game_id, pending| {
+            let abandoned = pending.host_tx.same_channel(tx);
+            if abandoned {
+                info!(game_id, "Removed pending game after its host left");
+            }
+            !abandoned
+        });
+        self.registry.remove_player_from_game(user_id, can_resume);
+    }
+
+    /// Pending games still open to join, for counting against capacity
+    pub fn pending_game_count(&self) -> usize {
+        self.remove_stale_pending_games();
+        self.pending_games.len()
+    }
+
+    /// Drop pending games nobody joined within the lobby's max age
+    fn remove_stale_pending_games(&self) {
+        self.pending_games.retain(|game_id, pending| {
+            let stale = pending.age() > self.lobby_max_age;
+            if stale {
+                info!(game_id, "Removed pending game nobody joined");
+            }
+            !stale
+        });
     }
 
     /// List pending games that are newer than max_age_secs
@@ -1,22 +1,36 @@
 use super::state::EphemeralState;
-use crate::game::core::messages::{ClientMessage, ServerMessage};
-use crate::game::engine::ws::{run_connection, ConnectionContext, ConnectionHandler};
+use crate::game::core::messages::{ClientMessage, ErrorCode, ServerMessage};
+use crate::game::core::validation::{
+    validate_answer, validate_game_code, validate_player_name, validate_seed,
+};
+use crate::game::engine::ws::{run_connection, ConnectionContext, ConnectionHandler, ConnectionOptions};
+use crate::game::engine::outbox::Outbox;
 use axum::extract::ws::WebSocket;
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::time::Duration;
 use tracing::warn;
 
 impl ConnectionHandler for EphemeralState {
     async fn handle_message(
         self: Arc<Self>,
         msg: ClientMessage,
-        tx: broadcast::Sender<ServerMessage>,
+        tx: Outbox,
         ctx: &mut ConnectionContext,
     ) {
         match msg {
-            ClientMessage::CreateGame { player_name } => {
+            // Answered by the connection loop before dispatch
+            ClientMessage::Hello { .. } => {}
+            ClientMessage::CreateGame { player_name, seed } => {
+                let (player_name, seed) =
+                    match (validate_player_name(&player_name), seed.map(validate_seed).transpose()) {
+                        (Ok(name), Ok(seed)) => (name, seed),
+                        (Err(err), _) | (_, Err(err)) => {
+                            let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                            return;
+                        }
+                    };
                 ctx.user_id = Some(player_name.clone());
-                let game_id = self.create_game(player_name, tx.clone());
+                let game_id = self.create_game(player_name, seed, tx.clone());
                 let _ = tx.send(ServerMessage::GameCreated { game_id });
                 let _ = tx.send(ServerMessage::WaitingForOpponent);
             }
@@ -24,63 +38,150 @@ impl ConnectionHandler for EphemeralState {
                 game_id,
                 player_name,
             } => {
+                let (game_id, player_name) =
+                    match (validate_game_code(&game_id), validate_player_name(&player_name)) {
+                        (Ok(game_id), Ok(player_name)) => (game_id, player_name),
+                        (Err(err), _) | (_, Err(err)) => {
+                            let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                            return;
+                        }
+                    };
                 let Some(joined) = self.join_game(&game_id, player_name, tx.clone()) else {
                     let _ = tx.send(ServerMessage::GameNotFound);
                     return;
                 };
                 // Set user_id to the (possibly modified) guest name
-                ctx.user_id = Some(joined.guest_name.clone());
-
-                // Notify host that opponent joined
-                let _ = joined.host_tx.send(ServerMessage::OpponentJoined {
-                    opponent_name: joined.guest_name.clone(),
-                });
-
-                // Send GameStart to both players
-                let _ = joined.host_tx.send(ServerMessage::GameStart {
-                    opponent: joined.guest_name.clone(),
-                });
-                let _ = tx.send(ServerMessage::GameStart {
-                    opponent: joined.host_name.clone(),
-                });
-
-                // Start round 1
-                self.registry
-                    .start_first_round(&joined.game_id, &joined.host_tx, &tx)
-                    .await;
+                ctx.user_id = Some(joined.guest_name);
             }
-            ClientMessage::Answer { answer } => {
+            ClientMessage::Resume { token } => match self.resume(&token, tx.clone()).await {
+                Ok(user_id) => ctx.user_id = Some(user_id),
+                Err(code) => {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            },
+            ClientMessage::Answer { answer, round } => {
                 let Some(user_id) = &ctx.user_id else {
                     warn!("Received answer from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
                     return;
                 };
-                self.registry.handle_answer(user_id, &answer, &tx).await;
+                let answer = match validate_answer(&answer) {
+                    Ok(answer) => answer,
+                    Err(err) => {
+                        let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                        return;
+                    }
+                };
+                if let Err(code) = self.registry.handle_answer(user_id, &answer, round, ctx.request_id.clone()) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
             }
             ClientMessage::Skip => {
                 let Some(user_id) = &ctx.user_id else {
                     warn!("Received skip from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.registry.handle_skip(user_id, ctx.request_id.clone()) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::OfferDraw => {
+                let Some(user_id) = &ctx.user_id else {
+                    warn!("Received draw offer from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
                     return;
                 };
-                self.registry.handle_skip(user_id, &tx).await;
+                if let Err(code) = self.registry.handle_offer_draw(user_id, ctx.request_id.clone()) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::AcceptDraw => {
+                let Some(user_id) = &ctx.user_id else {
+                    warn!("Received draw acceptance from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.registry.handle_accept_draw(user_id, ctx.request_id.clone()) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
             }
             ClientMessage::RequestRematch => {
                 let Some(user_id) = &ctx.user_id else {
                     warn!("Received rematch request from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.registry.handle_rematch(user_id) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Sync => {
+                let Some(user_id) = &ctx.user_id else {
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.sync(user_id, &tx).await {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Pause => {
+                let Some(user_id) = &ctx.user_id else {
+                    warn!("Received pause from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
                     return;
                 };
-                self.registry.handle_rematch(user_id, &tx).await;
+                if let Err(code) = self.registry.handle_pause(user_id) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Unpause => {
+                let Some(user_id) = &ctx.user_id else {
+                    warn!("Received unpause from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.registry.handle_unpause(user_id) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
             }
             ClientMessage::Join { .. } => {
                 warn!("Received Join message on ephemeral endpoint");
-                let _ = tx.send(ServerMessage::Error {
-                    message: "Use /ws/matchmaking for authenticated matchmaking".to_string(),
-                });
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/matchmaking for authenticated matchmaking",
+                ));
+            }
+            ClientMessage::StartDaily { .. } => {
+                warn!("Received daily challenge message on ephemeral endpoint");
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/daily for the daily challenge",
+                ));
+            }
+            ClientMessage::CreateChallenge { .. } | ClientMessage::AcceptChallenge { .. } => {
+                warn!("Received ghost challenge message on ephemeral endpoint");
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/ghost for ghost challenges",
+                ));
             }
         }
     }
 
-    fn handle_disconnect(&self, user_id: &str) {
-        self.handle_disconnect(user_id);
+    fn handle_disconnect(&self, user_id: &str, tx: &Outbox, can_resume: bool) {
+        self.handle_disconnect(user_id, tx, can_resume);
+    }
+
+    fn record_latency(&self, user_id: &str, rtt: Duration) {
+        self.registry.record_latency(user_id, rtt);
+    }
+
+    fn resync(&self, user_id: &str) {
+        self.registry.resync(user_id);
     }
 
     fn name(&self) -> &'static str {
@@ -88,6 +189,10 @@ impl ConnectionHandler for EphemeralState {
     }
 }
 
-pub async fn handle_connection(socket: WebSocket, state: Arc<EphemeralState>) {
-    run_connection(socket, state).await;
+pub async fn handle_connection(
+    socket: WebSocket,
+    state: Arc<EphemeralState>,
+    options: ConnectionOptions,
+) {
+    run_connection(socket, state, options).await;
 }
@@ -0,0 +1,7 @@
+mod state;
+pub mod summary;
+mod ws_handler;
+
+pub use state::GhostState;
+pub use summary::GhostChallengeSummary;
+pub use ws_handler::handle_connection;
@@ -0,0 +1,309 @@
+use super::summary::{GhostChallengeResult, GhostChallengeSummary};
+use crate::game::core::messages::{ErrorCode, GameEndReason, ServerMessage};
+use crate::game::core::solo::RunRecord;
+use crate::game::core::word_sequence::random_seed;
+use crate::game::core::{GhostChallenge, GhostRepository, GhostResult, WordRepository};
+use crate::game::engine::active_game::DEFAULT_ROUND_TIMEOUT;
+use crate::game::engine::solo_run::{Ghost, RunCommand, RunHandle, SoloRun};
+use crate::game::engine::outbox::Outbox;
+use crate::game::ephemeral::game_id::generate_game_id;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Words in each ghost challenge
+pub const GHOST_ROUNDS: u32 = 10;
+
+/// Asynchronous challenges: one player records a run over a seeded word set,
+/// others later play the same words against a replay ("ghost") of it.
+pub struct GhostState {
+    pub words: WordRepository,
+    pub challenges: GhostRepository,
+    pub round_timeout: Duration,
+    /// Runs in progress, by run id
+    pub runs: Arc<DashMap<String, RunHandle>>,
+}
+
+impl GhostState {
+    pub fn new(
+        words: WordRepository,
+        challenges: GhostRepository,
+        round_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            words,
+            challenges,
+            round_timeout: round_timeout.unwrap_or(DEFAULT_ROUND_TIMEOUT),
+            runs: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn is_running(&self, run_id: &str) -> bool {
+        self.runs.contains_key(run_id)
+    }
+
+    /// Start recording a new challenge. Returns the run id.
+    /// The challenge is only saved, and its code sent, once every round is played.
+    pub fn create(&self, player_name: String, tx: Outbox) -> String {
+        let run_id = uuid::Uuid::new_v4().to_string();
+        let seed = random_seed();
+        info!(player_name, seed, "Recording ghost challenge");
+
+        let _ = tx.send(ServerMessage::ChallengeStart {
+            rounds: GHOST_ROUNDS,
+            ghost: None,
+        });
+        let run = SoloRun::new(
+            player_name.clone(),
+            seed,
+            GHOST_ROUNDS,
+            tx.clone(),
+            self.words.clone(),
+            self.round_timeout,
+        );
+        let challenges = self.challenges.clone();
+        let runs = self.runs.clone();
+        let id = run_id.clone();
+        self.runs.insert(
+            run_id.clone(),
+            run.spawn(move |record| async move {
+                save_challenge(&challenges, player_name, seed, record, &tx).await;
+                runs.remove(&id);
+            }),
+        );
+        run_id
+    }
+
+    /// Play a stored challenge against its ghost. Returns the run id, or None
+    /// if there is no such challenge.
+    pub async fn accept(
+        &self,
+        challenge_id: &str,
+        player_name: String,
+        tx: Outbox,
+    ) -> Result<Option<String>, ErrorCode> {
+        let challenge = match self.challenges.get(challenge_id).await {
+            Ok(Some(challenge)) => challenge,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                error!(challenge_id, "Failed to load ghost challenge: {}", e);
+                return Err(ErrorCode::Internal);
+            }
+        };
+
+        // Round winners are reported by name, so keep the two apart
+        let player_name = if player_name == challenge.creator {
+            format!("{} (2)", player_name)
+        } else {
+            player_name
+        };
+        info!(
+            challenge_id,
+            player_name,
+            creator = challenge.creator,
+            "Playing ghost challenge"
+        );
+
+        let _ = tx.send(ServerMessage::ChallengeStart {
+            rounds: challenge.rounds,
+            ghost: Some(challenge.creator.clone()),
+        });
+        let run = SoloRun::new(
+            player_name.clone(),
+            challenge.seed,
+            challenge.rounds,
+            tx.clone(),
+            self.words.clone(),
+            self.round_timeout,
+        )
+        .with_ghost(Ghost {
+            name: challenge.creator.clone(),
+            record: challenge.ghost.clone(),
+        });
+
+        let run_id = uuid::Uuid::new_v4().to_string();
+        let challenges = self.challenges.clone();
+        let runs = self.runs.clone();
+        let id = run_id.clone();
+        self.runs.insert(
+            run_id.clone(),
+            run.spawn(move |record| async move {
+                save_result(&challenges, &challenge, player_name, record, &tx).await;
+                runs.remove(&id);
+            }),
+        );
+        Ok(Some(run_id))
+    }
+
+    /// Send a server notice to every player in a run
+    pub fn announce(&self, msg: &ServerMessage) {
+        for handle in self.runs.iter() {
+            handle.send(RunCommand::Announce(msg.clone()));
+        }
+    }
+
+    fn send(&self, run_id: &str, command: RunCommand) -> Result<(), ErrorCode> {
+        let handle = self.runs.get(run_id).ok_or(ErrorCode::NotInGame)?;
+        if handle.send(command) {
+            Ok(())
+        } else {
+            Err(ErrorCode::NotInGame)
+        }
+    }
+
+    pub fn handle_answer(
+        &self,
+        run_id: &str,
+        answer: &str,
+        round: Option<u32>,
+    ) -> Result<(), ErrorCode> {
+        self.send(
+            run_id,
+            RunCommand::Answer {
+                answer: answer.to_string(),
+                round,
+            },
+        )
+    }
+
+    pub fn handle_skip(&self, run_id: &str) -> Result<(), ErrorCode> {
+        self.send(run_id, RunCommand::Skip)
+    }
+
+    /// Send a player the current round again, after they fell behind or asked with `sync`
+    pub fn resync(&self, run_id: &str) -> Result<(), ErrorCode> {
+        self.send(run_id, RunCommand::Resync)
+    }
+
+    pub fn handle_disconnect(&self, run_id: &str) {
+        info!(run_id, "Player disconnected");
+        let _ = self.send(run_id, RunCommand::Abandon);
+    }
+
+    /// A challenge with the results of everyone who played it
+    pub async fn summary(
+        &self,
+        challenge_id: &str,
+    ) -> Result<Option<GhostChallengeSummary>, sqlx::Error> {
+        let Some(challenge) = self.challenges.get(challenge_id).await? else {
+            return Ok(None);
+        };
+        let results = self.challenges.results(challenge_id).await?;
+
+        Ok(Some(GhostChallengeSummary {
+            challenge_id: challenge.id,
+            creator: challenge.creator,
+            rounds: challenge.rounds,
+            creator_correct: challenge.ghost.correct(),
+            creator_time_ms: challenge.ghost.time_ms(),
+            results: results
+                .into_iter()
+                .map(|result| GhostChallengeResult {
+                    challenger: result.challenger,
+                    challenger_score: result.challenger_score,
+                    creator_score: result.creator_score,
+                    winner: result.winner,
+                })
+                .collect(),
+        }))
+    }
+}
+
+/// Store a finished recording under a fresh code. Runs left early are dropped:
+/// a ghost that stops halfway isn't much of an opponent.
+async fn save_challenge(
+    challenges: &GhostRepository,
+    creator: String,
+    seed: u64,
+    record: RunRecord,
+    tx: &Outbox,
+) {
+    if record.rounds.len() < GHOST_ROUNDS as usize {
+        info!(creator, "Recording left early, discarding challenge");
+        return;
+    }
+
+    let saved = async {
+        let id = loop {
+            let id = generate_game_id();
+            if !challenges.exists(&id).await? {
+                break id;
+            }
+        };
+        let challenge = GhostChallenge {
+            id,
+            creator,
+            seed,
+            rounds: GHOST_ROUNDS,
+            ghost: record,
+        };
+        challenges.create(&challenge).await?;
+        Ok::<_, sqlx::Error>(challenge)
+    }
+    .await;
+
+    match saved {
+        Ok(challenge) => {
+            info!(
+                challenge_id = challenge.id,
+                creator = challenge.creator,
+                "Ghost challenge saved"
+            );
+            let _ = tx.send(ServerMessage::ChallengeCreated {
+                challenge_id: challenge.id,
+                correct: challenge.ghost.correct(),
+                time_ms: challenge.ghost.time_ms(),
+            });
+        }
+        Err(e) => {
+            error!("Failed to save ghost challenge: {}", e);
+            let _ = tx.send(ServerMessage::Error {
+                code: ErrorCode::Internal,
+                message: "Could not save your challenge".to_string(),
+                request_id: None,
+            });
+        }
+    }
+}
+
+/// Score a run against the ghost and store it where the creator can see it.
+/// Leaving early forfeits the remaining rounds the ghost would have won.
+async fn save_result(
+    challenges: &GhostRepository,
+    challenge: &GhostChallenge,
+    challenger: String,
+    record: RunRecord,
+    tx: &Outbox,
+) {
+    let (challenger_score, creator_score) =
+        record.score_against(&challenge.ghost, challenge.rounds);
+    let winner = match challenger_score.cmp(&creator_score) {
+        std::cmp::Ordering::Greater => Some(challenger.clone()),
+        std::cmp::Ordering::Less => Some(challenge.creator.clone()),
+        std::cmp::Ordering::Equal => None, // Draw
+    };
+    info!(
+        challenge_id = challenge.id,
+        challenger, challenger_score, creator_score, "Ghost challenge played"
+    );
+
+    let result = GhostResult {
+        challenge_id: challenge.id.clone(),
+        challenger,
+        challenger_score,
+        creator_score,
+        winner: winner.clone(),
+    };
+    if let Err(e) = challenges.record_result(&result).await {
+        error!(
+            challenge_id = challenge.id,
+            "Failed to record ghost result: {}", e
+        );
+    }
+    // Every round of the challenge is played, so it ends on the round limit
+    let _ = tx.send(ServerMessage::GameEnd {
+        winner,
+        reason: GameEndReason::MaxRounds,
+    });
+}
@@ -0,0 +1,25 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use ts_rs::TS;
+
+/// One game played against a challenge's ghost
+#[derive(Debug, Serialize, JsonSchema, TS)]
+pub struct GhostChallengeResult {
+    pub challenger: String,
+    pub challenger_score: u32,
+    pub creator_score: u32,
+    /// None for a draw
+    pub winner: Option<String>,
+}
+
+/// A ghost challenge and everyone who has played it, oldest first
+#[derive(Debug, Serialize, JsonSchema, TS)]
+pub struct GhostChallengeSummary {
+    pub challenge_id: String,
+    pub creator: String,
+    pub rounds: u32,
+    pub creator_correct: u32,
+    #[ts(type = "number")]
+    pub creator_time_ms: u64,
+    pub results: Vec<GhostChallengeResult>,
+}
@@ -0,0 +1,166 @@
+use super::state::GhostState;
+use crate::game::core::messages::{ClientMessage, ErrorCode, ServerMessage};
+use crate::game::core::validation::{validate_answer, validate_game_code, validate_player_name};
+use crate::game::engine::ws::{
+    ConnectionContext, ConnectionHandler, ConnectionOptions, run_connection,
+};
+use crate::game::engine::outbox::Outbox;
+use axum::extract::ws::WebSocket;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+// `ctx.user_id` holds the id of the connection's current run, since player
+// names aren't unique here
+
+impl ConnectionHandler for GhostState {
+    async fn handle_message(
+        self: Arc<Self>,
+        msg: ClientMessage,
+        tx: Outbox,
+        ctx: &mut ConnectionContext,
+    ) {
+        match msg {
+            // Answered by the connection loop before dispatch
+            ClientMessage::Hello { .. } => {}
+            ClientMessage::CreateChallenge { player_name } => {
+                let player_name = match validate_player_name(&player_name) {
+                    Ok(name) => name,
+                    Err(err) => {
+                        let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                        return;
+                    }
+                };
+                if ctx
+                    .user_id
+                    .as_deref()
+                    .is_some_and(|run_id| self.is_running(run_id))
+                {
+                    let _ =
+                        tx.send(ctx.error(ErrorCode::BadRequest, "Finish the current run first"));
+                    return;
+                }
+                ctx.user_id = Some(self.create(player_name, tx.clone()));
+            }
+            ClientMessage::AcceptChallenge {
+                challenge_id,
+                player_name,
+            } => {
+                let (challenge_id, player_name) = match (
+                    validate_game_code(&challenge_id),
+                    validate_player_name(&player_name),
+                ) {
+                    (Ok(challenge_id), Ok(player_name)) => (challenge_id, player_name),
+                    (Err(err), _) | (_, Err(err)) => {
+                        let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                        return;
+                    }
+                };
+                if ctx
+                    .user_id
+                    .as_deref()
+                    .is_some_and(|run_id| self.is_running(run_id))
+                {
+                    let _ =
+                        tx.send(ctx.error(ErrorCode::BadRequest, "Finish the current run first"));
+                    return;
+                }
+                match self.accept(&challenge_id, player_name, tx.clone()).await {
+                    Ok(Some(run_id)) => ctx.user_id = Some(run_id),
+                    Ok(None) => {
+                        let _ = tx.send(ServerMessage::GameNotFound);
+                    }
+                    Err(code) => {
+                        let _ = tx.send(ctx.error_code(code));
+                    }
+                }
+            }
+            ClientMessage::Answer { answer, round } => {
+                let Some(run_id) = &ctx.user_id else {
+                    warn!("Received answer outside a run");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                let answer = match validate_answer(&answer) {
+                    Ok(answer) => answer,
+                    Err(err) => {
+                        let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                        return;
+                    }
+                };
+                if let Err(code) = self.handle_answer(run_id, &answer, round) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Skip => {
+                let Some(run_id) = &ctx.user_id else {
+                    warn!("Received skip outside a run");
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.handle_skip(run_id) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Sync => {
+                let Some(run_id) = &ctx.user_id else {
+                    let _ = tx.send(ctx.error_code(ErrorCode::NotInGame));
+                    return;
+                };
+                if let Err(code) = self.resync(run_id) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Pause | ClientMessage::Unpause => {
+                let _ = tx.send(ctx.error_code(ErrorCode::PauseNotAllowed));
+            }
+            // Solo runs have no opponent to agree with
+            ClientMessage::OfferDraw | ClientMessage::AcceptDraw => {
+                let _ = tx.send(ctx.error_code(ErrorCode::DrawNotAllowed));
+            }
+            ClientMessage::Join { .. }
+            | ClientMessage::CreateGame { .. }
+            | ClientMessage::JoinGame { .. }
+            | ClientMessage::Resume { .. }
+            | ClientMessage::RequestRematch => {
+                warn!("Received game message on ghost endpoint");
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/ephemeral or /ws/matchmaking to play against someone live",
+                ));
+            }
+            ClientMessage::StartDaily { .. } => {
+                warn!("Received daily challenge message on ghost endpoint");
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/daily for the daily challenge",
+                ));
+            }
+        }
+    }
+
+    fn handle_disconnect(&self, run_id: &str, _tx: &Outbox, _can_resume: bool) {
+        self.handle_disconnect(run_id);
+    }
+
+    // Ghosts don't care about latency
+    fn record_latency(&self, _run_id: &str, _rtt: Duration) {}
+
+    fn resync(&self, run_id: &str) {
+        let _ = self.resync(run_id);
+    }
+
+    fn name(&self) -> &'static str {
+        "ghost"
+    }
+}
+
+pub async fn handle_connection(
+    socket: WebSocket,
+    state: Arc<GhostState>,
+    options: ConnectionOptions,
+) {
+    run_connection(socket, state, options).await;
+}
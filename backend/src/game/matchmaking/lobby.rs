@@ -1,15 +1,25 @@
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 /// Result of attempting to join matchmaking
 #[derive(Debug, PartialEq)]
 pub enum MatchOutcome {
     Waiting,
-    Matched { opponent_id: String },
+    Matched {
+        opponent_id: String,
+        /// How long the opponent was waiting
+        waited: Duration,
+    },
+}
+
+struct WaitingPlayer {
+    user_id: String,
+    since: Instant,
 }
 
 /// Matchmaking queue (pure, no transport concerns)
 pub struct Lobby {
-    waiting: Mutex<Option<String>>,
+    waiting: Mutex<Option<WaitingPlayer>>,
 }
 
 impl Lobby {
@@ -23,18 +33,39 @@ impl Lobby {
     pub fn try_match(&self, user_id: String) -> MatchOutcome {
         let mut waiting = self.waiting.lock().unwrap();
 
-        let Some(opponent_id) = waiting.take() else {
-            *waiting = Some(user_id);
+        let Some(opponent) = waiting.take() else {
+            *waiting = Some(WaitingPlayer {
+                user_id,
+                since: Instant::now(),
+            });
             return MatchOutcome::Waiting;
         };
 
-        MatchOutcome::Matched { opponent_id }
+        MatchOutcome::Matched {
+            opponent_id: opponent.user_id,
+            waited: opponent.since.elapsed(),
+        }
+    }
+
+    /// Number of players waiting for an opponent
+    pub fn depth(&self) -> usize {
+        usize::from(self.waiting.lock().unwrap().is_some())
+    }
+
+    /// Players waiting for an opponent, longest waiting first
+    pub fn waiting_players(&self) -> Vec<String> {
+        self.waiting
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|w| w.user_id.clone())
+            .collect()
     }
 
     /// Remove a player from waiting (on disconnect)
     pub fn remove_waiting(&self, user_id: &str) {
         let mut waiting = self.waiting.lock().unwrap();
-        if waiting.as_deref() == Some(user_id) {
+        if waiting.as_ref().is_some_and(|w| w.user_id == user_id) {
             *waiting = None;
         }
     }
@@ -60,12 +91,10 @@ mod tests {
         lobby.try_match("alice".to_string());
         let result = lobby.try_match("bob".to_string());
 
-        assert_eq!(
+        assert!(matches!(
             result,
-            MatchOutcome::Matched {
-                opponent_id: "alice".to_string()
-            }
-        );
+            MatchOutcome::Matched { opponent_id, .. } if opponent_id == "alice"
+        ));
     }
 
     #[test]
@@ -84,7 +113,9 @@ mod tests {
         let lobby = Lobby::new();
 
         lobby.try_match("alice".to_string());
+        assert_eq!(lobby.depth(), 1);
         lobby.remove_waiting("alice");
+        assert_eq!(lobby.depth(), 0);
 
         // bob should wait, not match
         let result = lobby.try_match("bob".to_string());
@@ -1,21 +1,29 @@
 use super::lobby::{Lobby, MatchOutcome};
-use crate::game::core::messages::ServerMessage;
-use crate::game::core::session::GameSession;
-use crate::game::core::WordRepository;
-use crate::game::engine::active_game::ActiveGame;
+use crate::game::core::messages::{ErrorCode, ServerMessage};
+use crate::game::core::{GameLogRepository, MatchRepository, ModeSnapshot, WordRepository};
+use crate::game::engine::metrics::Metrics;
+use crate::game::engine::active_game::MatchRules;
+use crate::game::engine::forfeits::{ForfeitConfig, Forfeits};
 use crate::game::engine::registry::GameRegistry;
+use crate::game::engine::outbox::Outbox;
+use std::net::IpAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use dashmap::DashMap;
-use tokio::sync::broadcast;
+use dashmap::mapref::entry::Entry;
 use tracing::{debug, info};
 
 /// Internal result after matching + channel lookup
 pub enum JoinResult {
     Waiting,
+    /// The player forfeited too many matches lately and may queue again
+    /// after `remaining`
+    CoolingDown { remaining: Duration },
+    /// Another connection is queued or playing under this name
+    NameTaken,
     Matched {
         opponent_id: String,
-        opponent_tx: broadcast::Sender<ServerMessage>,
+        opponent_tx: Outbox,
         game_id: String,
     },
 }
@@ -23,79 +31,220 @@ pub enum JoinResult {
 pub struct MatchmakingState {
     pub registry: Arc<GameRegistry>,
     pub lobby: Lobby,
-    pub player_channels: DashMap<String, broadcast::Sender<ServerMessage>>,
+    /// Outbox of the connection that holds each name
+    pub player_channels: DashMap<String, Outbox>,
+    /// Players who left matches before they ended, for queue cooldowns
+    pub forfeits: Arc<Forfeits>,
 }
 
 impl MatchmakingState {
-    pub fn new(words: WordRepository, round_timeout: Option<Duration>) -> Self {
+    pub fn new(
+        words: WordRepository,
+        matches: MatchRepository,
+        logs: GameLogRepository,
+        rules: MatchRules,
+        metrics: Arc<Metrics>,
+        forfeit_config: ForfeitConfig,
+    ) -> Self {
+        let forfeits = Arc::new(Forfeits::new(forfeit_config));
         Self {
-            registry: Arc::new(GameRegistry::new(words, round_timeout)),
+            registry: Arc::new(GameRegistry::new(
+                words,
+                matches,
+                logs,
+                "matchmaking",
+                rules,
+                metrics,
+                Some(forfeits.clone()),
+            )),
             lobby: Lobby::new(),
             player_channels: DashMap::new(),
+            forfeits,
         }
     }
 
-    fn register_player(&self, user_id: &str, tx: broadcast::Sender<ServerMessage>) {
+    fn register_player(&self, user_id: &str, tx: Outbox) {
         debug!(user_id, "Registering player channel");
         self.player_channels.insert(user_id.to_string(), tx);
     }
 
-    pub fn try_join(&self, user_id: String, tx: broadcast::Sender<ServerMessage>) -> JoinResult {
-        // Register this player's channel
-        self.register_player(&user_id, tx.clone());
+    /// Claim the name for the connection behind `tx`. Fails if another
+    /// connection holds it, or a player by that name may still resume a game.
+    fn claim_name(&self, user_id: &str, tx: &Outbox) -> bool {
+        match self.player_channels.entry(user_id.to_string()) {
+            Entry::Occupied(owner) => owner.get().same_channel(tx),
+            Entry::Vacant(_) if self.registry.has_player(user_id) => false,
+            Entry::Vacant(slot) => {
+                debug!(user_id, "Registering player channel");
+                slot.insert(tx.clone());
+                true
+            }
+        }
+    }
+
+    pub fn try_join(&self, user_id: String, ip: Option<IpAddr>, tx: Outbox) -> JoinResult {
+        if let Some(remaining) = self.forfeits.cooldown(&user_id, ip, Instant::now()) {
+            info!(user_id, ?remaining, "Player still cooling down after forfeits");
+            return JoinResult::CoolingDown { remaining };
+        }
+
+        if !self.claim_name(&user_id, &tx) {
+            info!(user_id, "Refusing join, name already in use");
+            return JoinResult::NameTaken;
+        }
+        self.forfeits.take_seat(&user_id, ip);
 
         // Try matchmaking
-        match self.lobby.try_match(user_id.clone()) {
-            MatchOutcome::Waiting => {
-                info!(user_id, "Player waiting for opponent");
-                JoinResult::Waiting
-            }
-            MatchOutcome::Matched { opponent_id } => {
-                info!(user_id, opponent_id, "Players matched");
-
-                // Look up opponent's channel
-                let opponent_tx = self
-                    .player_channels
-                    .get(&opponent_id)
-                    .map(|r| r.clone())
-                    .expect("opponent should have registered channel");
-
-                // Create game
-                let game_id = uuid::Uuid::new_v4().to_string();
-                debug!(game_id, user_id, opponent_id, "Creating game");
-
-                let session = GameSession::new(opponent_id.clone(), user_id.clone());
-                let game = ActiveGame::new(session, opponent_tx.clone(), tx);
-
-                self.registry.games.insert(game_id.clone(), game);
-                self.registry
-                    .player_games
-                    .insert(opponent_id.clone(), game_id.clone());
-                self.registry.player_games.insert(user_id, game_id.clone());
-
-                JoinResult::Matched {
+        loop {
+            match self.lobby.try_match(user_id.clone()) {
+                MatchOutcome::Waiting => {
+                    info!(user_id, "Player waiting for opponent");
+                    return JoinResult::Waiting;
+                }
+                MatchOutcome::Matched {
                     opponent_id,
-                    opponent_tx,
-                    game_id,
+                    waited,
+                } => {
+                    // The opponent may have disconnected after we took them
+                    // from the queue; queue up again instead
+                    let Some(opponent_tx) =
+                        self.player_channels.get(&opponent_id).map(|r| r.clone())
+                    else {
+                        debug!(user_id, opponent_id, "Matched player already left, requeueing");
+                        continue;
+                    };
+                    info!(user_id, opponent_id, ?waited, "Players matched");
+                    self.registry.services.metrics.queue_waited(waited);
+
+                    let game_id = uuid::Uuid::new_v4().to_string();
+                    debug!(game_id, user_id, opponent_id, "Creating game");
+
+                    return JoinResult::Matched {
+                        opponent_id,
+                        opponent_tx,
+                        game_id,
+                    };
                 }
             }
         }
     }
 
-    pub fn handle_disconnect(&self, user_id: &str) {
+    /// Rejoin a game after a server restart. Returns the player's id for the connection.
+    pub async fn resume(
+        &self,
+        token: &str,
+        ip: Option<IpAddr>,
+        tx: Outbox,
+    ) -> Result<String, ErrorCode> {
+        let user_id = self.registry.resume(token, tx.clone()).await?;
+        self.register_player(&user_id, tx);
+        self.forfeits.take_seat(&user_id, ip);
+        Ok(user_id)
+    }
+
+    /// Running games, for saving. Players still in the lobby simply rejoin.
+    pub async fn snapshot(&self) -> ModeSnapshot {
+        ModeSnapshot {
+            games: self.registry.snapshot().await,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Send a server notice to every player, waiting or in a game
+    pub fn announce(&self, msg: &ServerMessage) {
+        self.announce_to_waiting(msg);
+        self.registry.announce(msg);
+    }
+
+    fn announce_to_waiting(&self, msg: &ServerMessage) {
+        for tx in self.player_channels.iter() {
+            if !self.registry.player_games.contains_key(tx.key()) {
+                let _ = tx.send(msg.clone());
+            }
+        }
+    }
+
+    /// Save every game and tell everyone the server is going down
+    pub async fn shutdown(&self) -> ModeSnapshot {
+        self.announce_to_waiting(&ServerMessage::ServerShutdown);
+        ModeSnapshot {
+            games: self.registry.shutdown().await,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Bring back games saved before a restart
+    pub fn restore(&self, snapshot: ModeSnapshot) {
+        for game in snapshot.games {
+            self.registry.restore_game(game);
+        }
+    }
+
+    /// Answer `sync`: the state of the player's game, or `waiting` while
+    /// they are in the queue
+    pub async fn sync(&self, user_id: &str) -> Result<ServerMessage, ErrorCode> {
+        if self.lobby.waiting_players().iter().any(|id| id == user_id) {
+            return Ok(ServerMessage::Waiting);
+        }
+        self.registry.sync(user_id).await
+    }
+
+    /// A connection closed. Only the connection holding the name leaves
+    /// the queue or game; one replaced by a `resume` elsewhere has no say.
+    pub fn handle_disconnect(&self, user_id: &str, tx: &Outbox, can_resume: bool) {
+        let owns_name = self
+            .player_channels
+            .get(user_id)
+            .is_some_and(|owner| owner.same_channel(tx));
+        if !owns_name {
+            debug!(user_id, "Stale connection closed, player is on a newer one");
+            return;
+        }
         info!(user_id, "Player disconnected");
 
-        // Remove from lobby if waiting
+        // Leave the lobby before giving up the channel, so a player matched
+        // with us in the meantime still finds it
         self.lobby.remove_waiting(user_id);
+        self.player_channels
+            .remove_if(user_id, |_, owner| owner.same_channel(tx));
 
-        // Remove player channel
-        self.player_channels.remove(user_id);
-
-        // If in a game, clean up and notify opponent
-        if let Some(info) = self.registry.remove_player_from_game(user_id)
-            && let Some(opponent_tx) = self.player_channels.get(&info.opponent_id)
-        {
-            let _ = opponent_tx.send(ServerMessage::OpponentDisconnected);
+        // If in a game, the game task notifies the opponent and records any
+        // forfeit, which needs the seat
+        if !self.registry.remove_player_from_game(user_id, can_resume) {
+            self.forfeits.leave_seat(user_id);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::engine::outbox;
+
+    async fn matchmaking_state() -> MatchmakingState {
+        let pool = sqlx::SqlitePool::connect(":memory:").await.unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        MatchmakingState::new(
+            WordRepository::load(pool.clone()).await.unwrap(),
+            MatchRepository::new(pool.clone()),
+            GameLogRepository::new(pool),
+            MatchRules::default(),
+            Arc::new(Metrics::new()),
+            ForfeitConfig::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn join_requeues_when_the_matched_player_already_left() {
+        let state = matchmaking_state().await;
+        // Still queued, but their channel is already gone
+        state.lobby.try_match("gone".to_string());
+
+        let (tx, _rx) = outbox::channel(4);
+        assert!(matches!(
+            state.try_join("bob".to_string(), None, tx),
+            JoinResult::Waiting
+        ));
+        assert_eq!(state.lobby.waiting_players(), vec!["bob".to_string()]);
+    }
+}
@@ -1,56 +1,156 @@
 use super::state::{JoinResult, MatchmakingState};
-use crate::game::core::messages::{ClientMessage, ServerMessage};
-use crate::game::engine::ws::{run_connection, ConnectionContext, ConnectionHandler};
+use crate::game::core::messages::{ClientMessage, ErrorCode, ServerMessage};
+use crate::game::core::validation::{validate_answer, validate_user_id};
+use crate::game::core::word_sequence::random_seed;
+use crate::game::engine::ws::{run_connection, ConnectionContext, ConnectionHandler, ConnectionOptions};
+use crate::game::engine::outbox::Outbox;
 use axum::extract::ws::WebSocket;
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
 impl ConnectionHandler for MatchmakingState {
     async fn handle_message(
         self: Arc<Self>,
         msg: ClientMessage,
-        tx: broadcast::Sender<ServerMessage>,
+        tx: Outbox,
         ctx: &mut ConnectionContext,
     ) {
         match msg {
+            // Answered by the connection loop before dispatch
+            ClientMessage::Hello { .. } => {}
             ClientMessage::Join { user_id } => {
+                if let Err(err) = validate_user_id(&user_id) {
+                    let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                    return;
+                }
                 info!(user_id, "Player joining matchmaking");
-                ctx.user_id = Some(user_id.clone());
-                handle_join(&self, user_id, &tx).await;
+                // Joining under a new name leaves whatever the old one was in
+                if let Some(previous) = ctx.user_id.take_if(|previous| *previous != user_id) {
+                    self.handle_disconnect(&previous, &tx, false);
+                }
+                if handle_join(&self, user_id.clone(), &tx, ctx) {
+                    ctx.user_id = Some(user_id);
+                }
             }
-            ClientMessage::Answer { answer } => {
+            ClientMessage::Resume { token } => match self.resume(&token, ctx.ip, tx.clone()).await {
+                Ok(user_id) => ctx.user_id = Some(user_id),
+                Err(code) => {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            },
+            ClientMessage::Answer { answer, round } => {
                 let Some(user_id) = &ctx.user_id else {
                     warn!("Received answer from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::Unauthenticated));
                     return;
                 };
-                self.registry.handle_answer(user_id, &answer, &tx).await;
+                let answer = match validate_answer(&answer) {
+                    Ok(answer) => answer,
+                    Err(err) => {
+                        let _ = tx.send(ctx.error(err.code(), err.to_string()));
+                        return;
+                    }
+                };
+                if let Err(code) = self.registry.handle_answer(user_id, &answer, round, ctx.request_id.clone()) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
             }
             ClientMessage::Skip => {
                 let Some(user_id) = &ctx.user_id else {
                     warn!("Received skip from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::Unauthenticated));
+                    return;
+                };
+                if let Err(code) = self.registry.handle_skip(user_id, ctx.request_id.clone()) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::OfferDraw => {
+                let Some(user_id) = &ctx.user_id else {
+                    warn!("Received draw offer from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::Unauthenticated));
+                    return;
+                };
+                if let Err(code) = self.registry.handle_offer_draw(user_id, ctx.request_id.clone()) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::AcceptDraw => {
+                let Some(user_id) = &ctx.user_id else {
+                    warn!("Received draw acceptance from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::Unauthenticated));
                     return;
                 };
-                self.registry.handle_skip(user_id, &tx).await;
+                if let Err(code) = self.registry.handle_accept_draw(user_id, ctx.request_id.clone()) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
             }
             ClientMessage::RequestRematch => {
                 let Some(user_id) = &ctx.user_id else {
                     warn!("Received rematch request from unknown user");
+                    let _ = tx.send(ctx.error_code(ErrorCode::Unauthenticated));
+                    return;
+                };
+                if let Err(code) = self.registry.handle_rematch(user_id) {
+                    let _ = tx.send(ctx.error_code(code));
+                }
+            }
+            ClientMessage::Sync => {
+                let Some(user_id) = &ctx.user_id else {
+                    let _ = tx.send(ctx.error_code(ErrorCode::Unauthenticated));
                     return;
                 };
-                self.registry.handle_rematch(user_id, &tx).await;
+                match self.sync(user_id).await {
+                    Ok(state) => {
+                        let _ = tx.send(state);
+                    }
+                    Err(code) => {
+                        let _ = tx.send(ctx.error_code(code));
+                    }
+                }
+            }
+            // Ranked games run on the clock
+            ClientMessage::Pause | ClientMessage::Unpause => {
+                let _ = tx.send(ctx.error_code(ErrorCode::PauseNotAllowed));
             }
             ClientMessage::CreateGame { .. } | ClientMessage::JoinGame { .. } => {
                 warn!("Received ephemeral game message on matchmaking endpoint");
-                let _ = tx.send(ServerMessage::Error {
-                    message: "Use /ws/ephemeral for create/join games".to_string(),
-                });
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/ephemeral for create/join games",
+                ));
+            }
+            ClientMessage::StartDaily { .. } => {
+                warn!("Received daily challenge message on matchmaking endpoint");
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/daily for the daily challenge",
+                ));
+            }
+            ClientMessage::CreateChallenge { .. } | ClientMessage::AcceptChallenge { .. } => {
+                warn!("Received ghost challenge message on matchmaking endpoint");
+                ctx.record_invalid_message();
+                let _ = tx.send(ctx.error(
+                    ErrorCode::WrongEndpoint,
+                    "Use /ws/ghost for ghost challenges",
+                ));
             }
         }
     }
 
-    fn handle_disconnect(&self, user_id: &str) {
-        self.handle_disconnect(user_id);
+    fn handle_disconnect(&self, user_id: &str, tx: &Outbox, can_resume: bool) {
+        self.handle_disconnect(user_id, tx, can_resume);
+    }
+
+    fn record_latency(&self, user_id: &str, rtt: Duration) {
+        self.registry.record_latency(user_id, rtt);
+    }
+
+    fn resync(&self, user_id: &str) {
+        self.registry.resync(user_id);
     }
 
     fn name(&self) -> &'static str {
@@ -58,15 +158,32 @@ impl ConnectionHandler for MatchmakingState {
     }
 }
 
-async fn handle_join(
+/// Queue the player. Returns whether the connection now holds the name.
+fn handle_join(
     state: &MatchmakingState,
     user_id: String,
-    tx: &broadcast::Sender<ServerMessage>,
-) {
-    match state.try_join(user_id.clone(), tx.clone()) {
+    tx: &Outbox,
+    ctx: &ConnectionContext,
+) -> bool {
+    match state.try_join(user_id.clone(), ctx.ip, tx.clone()) {
         JoinResult::Waiting => {
             debug!(user_id, "Sending Waiting message");
             let _ = tx.send(ServerMessage::Waiting);
+            true
+        }
+        JoinResult::CoolingDown { remaining } => {
+            let _ = tx.send(ctx.error(
+                ErrorCode::QueueCooldown,
+                format!(
+                    "You left too many matches recently, try again in {}s",
+                    remaining.as_millis().div_ceil(1000)
+                ),
+            ));
+            false
+        }
+        JoinResult::NameTaken => {
+            let _ = tx.send(ctx.error_code(ErrorCode::NameTaken));
+            false
         }
         JoinResult::Matched {
             opponent_id,
@@ -74,23 +191,25 @@ async fn handle_join(
             game_id,
         } => {
             info!(game_id, user_id, opponent_id, "Game starting");
-
-            let _ = tx.send(ServerMessage::GameStart {
-                opponent: opponent_id.clone(),
-            });
-            let _ = opponent_tx.send(ServerMessage::GameStart {
-                opponent: user_id,
-            });
-
-            // Start round 1
             state
                 .registry
-                .start_first_round(&game_id, &opponent_tx, tx)
-                .await;
+                .start_game(
+                    &game_id,
+                    opponent_id,
+                    user_id,
+                    opponent_tx,
+                    tx.clone(),
+                    random_seed(),
+                );
+            true
         }
     }
 }
 
-pub async fn handle_connection(socket: WebSocket, state: Arc<MatchmakingState>) {
-    run_connection(socket, state).await;
+pub async fn handle_connection(
+    socket: WebSocket,
+    state: Arc<MatchmakingState>,
+    options: ConnectionOptions,
+) {
+    run_connection(socket, state, options).await;
 }
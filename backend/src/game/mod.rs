@@ -1,7 +1,16 @@
 pub mod core;
+pub mod daily;
+pub mod drain;
 pub mod engine;
 pub mod ephemeral;
+pub mod ghost;
 pub mod matchmaking;
+pub mod recovery;
+pub mod replay;
 
-pub use core::WordRepository;
+pub use core::{
+    DailyRepository, DailyResult, FrequencyBand, GameLog, GameLogRepository, GameLogSummary,
+    GhostChallenge, GhostRepository, GhostResult,
+    MatchRecord, MatchRepository, SnapshotRepository, WordEntry, WordRepository,
+};
 pub use core::messages;
@@ -0,0 +1,107 @@
+use crate::game::core::SnapshotRepository;
+use crate::game::ephemeral::EphemeralState;
+use crate::game::matchmaking::MatchmakingState;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+pub const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
+const EPHEMERAL: &str = "ephemeral";
+const MATCHMAKING: &str = "matchmaking";
+
+/// Saves live games to the database and brings them back after a restart,
+/// so a deploy or crash doesn't end every running match
+pub struct GameRecovery {
+    ephemeral: Arc<EphemeralState>,
+    matchmaking: Arc<MatchmakingState>,
+    snapshots: SnapshotRepository,
+    /// Held while saving. True once the final shutdown snapshot is written,
+    /// after which periodic saves would only record games being torn down.
+    shut_down: Mutex<bool>,
+}
+
+impl GameRecovery {
+    pub fn new(
+        ephemeral: Arc<EphemeralState>,
+        matchmaking: Arc<MatchmakingState>,
+        snapshots: SnapshotRepository,
+    ) -> Self {
+        Self {
+            ephemeral,
+            matchmaking,
+            snapshots,
+            shut_down: Mutex::new(false),
+        }
+    }
+
+    /// Restart the games saved by the previous server process
+    pub async fn restore(&self) -> Result<(), sqlx::Error> {
+        let ephemeral = self.snapshots.load(EPHEMERAL).await?;
+        let matchmaking = self.snapshots.load(MATCHMAKING).await?;
+        info!(
+            games = ephemeral.games.len() + matchmaking.games.len(),
+            pending = ephemeral.pending.len(),
+            "Restoring saved games"
+        );
+        self.ephemeral.restore(ephemeral);
+        self.matchmaking.restore(matchmaking);
+        Ok(())
+    }
+
+    /// Save the current state of every game
+    pub async fn save(&self) -> Result<(), sqlx::Error> {
+        let shut_down = self.shut_down.lock().await;
+        if *shut_down {
+            return Ok(());
+        }
+        self.snapshots
+            .save(EPHEMERAL, &self.ephemeral.snapshot().await)
+            .await?;
+        self.snapshots
+            .save(MATCHMAKING, &self.matchmaking.snapshot().await)
+            .await
+    }
+
+    /// Save every `interval` until shutdown
+    pub fn spawn_periodic_saves(self: &Arc<Self>, interval: Duration) {
+        let recovery = self.clone();
+        tokio::spawn(async move {
+            let mut ticks =
+                tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticks.tick().await;
+                if *recovery.shut_down.lock().await {
+                    break;
+                }
+                if let Err(e) = recovery.save().await {
+                    error!("Failed to save games: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Stop every game, tell the players the server is going down and save
+    /// the games so they can be resumed after the restart
+    pub async fn shutdown(&self) {
+        let mut shut_down = self.shut_down.lock().await;
+        if *shut_down {
+            return;
+        }
+        *shut_down = true;
+
+        let ephemeral = self.ephemeral.shutdown().await;
+        let matchmaking = self.matchmaking.shutdown().await;
+        info!(
+            games = ephemeral.games.len() + matchmaking.games.len(),
+            pending = ephemeral.pending.len(),
+            "Saving games for shutdown"
+        );
+        for (mode, snapshot) in [(EPHEMERAL, ephemeral), (MATCHMAKING, matchmaking)] {
+            if let Err(e) = self.snapshots.save(mode, &snapshot).await {
+                error!(mode, "Failed to save games at shutdown: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::game::core::GameLogSummary;
+use schemars::JsonSchema;
+use serde::Serialize;
+use ts_rs::TS;
+
+/// A recorded game that can be replayed at `/ws/replay/{replay_id}`
+#[derive(Debug, Serialize, JsonSchema, TS)]
+pub struct ReplaySummary {
+    pub replay_id: String,
+    pub game_id: String,
+    pub mode: String,
+    pub player1: String,
+    pub player2: String,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`
+    pub created_at: String,
+}
+
+/// Recorded games, most recent first
+#[derive(Debug, Serialize, JsonSchema, TS)]
+pub struct ReplayList {
+    pub replays: Vec<ReplaySummary>,
+}
+
+impl From<Vec<GameLogSummary>> for ReplayList {
+    fn from(logs: Vec<GameLogSummary>) -> Self {
+        Self {
+            replays: logs
+                .into_iter()
+                .map(|log| ReplaySummary {
+                    replay_id: log.id,
+                    game_id: log.game_id,
+                    mode: log.mode,
+                    player1: log.player1,
+                    player2: log.player2,
+                    created_at: log.created_at,
+                })
+                .collect(),
+        }
+    }
+}
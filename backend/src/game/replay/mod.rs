@@ -0,0 +1,5 @@
+pub mod list;
+mod stream;
+
+pub use list::ReplayList;
+pub use stream::{ReplaySpeed, stream_replay};
@@ -0,0 +1,128 @@
+use crate::game::core::GameLog;
+use crate::game::core::messages::{ClientMessage, ErrorCode};
+use crate::game::engine::outbox::Outbox;
+use crate::game::engine::ws::{
+    ConnectionContext, ConnectionHandler, ConnectionOptions, run_connection,
+};
+use axum::extract::ws::WebSocket;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+/// Longest wait between two replayed events, before speeding up. Keeps time
+/// spent idling between a match and its rematch out of the replay.
+const MAX_EVENT_GAP: Duration = Duration::from_secs(30);
+
+/// Fastest allowed playback multiplier
+const MAX_SPEED: f64 = 100.0;
+
+/// Playback speed multiplier: 1 plays at the original pace, 2 twice as fast
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplaySpeed(f64);
+
+impl ReplaySpeed {
+    pub const ORIGINAL: Self = Self(1.0);
+
+    /// None unless `0 < speed <= 100`
+    pub fn new(speed: f64) -> Option<Self> {
+        (speed > 0.0 && speed <= MAX_SPEED).then_some(Self(speed))
+    }
+
+    fn scale(self, gap: Duration) -> Duration {
+        gap.min(MAX_EVENT_GAP).div_f64(self.0)
+    }
+}
+
+/// One viewer's replay: the recorded game as `viewer` saw it. Runs as an
+/// ordinary connection, so it counts against the address's connection cap
+/// and shows up for operators.
+struct ReplayViewer {
+    log: GameLog,
+    viewer: String,
+    speed: ReplaySpeed,
+}
+
+impl ConnectionHandler for ReplayViewer {
+    async fn handle_message(
+        self: Arc<Self>,
+        msg: ClientMessage,
+        tx: Outbox,
+        ctx: &mut ConnectionContext,
+    ) {
+        // Hello is answered by the connection loop; a replay takes nothing else
+        if !matches!(msg, ClientMessage::Hello { .. }) {
+            ctx.record_invalid_message();
+            let _ = tx.send(ctx.error(ErrorCode::WrongEndpoint, "Replays only stream"));
+        }
+    }
+
+    /// Pace the recorded events like the original, then close
+    fn on_connect(self: Arc<Self>, tx: Outbox) {
+        tokio::spawn(async move {
+            debug!(replay_id = self.log.id, viewer = self.viewer, "Streaming replay");
+            let mut last_ms = 0;
+            for timed in &self.log.events {
+                let gap = Duration::from_millis(timed.at_ms.saturating_sub(last_ms));
+                last_ms = timed.at_ms;
+                let Some(msg) = timed.event.replay_message(&self.viewer) else {
+                    continue;
+                };
+                tokio::time::sleep(self.speed.scale(gap)).await;
+
+                if tx.send(msg).is_err() {
+                    debug!(replay_id = self.log.id, "Replay viewer left");
+                    return;
+                }
+            }
+            tx.close();
+        });
+    }
+
+    fn handle_disconnect(&self, _user_id: &str, _tx: &Outbox, _can_resume: bool) {}
+
+    fn record_latency(&self, _user_id: &str, _rtt: Duration) {}
+
+    // The stream carries on from where it is; there is no state to catch up on
+    fn resync(&self, _user_id: &str) {}
+
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+}
+
+/// Send a recorded game as `viewer` saw it, paced like the original,
+/// then close the connection
+pub async fn stream_replay(
+    socket: WebSocket,
+    log: GameLog,
+    viewer: String,
+    speed: ReplaySpeed,
+    options: ConnectionOptions,
+) {
+    let viewer = ReplayViewer { log, viewer, speed };
+    run_connection(socket, Arc::new(viewer), options).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_must_be_positive_and_bounded() {
+        assert_eq!(ReplaySpeed::new(1.0), Some(ReplaySpeed::ORIGINAL));
+        assert!(ReplaySpeed::new(100.0).is_some());
+        for speed in [0.0, -1.0, 100.5, f64::NAN, f64::INFINITY] {
+            assert_eq!(ReplaySpeed::new(speed), None, "{speed}");
+        }
+    }
+
+    #[test]
+    fn gaps_are_capped_then_scaled() {
+        let double = ReplaySpeed::new(2.0).unwrap();
+        assert_eq!(double.scale(Duration::from_secs(4)), Duration::from_secs(2));
+        assert_eq!(
+            double.scale(Duration::from_secs(600)),
+            Duration::from_secs(15)
+        );
+    }
+}
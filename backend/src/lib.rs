@@ -1,17 +1,56 @@
+mod admin;
+mod client_ip;
 mod game;
+mod origins;
+pub mod protocol;
+pub mod readiness;
 
+pub use game::core::event_log::{GameEvent, TimedEvent};
+pub use game::core::solo::{RoundRecord, RunRecord};
+pub use game::engine::active_game::MatchRules;
+pub use game::engine::forfeits::ForfeitConfig;
+pub use game::engine::rate_limit::{BucketConfig, RateLimitConfig};
+pub use game::engine::ws::{DEFAULT_CHANNEL_CAPACITY, HeartbeatConfig};
 pub use game::messages;
+pub use game::drain::{DEFAULT_DRAIN_TIMEOUT, Drain};
+pub use game::recovery::{DEFAULT_SNAPSHOT_INTERVAL, GameRecovery};
+pub use origins::{AllowedOrigins, DEFAULT_ORIGIN, InvalidOrigin};
+/// Saved game state, as stored by [`SnapshotRepository`]
+pub mod snapshots {
+    pub use crate::game::core::{
+        GameProgress, GameSnapshot, ModeSnapshot, PendingSnapshot, RoundSnapshot,
+    };
+}
+pub use game::{
+    DailyRepository, DailyResult, FrequencyBand, GameLog, GameLogRepository, GameLogSummary,
+    GhostChallenge, GhostRepository, GhostResult, MatchRecord, MatchRepository,
+    SnapshotRepository, WordEntry, WordRepository,
+};
 
 use axum::{
     Json, Router,
-    extract::{State, WebSocketUpgrade, ws::WebSocket},
-    http,
-    response::Response,
+    extract::{ConnectInfo, Path, Query, Request, State, WebSocketUpgrade, ws::WebSocket},
+    http::{self, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::get,
 };
-use game::{WordRepository, ephemeral::EphemeralState, ephemeral::LobbyList, matchmaking::MatchmakingState};
-use tower_http::cors::{Any, CorsLayer};
+use game::engine::capacity::GameCapacity;
+use game::engine::connections::Connections;
+use game::engine::metrics::{METRICS_CONTENT_TYPE, Metrics};
+use game::engine::rate_limit::RateLimiter;
+use game::engine::ws::ConnectionOptions;
+use game::core::validation::validate_game_code;
+use game::daily::{ChallengeDate, DailyLeaderboard, DailySeeds, DailyState};
+use game::ghost::{GhostChallengeSummary, GhostState};
+use game::replay::{ReplayList, ReplaySpeed};
+use readiness::Readiness;
+use game::{ephemeral::EphemeralState, ephemeral::LobbyList, matchmaking::MatchmakingState};
+use ipnet::IpNet;
+use serde::Deserialize;
 use sqlx::SqlitePool;
+use std::fmt;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -19,67 +58,530 @@ async fn health() -> &'static str {
     "ok"
 }
 
+/// 200 when the database and dictionary are usable, 503 otherwise
+async fn ready(State(state): State<AppState>) -> (StatusCode, Json<readiness::ReadinessReport>) {
+    let report = state.readiness.check().await;
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// Prometheus scrape endpoint. Game and queue counts are read at scrape time.
+async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let metrics = &state.metrics;
+    metrics.set_pending_games(state.ephemeral.pending_game_count());
+    metrics.set_queue_depth(state.matchmaking.lobby.depth());
+    for (mode, count) in [
+        ("ephemeral", state.ephemeral.registry.games.len()),
+        ("matchmaking", state.matchmaking.registry.games.len()),
+        ("daily", state.daily.runs.len()),
+        ("ghost", state.ghost.runs.len()),
+    ] {
+        metrics.set_active_games(mode, count);
+    }
+    ([(http::header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], metrics.encode())
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub ephemeral: Arc<EphemeralState>,
     pub matchmaking: Arc<MatchmakingState>,
+    pub daily: Arc<DailyState>,
+    pub ghost: Arc<GhostState>,
+    pub game_logs: GameLogRepository,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub heartbeat: HeartbeatConfig,
+    /// Outgoing message queue size per connection
+    pub channel_capacity: usize,
+    /// How old a pending game can be and still be listed in the lobby
+    pub lobby_max_age: Duration,
+    pub allowed_origins: AllowedOrigins,
+    pub drain: Arc<Drain>,
+    pub metrics: Arc<Metrics>,
+    pub readiness: Arc<Readiness>,
+    pub connections: Arc<Connections>,
+    pub capacity: GameCapacity,
+    /// Proxies whose `X-Forwarded-For` header is believed
+    pub trusted_proxies: Arc<[IpNet]>,
+    /// Bearer token for `/admin` endpoints; they are disabled without one
+    pub admin_token: Option<String>,
 }
 
-async fn ephemeral_ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(|socket| handle_ephemeral_socket(socket, state))
+impl AppState {
+    /// Settings for a new connection. The peer address is only known when the
+    /// router is served with `into_make_service_with_connect_info`.
+    fn connection_options(
+        &self,
+        connect_info: Option<ConnectInfo<SocketAddr>>,
+        headers: &HeaderMap,
+    ) -> ConnectionOptions {
+        let ip = connect_info
+            .map(|ConnectInfo(addr)| client_ip::client_ip(addr.ip(), headers, &self.trusted_proxies));
+        ConnectionOptions {
+            ip,
+            limiter: self.rate_limiter.connection(ip),
+            heartbeat: self.heartbeat,
+            channel_capacity: self.channel_capacity,
+            drain: self.drain.switch(),
+            metrics: self.metrics.clone(),
+            connections: self.connections.clone(),
+            capacity: self.capacity.clone(),
+        }
+    }
 }
 
-async fn handle_ephemeral_socket(socket: WebSocket, state: AppState) {
-    game::ephemeral::handle_connection(socket, state.ephemeral).await;
+/// Refuse WebSocket upgrades from pages on other sites. CORS doesn't apply to
+/// WebSockets, so without this any site could open a game connection as its visitor.
+async fn require_allowed_origin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let origin = request.headers().get(http::header::ORIGIN);
+    if !state.allowed_origins.allows(origin) {
+        tracing::warn!(?origin, path = %request.uri().path(), "Rejected WebSocket origin");
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(next.run(request).await)
 }
 
-async fn matchmaking_ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(|socket| handle_matchmaking_socket(socket, state))
+async fn ephemeral_ws_handler(
+    ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Response {
+    let options = state.connection_options(connect_info, &headers);
+    ws.on_upgrade(|socket| handle_ephemeral_socket(socket, state, options))
 }
 
-async fn handle_matchmaking_socket(socket: WebSocket, state: AppState) {
-    game::matchmaking::handle_connection(socket, state.matchmaking).await;
+async fn handle_ephemeral_socket(socket: WebSocket, state: AppState, options: ConnectionOptions) {
+    game::ephemeral::handle_connection(socket, state.ephemeral, options).await;
 }
 
-const LOBBY_MAX_AGE_SECS: u64 = 300; // 5 minutes
+async fn matchmaking_ws_handler(
+    ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Response {
+    let options = state.connection_options(connect_info, &headers);
+    ws.on_upgrade(|socket| handle_matchmaking_socket(socket, state, options))
+}
+
+async fn handle_matchmaking_socket(socket: WebSocket, state: AppState, options: ConnectionOptions) {
+    game::matchmaking::handle_connection(socket, state.matchmaking, options).await;
+}
+
+async fn daily_ws_handler(
+    ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Response {
+    let options = state.connection_options(connect_info, &headers);
+    ws.on_upgrade(|socket| handle_daily_socket(socket, state, options))
+}
+
+async fn handle_daily_socket(socket: WebSocket, state: AppState, options: ConnectionOptions) {
+    game::daily::handle_connection(socket, state.daily, options).await;
+}
+
+async fn ghost_ws_handler(
+    ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Response {
+    let options = state.connection_options(connect_info, &headers);
+    ws.on_upgrade(|socket| handle_ghost_socket(socket, state, options))
+}
 
+async fn handle_ghost_socket(socket: WebSocket, state: AppState, options: ConnectionOptions) {
+    game::ghost::handle_connection(socket, state.ghost, options).await;
+}
+
+/// Default cap on connections from one client address
+pub const DEFAULT_MAX_CONNECTIONS_PER_IP: usize = 10;
+/// Default cap on pending games, running games and solo runs together
+pub const DEFAULT_MAX_GAMES: usize = 1000;
+
+/// Default age after which a pending game drops out of the lobby
+pub const DEFAULT_LOBBY_MAX_AGE: Duration = Duration::from_secs(300);
+
+/// Empty while draining or in maintenance, since joining would be refused
 async fn lobby_handler(State(state): State<AppState>) -> Json<LobbyList> {
-    Json(state.ephemeral.list_pending_games(LOBBY_MAX_AGE_SECS))
+    if state.drain.refuses_new_games() {
+        return Json(LobbyList { games: Vec::new() });
+    }
+    Json(state.ephemeral.list_pending_games(state.lobby_max_age.as_secs()))
 }
 
-pub fn app(pool: SqlitePool) -> Router {
-    app_with_config(pool, None)
+#[derive(Deserialize)]
+struct LeaderboardQuery {
+    /// YYYY-MM-DD, today (UTC) if omitted
+    date: Option<String>,
 }
 
-pub fn app_with_config(pool: SqlitePool, round_timeout: Option<Duration>) -> Router {
-    let word_repo = WordRepository::new(pool);
+async fn daily_leaderboard_handler(
+    State(state): State<AppState>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<DailyLeaderboard>, StatusCode> {
+    let date = match query.date {
+        Some(date) => ChallengeDate::parse(&date).ok_or(StatusCode::BAD_REQUEST)?,
+        None => ChallengeDate::today(),
+    };
+    match state.daily.leaderboard(date).await {
+        Ok(leaderboard) => Ok(Json(leaderboard)),
+        Err(e) => {
+            tracing::error!("Failed to load daily leaderboard: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
 
-    let state = AppState {
-        ephemeral: Arc::new(EphemeralState::new(word_repo.clone(), round_timeout)),
-        matchmaking: Arc::new(MatchmakingState::new(word_repo, round_timeout)),
+/// A ghost challenge and its results, so the creator can see how others did
+async fn ghost_challenge_handler(
+    State(state): State<AppState>,
+    Path(challenge_id): Path<String>,
+) -> Result<Json<GhostChallengeSummary>, StatusCode> {
+    let challenge_id = validate_game_code(&challenge_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    match state.ghost.summary(&challenge_id).await {
+        Ok(Some(summary)) => Ok(Json(summary)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to load ghost challenge: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Most replays listed by `GET /replays`
+const REPLAY_LIST_SIZE: u32 = 50;
+
+#[derive(Deserialize)]
+struct ReplayListQuery {
+    /// Only replays of this game code
+    game_id: Option<String>,
+}
+
+async fn replay_list_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ReplayListQuery>,
+) -> Result<Json<ReplayList>, StatusCode> {
+    match state
+        .game_logs
+        .list(query.game_id.as_deref(), REPLAY_LIST_SIZE)
+        .await
+    {
+        Ok(logs) => Ok(Json(logs.into())),
+        Err(e) => {
+            tracing::error!("Failed to list replays: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReplayQuery {
+    /// Playback multiplier, 1 (original pace) if omitted
+    speed: Option<f64>,
+    /// Whose view to replay, player 1 if omitted
+    player: Option<String>,
+}
+
+/// Stream a recorded game over the WebSocket protocol, then close
+async fn replay_ws_handler(
+    ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(replay_id): Path<String>,
+    Query(query): Query<ReplayQuery>,
+) -> Result<Response, StatusCode> {
+    let speed = match query.speed {
+        Some(speed) => ReplaySpeed::new(speed).ok_or(StatusCode::BAD_REQUEST)?,
+        None => ReplaySpeed::ORIGINAL,
+    };
+    let log = match state.game_logs.get(&replay_id).await {
+        Ok(Some(log)) => log,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Failed to load game log: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
     };
+    let viewer = match query.player {
+        Some(player) if player == log.player1 || player == log.player2 => player,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+        None => log.player1.clone(),
+    };
+    let options = state.connection_options(connect_info, &headers);
+    Ok(ws.on_upgrade(move |socket| {
+        game::replay::stream_replay(socket, log, viewer, speed, options)
+    }))
+}
 
-    let cors_allow_all = std::env::var("CORS_ALLOW_ALL")
-        .map(|v| v == "true" || v == "1")
-        .unwrap_or(false);
+pub async fn app(pool: SqlitePool) -> Result<Router, StartupError> {
+    app_with_config(pool, None).await
+}
 
-    let cors = if cors_allow_all {
-        CorsLayer::new()
-            .allow_origin(Any)
-            .allow_methods(Any)
-            .allow_headers(Any)
-    } else {
-        CorsLayer::new()
-            .allow_origin(["https://yomi.alsvik.cloud".parse().unwrap()])
-            .allow_methods([http::Method::GET])
-            .allow_headers([http::header::CONTENT_TYPE])
+/// Tunables for the game server. `Default` matches production settings.
+#[derive(Debug, Clone, Default)]
+pub struct AppOptions {
+    pub round_timeout: Option<Duration>,
+    /// Round wins that take a match, 10 if None
+    pub wins_needed: Option<u32>,
+    /// Rounds after which a match ends whatever the score, 30 if None
+    pub max_rounds: Option<u32>,
+    /// How long a restored game waits for its players, 2 minutes if None
+    pub resume_grace: Option<Duration>,
+    /// How long a match waits for a player whose connection dropped, 30 seconds if None
+    pub reconnect_grace: Option<Duration>,
+    pub rate_limits: RateLimitConfig,
+    pub heartbeat: HeartbeatConfig,
+    /// Queue cooldowns for players who leave ranked matches
+    pub forfeits: ForfeitConfig,
+    /// Outgoing message queue size per connection, 16 if None
+    pub channel_capacity: Option<usize>,
+    /// How long pending games stay listed in the lobby, 5 minutes if None
+    pub lobby_max_age: Option<Duration>,
+    /// Origins allowed by CORS
+    pub allowed_origins: AllowedOrigins,
+    /// Open WebSocket connections allowed per client address, 10 if None
+    pub max_connections_per_ip: Option<usize>,
+    /// Pending and running games (including solo runs) allowed at once, 1000 if None
+    pub max_games: Option<usize>,
+    /// Proxies (e.g. Caddy) whose `X-Forwarded-For` gives the client address
+    pub trusted_proxies: Vec<IpNet>,
+    /// How often live games are saved for recovery, 30 seconds if None
+    pub snapshot_interval: Option<Duration>,
+    /// How long running games get to finish when draining, 5 minutes if None
+    pub drain_timeout: Option<Duration>,
+    /// Enables the `/admin` endpoints, which require it as a bearer token
+    pub admin_token: Option<String>,
+    /// Words each frequency band needs for `/ready` to pass, 0 if None
+    pub min_words_per_band: Option<usize>,
+    /// Key the daily challenge seeds are derived from. If None, one is
+    /// generated and kept in the database.
+    pub daily_seed_secret: Option<String>,
+}
+
+/// Why the server could not start
+#[derive(Debug)]
+pub enum StartupError {
+    Database(sqlx::Error),
+    /// The `words` table is empty, so no game could draw a word
+    EmptyDictionary,
+}
+
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupError::Database(e) => write!(f, "database error: {}", e),
+            StartupError::EmptyDictionary => write!(f, "the word dictionary is empty"),
+        }
+    }
+}
+
+impl std::error::Error for StartupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StartupError::Database(e) => Some(e),
+            StartupError::EmptyDictionary => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for StartupError {
+    fn from(e: sqlx::Error) -> Self {
+        StartupError::Database(e)
+    }
+}
+
+/// The router, plus the handles `main` uses to drain and save games on shutdown
+pub struct App {
+    pub router: Router,
+    pub recovery: Arc<GameRecovery>,
+    pub drain: Arc<Drain>,
+}
+
+pub async fn app_with_config(
+    pool: SqlitePool,
+    round_timeout: Option<Duration>,
+) -> Result<Router, StartupError> {
+    app_with_options(
+        pool,
+        AppOptions {
+            round_timeout,
+            ..AppOptions::default()
+        },
+    )
+    .await
+}
+
+/// Build the router. Loads the word dictionary into memory first.
+pub async fn app_with_options(pool: SqlitePool, options: AppOptions) -> Result<Router, StartupError> {
+    let word_repo = WordRepository::load(pool.clone()).await?;
+    Ok(build(pool, word_repo, options).await?.router)
+}
+
+/// Build the app around an already loaded dictionary. Restores the games
+/// saved by the previous process and starts saving them periodically.
+/// Refuses to start with an empty dictionary.
+pub async fn build(
+    pool: SqlitePool,
+    word_repo: WordRepository,
+    options: AppOptions,
+) -> Result<App, StartupError> {
+    if word_repo.is_empty() {
+        return Err(StartupError::EmptyDictionary);
+    }
+    let readiness = Arc::new(Readiness::new(
+        pool.clone(),
+        word_repo.clone(),
+        options
+            .min_words_per_band
+            .unwrap_or(readiness::DEFAULT_MIN_WORDS_PER_BAND),
+    ));
+    let defaults = MatchRules::default();
+    let rules = MatchRules {
+        round_timeout: options.round_timeout.unwrap_or(defaults.round_timeout),
+        wins_needed: options.wins_needed.unwrap_or(defaults.wins_needed),
+        max_rounds: options.max_rounds.unwrap_or(defaults.max_rounds),
+        resume_grace: options.resume_grace.unwrap_or(defaults.resume_grace),
+        reconnect_grace: options.reconnect_grace.unwrap_or(defaults.reconnect_grace),
     };
+    let round_timeout = Some(rules.round_timeout);
+    let match_repo = MatchRepository::new(pool.clone());
+    let daily_repo = DailyRepository::new(pool.clone());
+    let ghost_repo = GhostRepository::new(pool.clone());
+    let log_repo = GameLogRepository::new(pool.clone());
+    let snapshot_repo = SnapshotRepository::new(pool);
 
-    Router::new()
-        .route("/health", get(health))
-        .route("/lobby", get(lobby_handler))
+    let metrics = Arc::new(Metrics::new());
+    let ephemeral = Arc::new(EphemeralState::new(
+        word_repo.clone(),
+        match_repo.clone(),
+        log_repo.clone(),
+        rules,
+        metrics.clone(),
+        options.lobby_max_age.unwrap_or(DEFAULT_LOBBY_MAX_AGE),
+    ));
+    let matchmaking = Arc::new(MatchmakingState::new(
+        word_repo.clone(),
+        match_repo,
+        log_repo.clone(),
+        rules,
+        metrics.clone(),
+        options.forfeits.clone(),
+    ));
+    let daily_secret = match &options.daily_seed_secret {
+        Some(secret) => secret.clone(),
+        None => daily_repo.seed_secret().await?,
+    };
+    let daily = Arc::new(DailyState::new(
+        word_repo.clone(),
+        daily_repo,
+        DailySeeds::new(daily_secret.as_bytes()),
+        round_timeout,
+    ));
+    let ghost = Arc::new(GhostState::new(word_repo, ghost_repo, round_timeout));
+    let drain = Arc::new(Drain::new(
+        options
+            .drain_timeout
+            .unwrap_or(DEFAULT_DRAIN_TIMEOUT),
+        ephemeral.clone(),
+        matchmaking.clone(),
+        daily.clone(),
+        ghost.clone(),
+    ));
+
+    let capacity = {
+        let (ephemeral, matchmaking) = (ephemeral.clone(), matchmaking.clone());
+        let (daily, ghost) = (daily.clone(), ghost.clone());
+        GameCapacity::new(
+            options.max_games.unwrap_or(DEFAULT_MAX_GAMES),
+            move || {
+                ephemeral.pending_game_count()
+                    + ephemeral.registry.games.len()
+                    + matchmaking.registry.games.len()
+                    + daily.runs.len()
+                    + ghost.runs.len()
+            },
+        )
+    };
+
+    let state = AppState {
+        ephemeral,
+        matchmaking,
+        daily,
+        ghost,
+        game_logs: log_repo,
+        rate_limiter: Arc::new(RateLimiter::new(options.rate_limits)),
+        heartbeat: options.heartbeat,
+        channel_capacity: options
+            .channel_capacity
+            .unwrap_or(DEFAULT_CHANNEL_CAPACITY),
+        lobby_max_age: options.lobby_max_age.unwrap_or(DEFAULT_LOBBY_MAX_AGE),
+        allowed_origins: options.allowed_origins,
+        drain: drain.clone(),
+        metrics,
+        readiness,
+        connections: Arc::new(Connections::new(
+            options
+                .max_connections_per_ip
+                .unwrap_or(DEFAULT_MAX_CONNECTIONS_PER_IP),
+        )),
+        capacity,
+        trusted_proxies: options.trusted_proxies.into(),
+        admin_token: options.admin_token,
+    };
+
+    let recovery = Arc::new(GameRecovery::new(
+        state.ephemeral.clone(),
+        state.matchmaking.clone(),
+        snapshot_repo,
+    ));
+    recovery.restore().await?;
+    recovery.spawn_periodic_saves(
+        options
+            .snapshot_interval
+            .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL),
+    );
+
+    let cors = state.allowed_origins.cors();
+
+    let websockets = Router::new()
         .route("/ws/ephemeral", get(ephemeral_ws_handler))
         .route("/ws/matchmaking", get(matchmaking_ws_handler))
+        .route("/ws/daily", get(daily_ws_handler))
+        .route("/ws/ghost", get(ghost_ws_handler))
+        .route("/ws/replay/:replay_id", get(replay_ws_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_allowed_origin,
+        ));
+
+    let router = Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route("/metrics", get(metrics_handler))
+        .route("/lobby", get(lobby_handler))
+        .route("/daily/leaderboard", get(daily_leaderboard_handler))
+        .route("/ghost/:challenge_id", get(ghost_challenge_handler))
+        .route("/replays", get(replay_list_handler))
+        .merge(websockets)
+        .nest("/admin", admin::router(state.clone()))
         .layer(cors)
-        .with_state(state)
+        .with_state(state);
+
+    Ok(App {
+        router,
+        recovery,
+        drain,
+    })
 }
@@ -1,22 +1,29 @@
 mod config;
 
-use config::Config;
+use config::{Config, LogFormat};
 use sqlx::SqlitePool;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use std::net::SocketAddr;
+use yomitaisen::WordRepository;
+use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
 
+    let config = Config::load().unwrap_or_else(|e| panic!("Invalid configuration: {}", e));
+
+    let fmt_layer = match config.logging.format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    };
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
+        .with(fmt_layer)
         .with(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
-    let config = Config::from_env();
     let addr = config.addr();
 
-    let pool = SqlitePool::connect(&config.database_url)
+    let pool = SqlitePool::connect(&config.server.database_url)
         .await
         .expect("Failed to connect to database");
 
@@ -28,14 +35,61 @@ async fn main() {
     tracing::info!("Starting server on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, yomitaisen::app(pool))
-        .with_graceful_shutdown(shutdown_signal())
+    let words = WordRepository::load(pool.clone())
         .await
-        .unwrap();
+        .expect("Failed to load words");
+    tokio::spawn(reload_words_on_hangup(words.clone()));
+
+    let app = yomitaisen::build(pool, words, config.app_options())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to start: {}", e));
+    let recovery = app.recovery;
+    let drain = app.drain;
+    axum::serve(
+        listener,
+        app.router
+            .into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        // Drain on the first signal (or `POST /admin/drain`), skip the wait on a second one
+        tokio::select! {
+            _ = shutdown_signal() => {
+                drain.start();
+            }
+            _ = drain.started() => {}
+        }
+        tokio::select! {
+            _ = drain.finished() => {}
+            _ = shutdown_signal() => tracing::info!("Shutting down without waiting for games"),
+        }
+        // Save games and warn players while their connections are still open
+        recovery.shutdown().await;
+    })
+    .await
+    .unwrap();
 
     tracing::info!("Server shut down gracefully");
 }
 
+/// Reload the word dictionary on SIGHUP, e.g. after importing new words
+async fn reload_words_on_hangup(words: WordRepository) {
+    #[cfg(unix)]
+    {
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .expect("Failed to install SIGHUP handler");
+        while hangup.recv().await.is_some() {
+            match words.reload().await {
+                Ok(count) => tracing::info!(count, "Reloaded words"),
+                Err(e) => tracing::error!("Failed to reload words: {}", e),
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = words;
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
@@ -55,7 +109,7 @@ async fn shutdown_signal() {
     let terminate = std::future::pending::<()>();
 
     tokio::select! {
-        _ = ctrl_c => tracing::info!("Received Ctrl+C, shutting down..."),
-        _ = terminate => tracing::info!("Received SIGTERM, shutting down..."),
+        _ = ctrl_c => tracing::info!("Received Ctrl+C"),
+        _ = terminate => tracing::info!("Received SIGTERM"),
     }
 }
@@ -0,0 +1,117 @@
+use axum::http::{self, HeaderValue};
+use std::fmt;
+use tower_http::cors::{Any, CorsLayer};
+
+/// Origin of the production frontend
+pub const DEFAULT_ORIGIN: &str = "https://yomi.alsvik.cloud";
+
+/// Browser origins allowed to use the server
+#[derive(Debug, Clone, PartialEq)]
+pub enum AllowedOrigins {
+    /// Any origin, for local development
+    Any,
+    Only(Vec<HeaderValue>),
+}
+
+/// An entry in the allowed origins list that isn't an origin
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidOrigin(pub String);
+
+impl fmt::Display for InvalidOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid origin {:?}, expected `*` or a scheme and host like {}",
+            self.0, DEFAULT_ORIGIN
+        )
+    }
+}
+
+impl std::error::Error for InvalidOrigin {}
+
+impl AllowedOrigins {
+    /// `*` allows any origin; otherwise each entry must be an http(s) origin
+    /// without a path, e.g. `https://example.com` or `http://localhost:5173`
+    pub fn parse<S: AsRef<str>>(origins: &[S]) -> Result<Self, InvalidOrigin> {
+        if origins.iter().any(|origin| origin.as_ref() == "*") {
+            return Ok(AllowedOrigins::Any);
+        }
+        origins
+            .iter()
+            .map(|origin| parse_origin(origin.as_ref()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(AllowedOrigins::Only)
+    }
+
+    /// Whether a request with this `Origin` header may use the server.
+    /// Requests without one don't come from a browser page, so they can't be
+    /// cross-site and are let through.
+    pub fn allows(&self, origin: Option<&HeaderValue>) -> bool {
+        match (self, origin) {
+            (AllowedOrigins::Any, _) | (_, None) => true,
+            (AllowedOrigins::Only(origins), Some(origin)) => origins.contains(origin),
+        }
+    }
+
+    pub(crate) fn cors(&self) -> CorsLayer {
+        match self {
+            AllowedOrigins::Any => CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any),
+            AllowedOrigins::Only(origins) => CorsLayer::new()
+                .allow_origin(origins.clone())
+                .allow_methods([http::Method::GET])
+                .allow_headers([http::header::CONTENT_TYPE]),
+        }
+    }
+}
+
+impl Default for AllowedOrigins {
+    fn default() -> Self {
+        AllowedOrigins::Only(vec![HeaderValue::from_static(DEFAULT_ORIGIN)])
+    }
+}
+
+fn parse_origin(origin: &str) -> Result<HeaderValue, InvalidOrigin> {
+    let invalid = || InvalidOrigin(origin.to_string());
+    let host = origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"))
+        .ok_or_else(invalid)?;
+    if host.is_empty() || host.contains(['/', '?', '#', ' ']) {
+        return Err(invalid());
+    }
+    HeaderValue::from_str(origin).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_origins_and_wildcard() {
+        assert_eq!(
+            AllowedOrigins::parse(&["http://localhost:5173"]),
+            Ok(AllowedOrigins::Only(vec![HeaderValue::from_static(
+                "http://localhost:5173"
+            )]))
+        );
+        assert_eq!(
+            AllowedOrigins::parse(&["https://example.com", "*"]),
+            Ok(AllowedOrigins::Any)
+        );
+        for bad in ["example.com", "https://", "https://example.com/", "ftp://x"] {
+            assert!(AllowedOrigins::parse(&[bad]).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn allows_listed_origins_and_non_browser_clients() {
+        let origins = AllowedOrigins::default();
+        assert!(origins.allows(Some(&HeaderValue::from_static(DEFAULT_ORIGIN))));
+        assert!(origins.allows(None));
+        assert!(!origins.allows(Some(&HeaderValue::from_static("https://evil.example"))));
+        assert!(AllowedOrigins::Any.allows(Some(&HeaderValue::from_static("null"))));
+    }
+}
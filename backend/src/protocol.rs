@@ -0,0 +1,100 @@
+//! Machine-readable description of the client/server protocol.
+//!
+//! The committed files in `protocol/` are generated from the Rust types by
+//! `cargo run --bin protocol_schema`; `tests/schema_tests.rs` fails when they drift.
+
+use crate::game::daily::leaderboard::{DailyLeaderboard, DailyLeaderboardEntry};
+use crate::game::ephemeral::lobby::{LobbyGame, LobbyList};
+use crate::game::ghost::summary::{GhostChallengeResult, GhostChallengeSummary};
+use crate::game::replay::list::{ReplayList, ReplaySummary};
+use crate::messages::{
+    Capability, ClientEnvelope, ClientMessage, ErrorCode, GameEndReason, PROTOCOL_VERSION,
+    RoundState,
+    ServerMessage,
+};
+use schemars::generate::SchemaSettings;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use ts_rs::TS;
+
+/// File name of the JSON Schema inside `protocol/`
+pub const JSON_SCHEMA_FILE: &str = "schema.json";
+/// File name of the TypeScript definitions inside `protocol/`
+pub const TYPESCRIPT_FILE: &str = "protocol.ts";
+
+/// Where the generated files are committed
+pub fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("protocol")
+}
+
+const GENERATED_NOTICE: &str =
+    "Generated by `cargo run --bin protocol_schema` from the backend types. Do not edit.";
+
+/// JSON Schema (draft 2020-12) for every WebSocket message in both
+/// directions and the HTTP responses
+pub fn json_schema() -> Value {
+    let mut generator = SchemaSettings::draft2020_12().into_generator();
+    let roots = [
+        generator.subschema_for::<ClientEnvelope>(),
+        generator.subschema_for::<ServerMessage>(),
+        generator.subschema_for::<LobbyList>(),
+        generator.subschema_for::<DailyLeaderboard>(),
+        generator.subschema_for::<GhostChallengeSummary>(),
+        generator.subschema_for::<ReplayList>(),
+    ];
+    let definitions = generator.take_definitions(true);
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$comment": GENERATED_NOTICE,
+        "title": "Yomitaisen protocol",
+        "description": format!(
+            "Protocol version {PROTOCOL_VERSION}. Clients send ClientEnvelope over the \
+             WebSocket and receive ServerMessage; GET /lobby returns LobbyList, \
+             GET /daily/leaderboard returns DailyLeaderboard, GET /ghost/{{id}} \
+             returns GhostChallengeSummary and GET /replays returns ReplayList. \
+             /ws/replay/{{id}} streams a recorded game as ServerMessage."
+        ),
+        "anyOf": roots,
+        "$defs": definitions,
+    })
+}
+
+/// TypeScript definitions for the same types as [`json_schema`]
+pub fn typescript() -> String {
+    let decls = [
+        Capability::decl(),
+        ClientMessage::decl(),
+        ClientEnvelope::decl(),
+        ErrorCode::decl(),
+        ServerMessage::decl(),
+        GameEndReason::decl(),
+        RoundState::decl(),
+        LobbyGame::decl(),
+        LobbyList::decl(),
+        DailyLeaderboardEntry::decl(),
+        DailyLeaderboard::decl(),
+        GhostChallengeResult::decl(),
+        GhostChallengeSummary::decl(),
+        ReplaySummary::decl(),
+        ReplayList::decl(),
+    ];
+
+    let mut out = format!("// {GENERATED_NOTICE}\n\n");
+    out.push_str(&format!(
+        "export const PROTOCOL_VERSION = {PROTOCOL_VERSION};\n"
+    ));
+    for decl in decls {
+        out.push_str("\nexport ");
+        out.push_str(&decl);
+        out.push('\n');
+    }
+    out
+}
+
+/// Pretty-printed schema with a trailing newline, as written to disk
+pub fn json_schema_string() -> String {
+    let mut out = serde_json::to_string_pretty(&json_schema()).expect("schema serializes");
+    out.push('\n');
+    out
+}
@@ -0,0 +1,143 @@
+use crate::game::{FrequencyBand, WordRepository};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tracing::warn;
+
+/// Words a frequency band needs for the server to report ready by default.
+/// Zero only requires the dictionary as a whole to be non-empty.
+pub const DEFAULT_MIN_WORDS_PER_BAND: usize = 0;
+
+/// Result of `GET /ready`
+#[derive(Debug, Serialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub database: DatabaseCheck,
+    pub migrations: MigrationCheck,
+    pub words: WordPoolCheck,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatabaseCheck {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Latest migration applied to the database vs the latest this build ships
+#[derive(Debug, Serialize)]
+pub struct MigrationCheck {
+    pub ok: bool,
+    pub applied: Option<i64>,
+    pub expected: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WordPoolCheck {
+    pub ok: bool,
+    pub total: usize,
+    pub min_per_band: usize,
+    pub bands: Vec<BandCheck>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BandCheck {
+    pub band: &'static str,
+    pub count: usize,
+    pub ok: bool,
+}
+
+/// Checks behind `GET /ready`: the database answers, is fully migrated, and
+/// the loaded dictionary has enough words to play with
+pub struct Readiness {
+    pool: SqlitePool,
+    words: WordRepository,
+    min_words_per_band: usize,
+}
+
+impl Readiness {
+    pub fn new(pool: SqlitePool, words: WordRepository, min_words_per_band: usize) -> Self {
+        Self {
+            pool,
+            words,
+            min_words_per_band,
+        }
+    }
+
+    pub async fn check(&self) -> ReadinessReport {
+        let database = match sqlx::query("SELECT 1").execute(&self.pool).await {
+            Ok(_) => DatabaseCheck {
+                ok: true,
+                error: None,
+            },
+            Err(e) => {
+                warn!("Readiness: database unreachable: {}", e);
+                DatabaseCheck {
+                    ok: false,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        let expected = latest_migration();
+        let applied = if database.ok {
+            self.applied_migration().await
+        } else {
+            None
+        };
+        let migrations = MigrationCheck {
+            ok: applied == Some(expected),
+            applied,
+            expected,
+        };
+
+        let words = self.check_words();
+
+        ReadinessReport {
+            ready: database.ok && migrations.ok && words.ok,
+            database,
+            migrations,
+            words,
+        }
+    }
+
+    async fn applied_migration(&self) -> Option<i64> {
+        let result: Result<Option<i64>, sqlx::Error> =
+            sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+                .fetch_one(&self.pool)
+                .await;
+        result.unwrap_or_else(|e| {
+            warn!("Readiness: could not read migrations: {}", e);
+            None
+        })
+    }
+
+    fn check_words(&self) -> WordPoolCheck {
+        let bands: Vec<BandCheck> = FrequencyBand::ALL
+            .iter()
+            .map(|&band| {
+                let count = self.words.band_len(band);
+                BandCheck {
+                    band: band.name(),
+                    count,
+                    ok: count >= self.min_words_per_band,
+                }
+            })
+            .collect();
+        let total = self.words.len();
+        WordPoolCheck {
+            ok: total > 0 && bands.iter().all(|b| b.ok),
+            total,
+            min_per_band: self.min_words_per_band,
+            bands,
+        }
+    }
+}
+
+/// Version of the newest migration compiled into this build
+fn latest_migration() -> i64 {
+    sqlx::migrate!()
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0)
+}
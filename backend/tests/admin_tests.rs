@@ -0,0 +1,306 @@
+mod common;
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use yomitaisen::AppOptions;
+use yomitaisen::messages::{ErrorCode, GameEndReason, ServerMessage};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+async fn spawn_admin_server() -> TestServer {
+    spawn_test_server_with_options(AppOptions {
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        ..AppOptions::default()
+    })
+    .await
+}
+
+async fn admin_get(server: &TestServer, path: &str) -> Value {
+    let response = reqwest::Client::new()
+        .get(server.http_url(path))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    response.json().await.unwrap()
+}
+
+async fn admin_post(server: &TestServer, path: &str, body: Option<Value>) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .post(server.http_url(path))
+        .bearer_auth(ADMIN_TOKEN);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    request.send().await.unwrap()
+}
+
+/// Connect and wait for the handshake, so the server is tracking the connection
+async fn connected(mut ws: WsStream) -> WsStream {
+    ws.send(hello_msg(1, &[])).await.unwrap();
+    assert!(matches!(recv(&mut ws).await, ServerMessage::Welcome { .. }));
+    ws
+}
+
+/// Alice hosts and Bob joins; returns the game code and both players once round 1 has started
+async fn start_game(server: &TestServer) -> (String, WsStream, WsStream) {
+    let mut alice = connect_ephemeral(server).await;
+    alice.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut alice).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut alice).await, ServerMessage::WaitingForOpponent);
+
+    let mut bob = connect_ephemeral(server).await;
+    bob.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut bob).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::OpponentJoined { .. }
+    ));
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::GameStart { .. }
+    ));
+    for ws in [&mut alice, &mut bob] {
+        assert!(matches!(recv(ws).await, ServerMessage::RoundStart { .. }));
+    }
+    (game_id, alice, bob)
+}
+
+#[tokio::test]
+async fn admin_endpoints_require_the_token() {
+    let server = spawn_admin_server().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(server.http_url("/admin/games"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = client
+        .get(server.http_url("/admin/games"))
+        .bearer_auth("wrong")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let without_token = spawn_test_server().await;
+    let response = client
+        .get(without_token.http_url("/admin/games"))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn operator_lists_and_ends_a_game() {
+    let server = spawn_admin_server().await;
+    let (game_id, mut alice, mut bob) = start_game(&server).await;
+
+    let body = admin_get(&server, "/admin/games").await;
+    assert_eq!(
+        body["games"],
+        json!([{
+            "game_id": game_id,
+            "mode": "ephemeral",
+            "player1": "Alice",
+            "player2": "Bob",
+            "player1_score": 0,
+            "player2_score": 0,
+            "round": 1,
+            "running": true,
+            "player1_latency_ms": null,
+            "player2_latency_ms": null,
+        }])
+    );
+
+    let response = admin_post(&server, &format!("/admin/games/{}/end", game_id), None).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    for ws in [&mut alice, &mut bob] {
+        assert_eq!(
+            recv(ws).await,
+            ServerMessage::GameEnd {
+                winner: None,
+                reason: GameEndReason::Aborted,
+            }
+        );
+    }
+
+    // The players are still connected, but no longer in a game
+    alice.send(answer_msg("にほん")).await.unwrap();
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::Error {
+            code: ErrorCode::NotInGame,
+            ..
+        }
+    ));
+    assert_eq!(admin_get(&server, "/admin/games").await["games"], json!([]));
+
+    let response = admin_post(&server, &format!("/admin/games/{}/end", game_id), None).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn operator_sees_pending_games_queue_and_players() {
+    let server = spawn_admin_server().await;
+
+    let mut alice = connect_ephemeral(&server).await;
+    alice.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut alice).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut alice).await, ServerMessage::WaitingForOpponent);
+
+    let mut waiting = connect_matchmaking(&server).await;
+    waiting.send(join_msg("user-1")).await.unwrap();
+    assert_eq!(recv(&mut waiting).await, ServerMessage::Waiting);
+
+    let _idle = connected(connect_daily(&server).await).await;
+
+    let pending = admin_get(&server, "/admin/pending").await;
+    assert_eq!(pending["games"][0]["game_id"], game_id);
+    assert_eq!(pending["games"][0]["host_name"], "Alice");
+
+    let queue = admin_get(&server, "/admin/queue").await;
+    assert_eq!(queue["waiting"], json!(["user-1"]));
+
+    let players = admin_get(&server, "/admin/players").await;
+    let players: Vec<(String, Value)> = players["players"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| (p["mode"].as_str().unwrap().to_string(), p["player"].clone()))
+        .collect();
+    assert_eq!(
+        players,
+        vec![
+            ("ephemeral".to_string(), json!("Alice")),
+            ("matchmaking".to_string(), json!("user-1")),
+            ("daily".to_string(), Value::Null),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn kicked_player_is_disconnected() {
+    let server = spawn_admin_server().await;
+    let (_, mut alice, mut bob) = start_game(&server).await;
+
+    let players = admin_get(&server, "/admin/players").await;
+    let bob_connection = players["players"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["player"] == "Bob")
+        .unwrap()["connection_id"]
+        .as_u64()
+        .unwrap();
+
+    let response = admin_post(
+        &server,
+        &format!("/admin/players/{}/kick", bob_connection),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+    assert!(matches!(
+        recv(&mut bob).await,
+        ServerMessage::Error {
+            code: ErrorCode::Kicked,
+            ..
+        }
+    ));
+    loop {
+        match bob.next().await {
+            Some(Ok(msg)) if msg.is_close() => break,
+            Some(Ok(_)) => continue,
+            _ => break,
+        }
+    }
+    assert_eq!(recv(&mut alice).await, ServerMessage::OpponentDisconnected);
+
+    let response = admin_post(
+        &server,
+        &format!("/admin/players/{}/kick", bob_connection),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn broadcast_reaches_everyone_connected() {
+    let server = spawn_admin_server().await;
+    let mut ephemeral = connected(connect_ephemeral(&server).await).await;
+    let mut ghost = connected(connect_ghost(&server).await).await;
+
+    let response = admin_post(
+        &server,
+        "/admin/broadcast",
+        Some(json!({"message": "Back in five"})),
+    )
+    .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["sent"], 2);
+
+    for ws in [&mut ephemeral, &mut ghost] {
+        assert_eq!(
+            recv(ws).await,
+            ServerMessage::Announcement {
+                message: "Back in five".to_string()
+            }
+        );
+    }
+
+    let response = admin_post(&server, "/admin/broadcast", Some(json!({"message": "  "}))).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn maintenance_refuses_new_games_until_turned_off() {
+    let server = spawn_admin_server().await;
+
+    let response = admin_post(
+        &server,
+        "/admin/maintenance",
+        Some(json!({"enabled": true})),
+    )
+    .await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body, json!({"enabled": true}));
+
+    let mut ws = connect_ephemeral(&server).await;
+    ws.send(create_game_msg("Alice")).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error {
+            code: ErrorCode::Maintenance,
+            ..
+        }
+    ));
+
+    admin_post(
+        &server,
+        "/admin/maintenance",
+        Some(json!({"enabled": false})),
+    )
+    .await;
+    ws.send(create_game_msg("Alice")).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::GameCreated { .. }
+    ));
+}
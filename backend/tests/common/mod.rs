@@ -1,13 +1,21 @@
-use futures_util::{SinkExt, StreamExt};
+#![allow(dead_code)]
+
+use futures_util::StreamExt;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
 use yomitaisen::messages::{ClientMessage, ServerMessage};
+use yomitaisen::{AppOptions, BucketConfig, Drain, GameRecovery, RateLimitConfig, WordRepository};
 
 pub type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
 
 pub struct TestServer {
     base_url: String,
+    pub recovery: Arc<GameRecovery>,
+    pub drain: Arc<Drain>,
 }
 
 impl TestServer {
@@ -19,6 +27,18 @@ impl TestServer {
         format!("{}/ws/matchmaking", self.base_url)
     }
 
+    pub fn daily_url(&self) -> String {
+        format!("{}/ws/daily", self.base_url)
+    }
+
+    pub fn ghost_url(&self) -> String {
+        format!("{}/ws/ghost", self.base_url)
+    }
+
+    pub fn replay_url(&self, replay_id: &str, query: &str) -> String {
+        format!("{}/ws/replay/{}?{}", self.base_url, replay_id, query)
+    }
+
     pub fn http_url(&self, path: &str) -> String {
         format!(
             "http://{}{}",
@@ -33,32 +53,115 @@ pub async fn spawn_test_server() -> TestServer {
 }
 
 pub async fn spawn_test_server_with_timeout(round_timeout: Option<Duration>) -> TestServer {
+    spawn_test_server_with_options(AppOptions {
+        round_timeout,
+        ..AppOptions::default()
+    })
+    .await
+}
+
+pub async fn spawn_test_server_with_rate_limits(rate_limits: RateLimitConfig) -> TestServer {
+    spawn_test_server_with_options(AppOptions {
+        rate_limits,
+        ..AppOptions::default()
+    })
+    .await
+}
+
+/// Lift the answer and message budgets out of the way of tests that play
+/// quickly or run several players from 127.0.0.1
+pub fn relaxed_rate_limits() -> RateLimitConfig {
+    RateLimitConfig {
+        answers: BucketConfig::new(1000.0, 1000.0),
+        messages: BucketConfig::new(1000.0, 1000.0),
+        ip_answers: BucketConfig::new(1000.0, 1000.0),
+        ..RateLimitConfig::default()
+    }
+}
+
+pub async fn spawn_test_server_with_options(options: AppOptions) -> TestServer {
+    spawn_test_server_with_pool(test_pool().await, options).await
+}
+
+/// Fresh in-memory database with migrations applied
+pub async fn test_pool() -> sqlx::SqlitePool {
     let pool = sqlx::SqlitePool::connect(":memory:").await.unwrap();
     sqlx::migrate!().run(&pool).await.unwrap();
+    pool
+}
 
+/// Start a server on an existing database, e.g. to restart one that was shut down
+pub async fn spawn_test_server_with_pool(
+    pool: sqlx::SqlitePool,
+    options: AppOptions,
+) -> TestServer {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
 
+    let words = WordRepository::load(pool.clone()).await.unwrap();
+    let app = yomitaisen::build(pool, words, options).await.unwrap();
+    let recovery = app.recovery;
+    let drain = app.drain;
+    let router = app.router;
     tokio::spawn(async move {
-        let app = yomitaisen::app_with_config(pool, round_timeout);
+        let app = router.into_make_service_with_connect_info::<SocketAddr>();
         axum::serve(listener, app).await.unwrap();
     });
 
     TestServer {
         base_url: format!("ws://{}", addr),
+        recovery,
+        drain,
     }
 }
 
 pub async fn connect_ephemeral(server: &TestServer) -> WsStream {
-    let (ws, _) = connect_async(&server.ephemeral_url()).await.expect("Failed to connect");
+    let (ws, _) = connect_async(&server.ephemeral_url())
+        .await
+        .expect("Failed to connect");
     ws
 }
 
+/// Connect as if through a proxy that reported `forwarded_for` as the client
+pub async fn connect_forwarded(url: &str, forwarded_for: &str) -> WsStream {
+    let mut request = url.into_client_request().unwrap();
+    request.headers_mut().insert(
+        "X-Forwarded-For",
+        HeaderValue::from_str(forwarded_for).unwrap(),
+    );
+    connect_async(request).await.unwrap().0
+}
+
 pub async fn connect_matchmaking(server: &TestServer) -> WsStream {
-    let (ws, _) = connect_async(&server.matchmaking_url()).await.expect("Failed to connect");
+    let (ws, _) = connect_async(&server.matchmaking_url())
+        .await
+        .expect("Failed to connect");
+    ws
+}
+
+pub async fn connect_daily(server: &TestServer) -> WsStream {
+    let (ws, _) = connect_async(&server.daily_url())
+        .await
+        .expect("Failed to connect");
+    ws
+}
+
+pub async fn connect_ghost(server: &TestServer) -> WsStream {
+    let (ws, _) = connect_async(&server.ghost_url())
+        .await
+        .expect("Failed to connect");
     ws
 }
 
+pub fn hello_msg(protocol_version: u32, capabilities: &[&str]) -> Message {
+    let json = serde_json::to_string(&ClientMessage::Hello {
+        protocol_version,
+        capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+    })
+    .unwrap();
+    Message::Text(json.into())
+}
+
 pub fn join_msg(user_id: &str) -> Message {
     let json = serde_json::to_string(&ClientMessage::Join {
         user_id: user_id.to_string(),
@@ -70,6 +173,16 @@ pub fn join_msg(user_id: &str) -> Message {
 pub fn answer_msg(answer: &str) -> Message {
     let json = serde_json::to_string(&ClientMessage::Answer {
         answer: answer.to_string(),
+        round: None,
+    })
+    .unwrap();
+    Message::Text(json.into())
+}
+
+pub fn round_answer_msg(answer: &str, round: u32) -> Message {
+    let json = serde_json::to_string(&ClientMessage::Answer {
+        answer: answer.to_string(),
+        round: Some(round),
     })
     .unwrap();
     Message::Text(json.into())
@@ -78,6 +191,16 @@ pub fn answer_msg(answer: &str) -> Message {
 pub fn create_game_msg(player_name: &str) -> Message {
     let json = serde_json::to_string(&ClientMessage::CreateGame {
         player_name: player_name.to_string(),
+        seed: None,
+    })
+    .unwrap();
+    Message::Text(json.into())
+}
+
+pub fn create_seeded_game_msg(player_name: &str, seed: u64) -> Message {
+    let json = serde_json::to_string(&ClientMessage::CreateGame {
+        player_name: player_name.to_string(),
+        seed: Some(seed),
     })
     .unwrap();
     Message::Text(json.into())
@@ -92,14 +215,83 @@ pub fn join_game_msg(game_id: &str, player_name: &str) -> Message {
     Message::Text(json.into())
 }
 
+pub fn start_daily_msg(player_name: &str) -> Message {
+    let json = serde_json::to_string(&ClientMessage::StartDaily {
+        player_name: player_name.to_string(),
+    })
+    .unwrap();
+    Message::Text(json.into())
+}
+
+pub fn create_challenge_msg(player_name: &str) -> Message {
+    let json = serde_json::to_string(&ClientMessage::CreateChallenge {
+        player_name: player_name.to_string(),
+    })
+    .unwrap();
+    Message::Text(json.into())
+}
+
+pub fn accept_challenge_msg(challenge_id: &str, player_name: &str) -> Message {
+    let json = serde_json::to_string(&ClientMessage::AcceptChallenge {
+        challenge_id: challenge_id.to_string(),
+        player_name: player_name.to_string(),
+    })
+    .unwrap();
+    Message::Text(json.into())
+}
+
+pub fn skip_msg() -> Message {
+    let json = serde_json::to_string(&ClientMessage::Skip).unwrap();
+    Message::Text(json.into())
+}
+
+pub fn resume_msg(token: &str) -> Message {
+    let json = serde_json::to_string(&ClientMessage::Resume {
+        token: token.to_string(),
+    })
+    .unwrap();
+    Message::Text(json.into())
+}
+
 pub fn rematch_msg() -> Message {
     let json = serde_json::to_string(&ClientMessage::RequestRematch).unwrap();
     Message::Text(json.into())
 }
 
+pub fn sync_msg() -> Message {
+    let json = serde_json::to_string(&ClientMessage::Sync).unwrap();
+    Message::Text(json.into())
+}
+
+pub fn pause_msg() -> Message {
+    let json = serde_json::to_string(&ClientMessage::Pause).unwrap();
+    Message::Text(json.into())
+}
+
+pub fn unpause_msg() -> Message {
+    let json = serde_json::to_string(&ClientMessage::Unpause).unwrap();
+    Message::Text(json.into())
+}
+
+pub fn offer_draw_msg() -> Message {
+    let json = serde_json::to_string(&ClientMessage::OfferDraw).unwrap();
+    Message::Text(json.into())
+}
+
+pub fn accept_draw_msg() -> Message {
+    let json = serde_json::to_string(&ClientMessage::AcceptDraw).unwrap();
+    Message::Text(json.into())
+}
+
+/// Receive the next server message, skipping heartbeat pings
 pub async fn recv(ws: &mut WsStream) -> ServerMessage {
-    let msg = ws.next().await.unwrap().unwrap();
-    serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    loop {
+        let msg = ws.next().await.unwrap().unwrap();
+        if msg.is_ping() || msg.is_pong() {
+            continue;
+        }
+        return serde_json::from_str(msg.to_text().unwrap()).unwrap();
+    }
 }
 
 /// Look up correct reading from seed data
@@ -0,0 +1,163 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use std::time::Duration;
+use yomitaisen::messages::ServerMessage;
+use yomitaisen::AppOptions;
+
+async fn start_match(server: &TestServer) -> (WsStream, WsStream, String) {
+    let mut ws1 = connect_matchmaking(server).await;
+    let mut ws2 = connect_matchmaking(server).await;
+
+    ws1.send(join_msg("user-1")).await.unwrap();
+    ws2.send(join_msg("user-2")).await.unwrap();
+
+    assert_eq!(recv(&mut ws1).await, ServerMessage::Waiting);
+    assert!(matches!(recv(&mut ws1).await, ServerMessage::GameStart { .. }));
+    let ServerMessage::RoundStart { kanji, round: 1, .. } = recv(&mut ws1).await else {
+        panic!("Expected RoundStart for round 1");
+    };
+    assert!(matches!(recv(&mut ws2).await, ServerMessage::GameStart { .. }));
+    assert!(matches!(recv(&mut ws2).await, ServerMessage::RoundStart { round: 1, .. }));
+
+    (ws1, ws2, kanji)
+}
+
+/// Read messages until `RoundStart` for the given round, returning everything before it
+async fn recv_until_round(ws: &mut WsStream, round: u32) -> (Vec<ServerMessage>, String) {
+    let mut seen = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match recv(ws).await {
+                ServerMessage::RoundStart { round: r, kanji, .. } if r == round => {
+                    return (seen, kanji);
+                }
+                msg => seen.push(msg),
+            }
+        }
+    })
+    .await
+    .expect("Timed out waiting for round start")
+}
+
+/// Collect whatever arrives within the given time
+async fn recv_for(ws: &mut WsStream, duration: Duration) -> Vec<ServerMessage> {
+    let mut seen = Vec::new();
+    let _ = tokio::time::timeout(duration, async {
+        loop {
+            seen.push(recv(ws).await);
+        }
+    })
+    .await;
+    seen
+}
+
+fn count<F: Fn(&ServerMessage) -> bool>(messages: &[ServerMessage], pred: F) -> usize {
+    messages.iter().filter(|m| pred(m)).count()
+}
+
+#[tokio::test]
+async fn simultaneous_answers_have_one_winner_and_one_stale() {
+    let server = spawn_test_server_with_options(AppOptions {
+        rate_limits: relaxed_rate_limits(),
+        ..AppOptions::default()
+    })
+    .await;
+    let (mut ws1, mut ws2, mut kanji) = start_match(&server).await;
+
+    const ROUNDS: u32 = 8;
+    let mut log1 = Vec::new();
+    let mut log2 = Vec::new();
+
+    for round in 1..=ROUNDS {
+        let reading = get_reading(&kanji);
+        let (sent1, sent2) = tokio::join!(
+            ws1.send(round_answer_msg(reading, round)),
+            ws2.send(round_answer_msg(reading, round)),
+        );
+        sent1.unwrap();
+        sent2.unwrap();
+
+        let ((seen1, next_kanji), (seen2, _)) = tokio::join!(
+            recv_until_round(&mut ws1, round + 1),
+            recv_until_round(&mut ws2, round + 1),
+        );
+        log1.extend(seen1);
+        log2.extend(seen2);
+        kanji = next_kanji;
+    }
+    // The last round's stale reply arrives after the next round has started
+    let (tail1, tail2) = tokio::join!(
+        recv_for(&mut ws1, Duration::from_millis(200)),
+        recv_for(&mut ws2, Duration::from_millis(200)),
+    );
+    log1.extend(tail1);
+    log2.extend(tail2);
+
+    let rounds = ROUNDS as usize;
+    for log in [&log1, &log2] {
+        assert_eq!(
+            count(log, |m| matches!(m, ServerMessage::RoundResult { winner: Some(_), .. })),
+            rounds
+        );
+        assert_eq!(count(log, |m| matches!(m, ServerMessage::WrongAnswer)), 0);
+    }
+
+    // Every round has exactly one loser, whose answer is reported stale for that round
+    let mut stale: Vec<u32> = log1
+        .iter()
+        .chain(&log2)
+        .filter_map(|m| match m {
+            ServerMessage::StaleAnswer { round } => Some(*round),
+            _ => None,
+        })
+        .collect();
+    stale.sort();
+    assert_eq!(stale, (1..=ROUNDS).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn answers_racing_the_timeout_never_leak_into_the_next_round() {
+    let server = spawn_test_server_with_options(AppOptions {
+        round_timeout: Some(Duration::from_millis(25)),
+        rate_limits: relaxed_rate_limits(),
+        ..AppOptions::default()
+    })
+    .await;
+    let (mut ws1, mut ws2, mut kanji) = start_match(&server).await;
+
+    // Timeouts keep the game moving while we answer, so stay well below the round limit
+    const ROUNDS: u32 = 8;
+    let mut log = Vec::new();
+
+    for round in 1..=ROUNDS {
+        // Answer a little before, around, or after the deadline
+        let delay = Duration::from_millis([0, 15, 25, 35][round as usize % 4]);
+        let reading = get_reading(&kanji);
+
+        let ((seen, next_kanji), _) = tokio::join!(
+            async {
+                tokio::time::sleep(delay).await;
+                ws1.send(round_answer_msg(reading, round)).await.unwrap();
+                recv_until_round(&mut ws1, round + 1).await
+            },
+            recv_until_round(&mut ws2, round + 1),
+        );
+        log.extend(seen);
+        kanji = next_kanji;
+    }
+    log.extend(recv_for(&mut ws1, Duration::from_millis(100)).await);
+
+    let results = count(&log, |m| matches!(m, ServerMessage::RoundResult { .. }));
+    let wins = count(&log, |m| {
+        matches!(m, ServerMessage::RoundResult { winner: Some(w), .. } if w == "user-1")
+    });
+    let stale = count(&log, |m| matches!(m, ServerMessage::StaleAnswer { .. }));
+
+    // One result per round (plus any timeouts after the last answered round),
+    // and every answer either won its own round or was reported stale
+    assert!(results >= ROUNDS as usize);
+    assert_eq!(wins + stale, ROUNDS as usize);
+    assert_eq!(count(&log, |m| matches!(m, ServerMessage::WrongAnswer)), 0);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use yomitaisen::messages::{GameEndReason, ServerMessage};
+use yomitaisen::{AllowedOrigins, AppOptions};
+
+/// Host and guest in a started ephemeral game
+async fn start_game(server: &TestServer) -> (WsStream, WsStream) {
+    let mut host = connect_ephemeral(server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut host).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut host).await, ServerMessage::WaitingForOpponent);
+
+    let mut guest = connect_ephemeral(server).await;
+    guest.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(recv(&mut host).await, ServerMessage::OpponentJoined { .. }));
+    assert!(matches!(recv(&mut host).await, ServerMessage::GameStart { .. }));
+    assert!(matches!(recv(&mut guest).await, ServerMessage::GameStart { .. }));
+    (host, guest)
+}
+
+#[tokio::test]
+async fn match_ends_at_configured_win_target() {
+    let server = spawn_test_server_with_options(AppOptions {
+        wins_needed: Some(2),
+        ..AppOptions::default()
+    })
+    .await;
+    let (mut host, mut guest) = start_game(&server).await;
+
+    for _ in 0..2 {
+        let ServerMessage::RoundStart { kanji, .. } = recv(&mut host).await else {
+            panic!("Expected RoundStart");
+        };
+        assert!(matches!(recv(&mut guest).await, ServerMessage::RoundStart { .. }));
+        host.send(answer_msg(get_reading(&kanji))).await.unwrap();
+        assert!(matches!(recv(&mut host).await, ServerMessage::RoundResult { .. }));
+        assert!(matches!(recv(&mut guest).await, ServerMessage::RoundResult { .. }));
+    }
+
+    assert_eq!(
+        recv(&mut host).await,
+        ServerMessage::GameEnd {
+            winner: Some("Alice".to_string()),
+            reason: GameEndReason::Score,
+        }
+    );
+}
+
+#[tokio::test]
+async fn match_ends_at_configured_round_limit() {
+    let server = spawn_test_server_with_options(AppOptions {
+        max_rounds: Some(1),
+        ..AppOptions::default()
+    })
+    .await;
+    let (mut host, mut guest) = start_game(&server).await;
+
+    assert!(matches!(recv(&mut host).await, ServerMessage::RoundStart { round: 1, .. }));
+    assert!(matches!(recv(&mut guest).await, ServerMessage::RoundStart { round: 1, .. }));
+    host.send(skip_msg()).await.unwrap();
+    guest.send(skip_msg()).await.unwrap();
+
+    loop {
+        match recv(&mut host).await {
+            ServerMessage::GameEnd { winner, reason } => {
+                assert_eq!(winner, None);
+                assert_eq!(reason, GameEndReason::MaxRounds);
+                break;
+            }
+            ServerMessage::RoundStart { .. } => panic!("Round limit not applied"),
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn cors_allows_configured_origins() {
+    let server = spawn_test_server_with_options(AppOptions {
+        allowed_origins: AllowedOrigins::parse(&["http://localhost:5173"]).unwrap(),
+        ..AppOptions::default()
+    })
+    .await;
+    let client = reqwest::Client::new();
+
+    let allowed = client
+        .get(server.http_url("/health"))
+        .header("Origin", "http://localhost:5173")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        allowed.headers()["access-control-allow-origin"],
+        "http://localhost:5173"
+    );
+
+    let other = client
+        .get(server.http_url("/health"))
+        .header("Origin", "https://yomi.alsvik.cloud")
+        .send()
+        .await
+        .unwrap();
+    assert!(!other.headers().contains_key("access-control-allow-origin"));
+}
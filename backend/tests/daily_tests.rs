@@ -0,0 +1,158 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use yomitaisen::messages::{ErrorCode, ServerMessage};
+
+/// Start the daily challenge and return its number of rounds
+async fn start_daily(ws: &mut WsStream, player_name: &str) -> u32 {
+    ws.send(start_daily_msg(player_name)).await.unwrap();
+    match recv(ws).await {
+        ServerMessage::DailyStart { date, rounds, .. } => {
+            assert_eq!(date.len(), 10); // YYYY-MM-DD
+            rounds
+        }
+        other => panic!("Expected DailyStart, got {:?}", other),
+    }
+}
+
+/// Play every round, answering correctly or skipping. Returns the kanji shown.
+async fn play_daily(ws: &mut WsStream, player_name: &str, answer: bool) -> Vec<String> {
+    let rounds = start_daily(ws, player_name).await;
+    assert_eq!(rounds, 20);
+
+    let mut kanji_seen = Vec::new();
+    for round in 1..=rounds {
+        let ServerMessage::RoundStart { kanji, round: r, .. } = recv(ws).await else {
+            panic!("Expected RoundStart");
+        };
+        assert_eq!(r, round);
+
+        if answer {
+            ws.send(round_answer_msg(get_reading(&kanji), round)).await.unwrap();
+        } else {
+            ws.send(skip_msg()).await.unwrap();
+        }
+        let winner = match recv(ws).await {
+            ServerMessage::RoundResult { winner, .. } => winner,
+            other => panic!("Expected RoundResult, got {:?}", other),
+        };
+        assert_eq!(winner.is_some(), answer);
+        kanji_seen.push(kanji);
+    }
+    kanji_seen
+}
+
+#[tokio::test]
+async fn daily_challenge_scores_and_ranks_players() {
+    let server = spawn_test_server_with_rate_limits(relaxed_rate_limits()).await;
+
+    let mut alice = connect_daily(&server).await;
+    play_daily(&mut alice, "Alice", true).await;
+    let ServerMessage::DailyEnd { correct, rank, .. } = recv(&mut alice).await else {
+        panic!("Expected DailyEnd");
+    };
+    assert_eq!((correct, rank), (20, 1));
+
+    let mut bob = connect_daily(&server).await;
+    play_daily(&mut bob, "Bob", false).await;
+    let ServerMessage::DailyEnd { correct, rank, .. } = recv(&mut bob).await else {
+        panic!("Expected DailyEnd");
+    };
+    assert_eq!((correct, rank), (0, 2));
+
+    let response = reqwest::get(&server.http_url("/daily/leaderboard")).await.unwrap();
+    assert!(response.status().is_success());
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["rounds"], 20);
+    let entries = body["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["player_name"], "Alice");
+    assert_eq!(entries[0]["rank"], 1);
+    assert_eq!(entries[0]["correct"], 20);
+    assert_eq!(entries[1]["player_name"], "Bob");
+    assert_eq!(entries[1]["rank"], 2);
+}
+
+#[tokio::test]
+async fn everyone_gets_the_same_words() {
+    let server = spawn_test_server_with_rate_limits(relaxed_rate_limits()).await;
+    let mut alice = connect_daily(&server).await;
+    let mut bob = connect_daily(&server).await;
+
+    let (alice_words, bob_words) = tokio::join!(
+        play_daily(&mut alice, "Alice", true),
+        play_daily(&mut bob, "Bob", false),
+    );
+
+    assert_eq!(alice_words, bob_words);
+}
+
+#[tokio::test]
+async fn wrong_answer_can_be_retried() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_daily(&server).await;
+    start_daily(&mut ws, "Alice").await;
+
+    let ServerMessage::RoundStart { kanji, .. } = recv(&mut ws).await else {
+        panic!("Expected RoundStart");
+    };
+    ws.send(answer_msg("まちがい")).await.unwrap();
+    assert_eq!(recv(&mut ws).await, ServerMessage::WrongAnswer);
+
+    ws.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::RoundResult { winner: Some(w), .. } if w == "Alice"
+    ));
+}
+
+#[tokio::test]
+async fn one_attempt_per_day() {
+    let server = spawn_test_server().await;
+
+    // Leaving mid-run still uses up the attempt
+    let mut ws = connect_daily(&server).await;
+    start_daily(&mut ws, "Alice").await;
+    assert!(matches!(recv(&mut ws).await, ServerMessage::RoundStart { round: 1, .. }));
+    ws.close(None).await.unwrap();
+
+    let mut retry = connect_daily(&server).await;
+    retry.send(start_daily_msg("Alice")).await.unwrap();
+    assert!(matches!(
+        recv(&mut retry).await,
+        ServerMessage::Error { code: ErrorCode::AlreadyPlayed, .. }
+    ));
+}
+
+#[tokio::test]
+async fn game_messages_are_rejected_on_daily_endpoint() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_daily(&server).await;
+
+    ws.send(create_game_msg("Alice")).await.unwrap();
+
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::WrongEndpoint, .. }
+    ));
+}
+
+#[tokio::test]
+async fn leaderboard_rejects_malformed_date() {
+    let server = spawn_test_server().await;
+
+    let response = reqwest::get(&server.http_url("/daily/leaderboard?date=2024-02-30"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let response = reqwest::get(&server.http_url("/daily/leaderboard?date=2024-02-29"))
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["date"], "2024-02-29");
+    assert_eq!(body["entries"], serde_json::json!([]));
+}
@@ -1,4 +1,11 @@
 use sqlx::SqlitePool;
+use yomitaisen::{
+    DailyRepository, DailyResult, FrequencyBand, GameEvent, GameLog, GameLogRepository,
+    GhostChallenge, GhostRepository, GhostResult, MatchRecord, MatchRepository, RoundRecord,
+    RunRecord, SnapshotRepository, TimedEvent, WordRepository,
+};
+use yomitaisen::messages::GameEndReason;
+use yomitaisen::snapshots::{GameProgress, GameSnapshot, ModeSnapshot, PendingSnapshot};
 
 #[sqlx::test]
 async fn migrations_run_successfully(pool: SqlitePool) {
@@ -27,3 +34,196 @@ async fn can_lookup_word_by_kanji(pool: SqlitePool) {
 
     assert_eq!(reading.0, "にほん");
 }
+
+#[sqlx::test]
+async fn word_index_loads_seed_words(pool: SqlitePool) {
+    let words = WordRepository::load(pool).await.unwrap();
+
+    assert_eq!(words.len(), 10);
+    assert_eq!(words.band_len(FrequencyBand::Common), 10);
+    assert_eq!(words.band_len(FrequencyBand::Advanced), 0);
+
+    assert!(words.is_valid_reading("日本", "にほん"));
+    assert!(!words.is_valid_reading("日本", "にっぽん"));
+    assert_eq!(words.get_readings_for_kanji("学校"), vec!["がっこう".to_string()]);
+
+    let word = words.get_random_in_band(FrequencyBand::Common).unwrap();
+    assert!(words.is_valid_reading(&word.kanji, &word.reading));
+    assert!(words.get_random_in_band(FrequencyBand::Advanced).is_none());
+
+    let first = words.get_by_id(1).unwrap();
+    assert_eq!((first.kanji.as_str(), first.reading.as_str()), ("日本", "にほん"));
+}
+
+#[sqlx::test]
+async fn word_index_reload_picks_up_new_words(pool: SqlitePool) {
+    let words = WordRepository::load(pool.clone()).await.unwrap();
+    assert!(!words.is_valid_reading("日本", "にっぽん"));
+
+    sqlx::query("INSERT INTO words (kanji, reading, frequency_rank) VALUES ('日本', 'にっぽん', 50000)")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Unchanged until reloaded
+    assert!(!words.is_valid_reading("日本", "にっぽん"));
+
+    assert_eq!(words.reload().await.unwrap(), 11);
+    assert!(words.is_valid_reading("日本", "にっぽん"));
+    assert_eq!(words.get_readings_for_kanji("日本").len(), 2);
+    assert_eq!(words.band_len(FrequencyBand::Advanced), 1);
+}
+
+#[sqlx::test]
+async fn match_records_round_trip(pool: SqlitePool) {
+    let matches = MatchRepository::new(pool);
+    let record = MatchRecord {
+        game_id: "abc234".to_string(),
+        mode: "ephemeral".to_string(),
+        player1: "Alice".to_string(),
+        player2: "Bob".to_string(),
+        seed: (1 << 53) - 1,
+        player1_score: 10,
+        player2_score: 4,
+        winner: Some("Alice".to_string()),
+        rounds: 14,
+        reason: GameEndReason::Score,
+    };
+
+    matches.record(&record).await.unwrap();
+
+    assert_eq!(matches.for_game("abc234").await.unwrap(), vec![record]);
+    assert!(matches.for_game("zzz999").await.unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn daily_results_rank_by_correct_then_time(pool: SqlitePool) {
+    let daily = DailyRepository::new(pool);
+    let result = |player_name: &str, correct, time_ms| DailyResult {
+        date: "2024-06-01".to_string(),
+        player_name: player_name.to_string(),
+        correct,
+        time_ms,
+    };
+
+    for r in [result("slow", 18, 90_000), result("fast", 18, 40_000), result("sloppy", 12, 20_000)] {
+        daily.record(&r).await.unwrap();
+    }
+    // One result per player per day
+    assert!(daily.record(&result("fast", 20, 1)).await.is_err());
+    assert!(daily.has_played("2024-06-01", "fast").await.unwrap());
+    assert!(!daily.has_played("2024-06-02", "fast").await.unwrap());
+
+    let names: Vec<String> = daily
+        .leaderboard("2024-06-01", 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.player_name)
+        .collect();
+    assert_eq!(names, ["fast", "slow", "sloppy"]);
+    assert_eq!(daily.rank(&result("slow", 18, 90_000)).await.unwrap(), 2);
+}
+
+#[sqlx::test]
+async fn ghost_challenges_store_the_recorded_run(pool: SqlitePool) {
+    let ghosts = GhostRepository::new(pool);
+    let mut challenge = GhostChallenge {
+        id: "abc234".to_string(),
+        creator: "Alice".to_string(),
+        seed: 42,
+        rounds: 3,
+        ghost: RunRecord::default(),
+    };
+    for (correct, time_ms) in [(true, 1500), (false, 15000), (true, 800)] {
+        challenge.ghost.rounds.push(RoundRecord { correct, time_ms });
+    }
+
+    assert!(!ghosts.exists("abc234").await.unwrap());
+    ghosts.create(&challenge).await.unwrap();
+    assert!(ghosts.exists("abc234").await.unwrap());
+    assert_eq!(ghosts.get("abc234").await.unwrap(), Some(challenge));
+    assert_eq!(ghosts.get("zzz999").await.unwrap(), None);
+
+    let result = GhostResult {
+        challenge_id: "abc234".to_string(),
+        challenger: "Bob".to_string(),
+        challenger_score: 1,
+        creator_score: 2,
+        winner: Some("Alice".to_string()),
+    };
+    ghosts.record_result(&result).await.unwrap();
+    assert_eq!(ghosts.results("abc234").await.unwrap(), vec![result]);
+}
+
+#[sqlx::test]
+async fn game_logs_only_grow(pool: SqlitePool) {
+    let logs = GameLogRepository::new(pool);
+    let event = |at_ms, player: &str| TimedEvent {
+        at_ms,
+        event: GameEvent::SkipVote {
+            player: player.to_string(),
+        },
+    };
+    let mut log = GameLog {
+        id: "replay-1".to_string(),
+        game_id: "abc234".to_string(),
+        mode: "ephemeral".to_string(),
+        player1: "Alice".to_string(),
+        player2: "Bob".to_string(),
+        events: vec![event(0, "Alice"), event(1200, "Bob")],
+    };
+    logs.save(&log).await.unwrap();
+
+    // A stale save finishing late doesn't truncate the log
+    let stale = GameLog {
+        events: vec![event(0, "Alice")],
+        ..log.clone()
+    };
+    logs.save(&stale).await.unwrap();
+    assert_eq!(logs.get("replay-1").await.unwrap(), Some(log.clone()));
+
+    log.events.push(event(3000, "Alice"));
+    logs.save(&log).await.unwrap();
+    assert_eq!(logs.get("replay-1").await.unwrap(), Some(log));
+    assert_eq!(logs.get("replay-2").await.unwrap(), None);
+
+    assert_eq!(logs.list(Some("abc234"), 10).await.unwrap().len(), 1);
+    assert!(logs.list(Some("zzz999"), 10).await.unwrap().is_empty());
+    assert_eq!(logs.list(None, 10).await.unwrap()[0].id, "replay-1");
+}
+
+#[sqlx::test]
+async fn snapshots_replace_the_previous_save(pool: SqlitePool) {
+    let snapshots = SnapshotRepository::new(pool);
+    let game = GameSnapshot {
+        game_id: "abc234".to_string(),
+        player1: "Alice".to_string(),
+        player2: "Bob".to_string(),
+        player1_token: "t1".to_string(),
+        player2_token: "t2".to_string(),
+        progress: GameProgress {
+            scores: (3, 1),
+            seed: 42,
+            sequence_state: 1234,
+            round: None,
+        },
+    };
+    let pending = PendingSnapshot {
+        game_id: "xyz789".to_string(),
+        host: "Carol".to_string(),
+        host_token: "t3".to_string(),
+        seed: 7,
+        age_ms: 65_000,
+    };
+    let saved = ModeSnapshot {
+        games: vec![game],
+        pending: vec![pending],
+    };
+    snapshots.save("ephemeral", &saved).await.unwrap();
+    assert_eq!(snapshots.load("ephemeral").await.unwrap(), saved);
+    assert_eq!(snapshots.load("matchmaking").await.unwrap(), ModeSnapshot::default());
+
+    snapshots.save("ephemeral", &ModeSnapshot::default()).await.unwrap();
+    assert_eq!(snapshots.load("ephemeral").await.unwrap(), ModeSnapshot::default());
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use std::time::Duration;
+use yomitaisen::AppOptions;
+use yomitaisen::messages::{ErrorCode, ServerMessage};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+async fn spawn_admin_server(drain_timeout: Duration) -> TestServer {
+    spawn_test_server_with_options(AppOptions {
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        drain_timeout: Some(drain_timeout),
+        ..AppOptions::default()
+    })
+    .await
+}
+
+async fn post_drain(server: &TestServer, token: Option<&str>) -> reqwest::StatusCode {
+    let mut request = reqwest::Client::new().post(server.http_url("/admin/drain"));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.unwrap().status()
+}
+
+/// Alice hosts and Bob joins; returns both once the first round has started
+async fn start_game(server: &TestServer) -> (WsStream, WsStream) {
+    let mut alice = connect_ephemeral(server).await;
+    alice.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut alice).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut alice).await, ServerMessage::WaitingForOpponent);
+
+    let mut bob = connect_ephemeral(server).await;
+    bob.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut bob).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::OpponentJoined { .. }
+    ));
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::GameStart { .. }
+    ));
+    for ws in [&mut alice, &mut bob] {
+        assert!(matches!(recv(ws).await, ServerMessage::RoundStart { .. }));
+    }
+    (alice, bob)
+}
+
+#[tokio::test]
+async fn admin_drain_requires_the_token() {
+    let server = spawn_admin_server(Duration::from_secs(60)).await;
+
+    assert_eq!(
+        post_drain(&server, None).await,
+        reqwest::StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        post_drain(&server, Some("wrong")).await,
+        reqwest::StatusCode::UNAUTHORIZED
+    );
+    assert!(!server.drain.is_draining());
+
+    assert_eq!(
+        post_drain(&server, Some(ADMIN_TOKEN)).await,
+        reqwest::StatusCode::ACCEPTED
+    );
+    assert!(server.drain.is_draining());
+}
+
+#[tokio::test]
+async fn admin_drain_is_disabled_without_a_token() {
+    let server = spawn_test_server().await;
+    assert_eq!(
+        post_drain(&server, Some("anything")).await,
+        reqwest::StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn draining_warns_players_and_refuses_new_games() {
+    let server = spawn_admin_server(Duration::from_secs(60)).await;
+    let (mut alice, mut bob) = start_game(&server).await;
+
+    let mut carol = connect_ephemeral(&server).await;
+    carol.send(create_game_msg("Carol")).await.unwrap();
+    let ServerMessage::GameCreated { .. } = recv(&mut carol).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut carol).await, ServerMessage::WaitingForOpponent);
+
+    assert!(server.drain.start());
+    assert!(!server.drain.start(), "Draining only starts once");
+    for ws in [&mut alice, &mut bob, &mut carol] {
+        assert_eq!(
+            recv(ws).await,
+            ServerMessage::ServerDraining { seconds_left: 60 }
+        );
+    }
+
+    // The lobby hides Carol's game, and nobody can start another
+    let response = reqwest::get(&server.http_url("/lobby")).await.unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["games"], serde_json::json!([]));
+
+    let mut dave = connect_ephemeral(&server).await;
+    dave.send(create_game_msg("Dave")).await.unwrap();
+    assert!(matches!(
+        recv(&mut dave).await,
+        ServerMessage::Error {
+            code: ErrorCode::Maintenance,
+            ..
+        }
+    ));
+
+    // The running game carries on
+    alice.send(skip_msg()).await.unwrap();
+    assert_eq!(recv(&mut alice).await, ServerMessage::SkipWaiting);
+}
+
+#[tokio::test]
+async fn drain_finishes_when_the_last_game_ends() {
+    let server = spawn_admin_server(Duration::from_secs(60)).await;
+    let (alice, _bob) = start_game(&server).await;
+
+    server.drain.start();
+    let finished = tokio::time::timeout(Duration::from_millis(300), server.drain.finished()).await;
+    assert!(finished.is_err(), "A game is still running");
+
+    drop(alice);
+    tokio::time::timeout(Duration::from_secs(5), server.drain.finished())
+        .await
+        .expect("Drain should finish once the game ends");
+}
+
+#[tokio::test]
+async fn drain_gives_up_at_the_deadline() {
+    let server = spawn_admin_server(Duration::from_millis(200)).await;
+    let _players = start_game(&server).await;
+
+    server.drain.start();
+    tokio::time::timeout(Duration::from_secs(5), server.drain.finished())
+        .await
+        .expect("Drain should stop waiting at the deadline");
+}
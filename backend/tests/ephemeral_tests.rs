@@ -2,7 +2,8 @@ mod common;
 
 use common::*;
 use futures_util::SinkExt;
-use yomitaisen::messages::ServerMessage;
+use tokio_tungstenite::tungstenite::Message;
+use yomitaisen::messages::{ErrorCode, GameEndReason, ServerMessage};
 
 #[tokio::test]
 async fn create_game_returns_game_id_and_waits() {
@@ -169,8 +170,8 @@ async fn rematch_starts_new_game_when_both_request() {
     }
 
     // Both receive GameEnd (host won)
-    assert!(matches!(recv(&mut host_ws).await, ServerMessage::GameEnd { winner: Some(_) }));
-    assert!(matches!(recv(&mut guest_ws).await, ServerMessage::GameEnd { winner: Some(_) }));
+    assert!(matches!(recv(&mut host_ws).await, ServerMessage::GameEnd { winner: Some(_), reason: GameEndReason::Score }));
+    assert!(matches!(recv(&mut guest_ws).await, ServerMessage::GameEnd { winner: Some(_), reason: GameEndReason::Score }));
 
     // Now both request rematch
     host_ws.send(rematch_msg()).await.unwrap();
@@ -187,3 +188,150 @@ async fn rematch_starts_new_game_when_both_request() {
     assert!(matches!(recv(&mut host_ws).await, ServerMessage::RoundStart { round: 1, .. }));
     assert!(matches!(recv(&mut guest_ws).await, ServerMessage::RoundStart { round: 1, .. }));
 }
+
+#[tokio::test]
+async fn create_game_rejects_invalid_name() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(create_game_msg("<script>")).await.unwrap();
+
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::InvalidName, .. }
+    ));
+}
+
+/// Host a game with the given seed, answer `rounds` rounds, and return the kanji shown
+async fn play_seeded_game(server: &TestServer, seed: u64, rounds: u32) -> Vec<String> {
+    let mut host_ws = connect_ephemeral(server).await;
+    host_ws.send(create_seeded_game_msg("Alice", seed)).await.unwrap();
+
+    let game_id = match recv(&mut host_ws).await {
+        ServerMessage::GameCreated { game_id } => game_id,
+        other => panic!("Expected GameCreated, got {:?}", other),
+    };
+    assert_eq!(recv(&mut host_ws).await, ServerMessage::WaitingForOpponent);
+
+    let mut guest_ws = connect_ephemeral(server).await;
+    guest_ws.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+
+    assert!(matches!(recv(&mut host_ws).await, ServerMessage::OpponentJoined { .. }));
+    assert_eq!(
+        recv(&mut host_ws).await,
+        ServerMessage::GameStart { opponent: "Bob".to_string(), seed }
+    );
+    assert_eq!(
+        recv(&mut guest_ws).await,
+        ServerMessage::GameStart { opponent: "Alice".to_string(), seed }
+    );
+
+    let mut kanji_seen = Vec::new();
+    for _ in 0..rounds {
+        let ServerMessage::RoundStart { kanji, .. } = recv(&mut host_ws).await else {
+            panic!("Expected RoundStart");
+        };
+        assert!(matches!(recv(&mut guest_ws).await, ServerMessage::RoundStart { .. }));
+
+        host_ws.send(answer_msg(get_reading(&kanji))).await.unwrap();
+        assert!(matches!(recv(&mut host_ws).await, ServerMessage::RoundResult { .. }));
+        assert!(matches!(recv(&mut guest_ws).await, ServerMessage::RoundResult { .. }));
+        kanji_seen.push(kanji);
+    }
+    kanji_seen
+}
+
+#[tokio::test]
+async fn same_seed_reproduces_the_same_rounds() {
+    let server = spawn_test_server().await;
+
+    let first = play_seeded_game(&server, 20240601, 6).await;
+    let second = play_seeded_game(&server, 20240601, 6).await;
+
+    assert_eq!(first, second);
+}
+
+#[tokio::test]
+async fn create_game_rejects_oversized_seed() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(create_seeded_game_msg("Alice", u64::MAX)).await.unwrap();
+
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::InvalidSeed, .. }
+    ));
+}
+
+#[tokio::test]
+async fn join_game_rejects_malformed_code() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(join_game_msg("abc10l", "Bob")).await.unwrap();
+
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::InvalidGameCode, .. }
+    ));
+}
+
+#[tokio::test]
+async fn overlong_answer_is_rejected() {
+    let server = spawn_test_server().await;
+
+    let mut host_ws = connect_ephemeral(&server).await;
+    host_ws.send(create_game_msg("Alice")).await.unwrap();
+
+    let game_id = match recv(&mut host_ws).await {
+        ServerMessage::GameCreated { game_id } => game_id,
+        other => panic!("Expected GameCreated, got {:?}", other),
+    };
+    assert_eq!(recv(&mut host_ws).await, ServerMessage::WaitingForOpponent);
+
+    let mut guest_ws = connect_ephemeral(&server).await;
+    guest_ws.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+
+    assert!(matches!(recv(&mut guest_ws).await, ServerMessage::GameStart { .. }));
+    assert!(matches!(recv(&mut guest_ws).await, ServerMessage::RoundStart { .. }));
+
+    guest_ws.send(answer_msg(&"あ".repeat(100))).await.unwrap();
+
+    assert!(matches!(
+        recv(&mut guest_ws).await,
+        ServerMessage::Error { code: ErrorCode::InvalidAnswer, .. }
+    ));
+}
+
+#[tokio::test]
+async fn malformed_message_returns_bad_request() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(Message::Text(r#"{"type": "create_game"}"#.into()))
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::BadRequest, .. }
+    ));
+}
+
+#[tokio::test]
+async fn answer_outside_game_echoes_request_id() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(Message::Text(
+        r#"{"type": "answer", "answer": "にほん", "request_id": "req-7"}"#.into(),
+    ))
+    .await
+    .unwrap();
+
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::NotInGame, request_id: Some(id), .. } if id == "req-7"
+    ));
+}
@@ -0,0 +1,275 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use std::time::Duration;
+use yomitaisen::messages::{ErrorCode, GameEndReason, ServerMessage};
+use yomitaisen::{AppOptions, ForfeitConfig};
+
+/// Address user-1 connects from
+const ADDRESS_1: &str = "198.51.100.1";
+/// Address user-2 connects from
+const ADDRESS_2: &str = "198.51.100.2";
+
+/// Every forfeit puts the player, and their address, in the cooldown
+fn strict_forfeits() -> ForfeitConfig {
+    ForfeitConfig {
+        free_forfeits: 0,
+        free_address_forfeits: 0,
+        ..ForfeitConfig::default()
+    }
+}
+
+/// Clients connect through a local proxy, so each can have its own address
+fn behind_proxy(options: AppOptions) -> AppOptions {
+    AppOptions {
+        trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+        ..options
+    }
+}
+
+async fn connect_from(server: &TestServer, address: &str) -> WsStream {
+    connect_forwarded(&server.matchmaking_url(), address).await
+}
+
+async fn expect_error(ws: &mut WsStream, expected: ErrorCode) {
+    match recv(ws).await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, expected),
+        other => panic!("Expected Error, got {:?}", other),
+    }
+}
+
+/// Read messages until round 1 starts, returning its kanji
+async fn until_round_start(ws: &mut WsStream) -> String {
+    loop {
+        if let ServerMessage::RoundStart { kanji, .. } = recv(ws).await {
+            return kanji;
+        }
+    }
+}
+
+/// user-1 and user-2 matched and looking at round 1
+async fn start_match(server: &TestServer, capabilities: &[&str]) -> (WsStream, WsStream, String) {
+    let mut ws1 = connect_from(server, ADDRESS_1).await;
+    let mut ws2 = connect_from(server, ADDRESS_2).await;
+    if !capabilities.is_empty() {
+        for ws in [&mut ws1, &mut ws2] {
+            ws.send(hello_msg(1, capabilities)).await.unwrap();
+            assert!(matches!(recv(ws).await, ServerMessage::Welcome { .. }));
+        }
+    }
+    ws1.send(join_msg("user-1")).await.unwrap();
+    assert_eq!(recv(&mut ws1).await, ServerMessage::Waiting);
+    ws2.send(join_msg("user-2")).await.unwrap();
+
+    let kanji = until_round_start(&mut ws1).await;
+    until_round_start(&mut ws2).await;
+    (ws1, ws2, kanji)
+}
+
+async fn expect_forfeit_win(ws: &mut WsStream, winner: &str) {
+    assert_eq!(recv(ws).await, ServerMessage::OpponentDisconnected);
+    assert_eq!(
+        recv(ws).await,
+        ServerMessage::GameEnd {
+            winner: Some(winner.to_string()),
+            reason: GameEndReason::Forfeit,
+        }
+    );
+}
+
+/// Winner and end reason of the matchmaking match, once it has been stored
+async fn recorded_result(pool: &sqlx::SqlitePool) -> (Option<String>, String) {
+    for _ in 0..50 {
+        let row: Option<(Option<String>, String)> =
+            sqlx::query_as("SELECT winner, end_reason FROM matches WHERE mode = 'matchmaking'")
+                .fetch_optional(pool)
+                .await
+                .unwrap();
+        if let Some(row) = row {
+            return row;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Match was never recorded");
+}
+
+#[tokio::test]
+async fn leaving_a_match_forfeits_it() {
+    let pool = test_pool().await;
+    let server = spawn_test_server_with_pool(pool.clone(), AppOptions::default()).await;
+    let (mut ws1, mut ws2, _) = start_match(&server, &[]).await;
+
+    ws2.close(None).await.unwrap();
+    expect_forfeit_win(&mut ws1, "user-1").await;
+
+    assert_eq!(
+        recorded_result(&pool).await,
+        (Some("user-1".to_string()), "forfeit".to_string())
+    );
+}
+
+#[tokio::test]
+async fn player_who_does_not_return_in_time_forfeits() {
+    let server = spawn_test_server_with_options(AppOptions {
+        reconnect_grace: Some(Duration::from_millis(200)),
+        ..AppOptions::default()
+    })
+    .await;
+    let (mut ws1, ws2, _) = start_match(&server, &["resume"]).await;
+
+    drop(ws2);
+    assert!(matches!(
+        recv(&mut ws1).await,
+        ServerMessage::OpponentConnectionChanged {
+            connected: false,
+            ..
+        }
+    ));
+    expect_forfeit_win(&mut ws1, "user-1").await;
+}
+
+#[tokio::test]
+async fn repeat_leavers_cannot_queue_for_a_while() {
+    let server = spawn_test_server_with_options(behind_proxy(AppOptions {
+        forfeits: strict_forfeits(),
+        ..AppOptions::default()
+    }))
+    .await;
+    let (mut ws1, mut ws2, _) = start_match(&server, &[]).await;
+
+    ws2.close(None).await.unwrap();
+    expect_forfeit_win(&mut ws1, "user-1").await;
+
+    let mut ws2 = connect_from(&server, ADDRESS_2).await;
+    ws2.send(join_msg("user-2")).await.unwrap();
+    expect_error(&mut ws2, ErrorCode::QueueCooldown).await;
+
+    // A new name doesn't help from the same address
+    ws2.send(join_msg("someone-else")).await.unwrap();
+    expect_error(&mut ws2, ErrorCode::QueueCooldown).await;
+
+    // The winner can queue straight away
+    ws1.send(join_msg("user-1")).await.unwrap();
+    assert_eq!(recv(&mut ws1).await, ServerMessage::Waiting);
+}
+
+#[tokio::test]
+async fn names_in_use_cannot_be_joined_under() {
+    let server = spawn_test_server_with_options(behind_proxy(AppOptions {
+        forfeits: strict_forfeits(),
+        ..AppOptions::default()
+    }))
+    .await;
+    let (mut ws1, mut ws2, kanji) = start_match(&server, &[]).await;
+
+    let mut impostor = connect_from(&server, "203.0.113.7").await;
+    impostor.send(join_msg("user-2")).await.unwrap();
+    expect_error(&mut impostor, ErrorCode::NameTaken).await;
+
+    // The impostor leaving doesn't forfeit user-2's match
+    impostor.close(None).await.unwrap();
+    ws2.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    for ws in [&mut ws1, &mut ws2] {
+        match recv(ws).await {
+            ServerMessage::RoundResult { winner, .. } => {
+                assert_eq!(winner, Some("user-2".to_string()))
+            }
+            other => panic!("Expected RoundResult, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn leaving_after_the_match_ended_is_not_a_forfeit() {
+    let server = spawn_test_server_with_options(AppOptions {
+        wins_needed: Some(1),
+        forfeits: strict_forfeits(),
+        ..AppOptions::default()
+    })
+    .await;
+    let (mut ws1, mut ws2, kanji) = start_match(&server, &[]).await;
+
+    ws1.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws1).await,
+        ServerMessage::RoundResult { .. }
+    ));
+    assert_eq!(
+        recv(&mut ws1).await,
+        ServerMessage::GameEnd {
+            winner: Some("user-1".to_string()),
+            reason: GameEndReason::Score,
+        }
+    );
+
+    ws2.close(None).await.unwrap();
+    assert_eq!(recv(&mut ws1).await, ServerMessage::OpponentDisconnected);
+
+    let mut ws2 = connect_from(&server, ADDRESS_2).await;
+    ws2.send(join_msg("user-2")).await.unwrap();
+    assert_eq!(recv(&mut ws2).await, ServerMessage::Waiting);
+}
+
+#[tokio::test]
+async fn players_can_agree_to_a_draw() {
+    let pool = test_pool().await;
+    let server = spawn_test_server_with_pool(pool.clone(), behind_proxy(AppOptions::default())).await;
+    let (mut ws1, mut ws2, _) = start_match(&server, &[]).await;
+
+    // Nothing to accept yet
+    ws2.send(accept_draw_msg()).await.unwrap();
+    expect_error(&mut ws2, ErrorCode::NoDrawOffer).await;
+
+    ws1.send(offer_draw_msg()).await.unwrap();
+    for ws in [&mut ws1, &mut ws2] {
+        assert_eq!(
+            recv(ws).await,
+            ServerMessage::DrawOffered {
+                player: "user-1".to_string()
+            }
+        );
+    }
+    ws2.send(sync_msg()).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws2).await,
+        ServerMessage::GameState {
+            you_offered_draw: false,
+            opponent_offered_draw: true,
+            ..
+        }
+    ));
+
+    ws2.send(accept_draw_msg()).await.unwrap();
+    for ws in [&mut ws1, &mut ws2] {
+        assert_eq!(
+            recv(ws).await,
+            ServerMessage::GameEnd {
+                winner: None,
+                reason: GameEndReason::DrawAgreed,
+            }
+        );
+    }
+    assert_eq!(recorded_result(&pool).await, (None, "draw_agreed".to_string()));
+
+    // The match is over, so there is nothing left to draw
+    ws1.send(offer_draw_msg()).await.unwrap();
+    expect_error(&mut ws1, ErrorCode::DrawNotAllowed).await;
+}
+
+#[tokio::test]
+async fn draw_offers_lapse_when_the_round_ends() {
+    let server = spawn_test_server_with_options(behind_proxy(AppOptions::default())).await;
+    let (mut ws1, mut ws2, kanji) = start_match(&server, &[]).await;
+
+    ws1.send(offer_draw_msg()).await.unwrap();
+    assert!(matches!(recv(&mut ws1).await, ServerMessage::DrawOffered { .. }));
+    assert!(matches!(recv(&mut ws2).await, ServerMessage::DrawOffered { .. }));
+
+    ws1.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    assert!(matches!(recv(&mut ws2).await, ServerMessage::RoundResult { .. }));
+    assert!(matches!(recv(&mut ws2).await, ServerMessage::RoundStart { round: 2, .. }));
+
+    ws2.send(accept_draw_msg()).await.unwrap();
+    expect_error(&mut ws2, ErrorCode::NoDrawOffer).await;
+}
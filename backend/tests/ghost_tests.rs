@@ -0,0 +1,139 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use yomitaisen::messages::{ErrorCode, GameEndReason, ServerMessage};
+
+/// Play every round of a run: answer correctly when `answer(round)` says so,
+/// otherwise skip. Returns the kanji shown and each round's winner.
+async fn play_run(
+    ws: &mut WsStream,
+    rounds: u32,
+    answer: impl Fn(u32) -> bool,
+) -> (Vec<String>, Vec<Option<String>>) {
+    let mut kanji_seen = Vec::new();
+    let mut winners = Vec::new();
+    for round in 1..=rounds {
+        let ServerMessage::RoundStart { kanji, round: r, .. } = recv(ws).await else {
+            panic!("Expected RoundStart");
+        };
+        assert_eq!(r, round);
+
+        if answer(round) {
+            ws.send(round_answer_msg(get_reading(&kanji), round)).await.unwrap();
+        } else {
+            ws.send(skip_msg()).await.unwrap();
+        }
+        match recv(ws).await {
+            ServerMessage::RoundResult { winner, .. } => winners.push(winner),
+            other => panic!("Expected RoundResult, got {:?}", other),
+        }
+        kanji_seen.push(kanji);
+    }
+    (kanji_seen, winners)
+}
+
+/// Record a challenge as Alice and return its code and the words shown
+async fn record_challenge(server: &TestServer, answer: impl Fn(u32) -> bool) -> (String, Vec<String>) {
+    let mut ws = connect_ghost(server).await;
+    ws.send(create_challenge_msg("Alice")).await.unwrap();
+
+    let rounds = match recv(&mut ws).await {
+        ServerMessage::ChallengeStart { rounds, ghost: None, .. } => rounds,
+        other => panic!("Expected ChallengeStart, got {:?}", other),
+    };
+    let (kanji, _) = play_run(&mut ws, rounds, answer).await;
+
+    match recv(&mut ws).await {
+        ServerMessage::ChallengeCreated { challenge_id, .. } => (challenge_id, kanji),
+        other => panic!("Expected ChallengeCreated, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn ghost_wins_the_rounds_its_creator_answered() {
+    let server = spawn_test_server_with_rate_limits(relaxed_rate_limits()).await;
+    let (challenge_id, alice_words) = record_challenge(&server, |round| round % 2 == 0).await;
+
+    let mut bob = connect_ghost(&server).await;
+    bob.send(accept_challenge_msg(&challenge_id, "Bob")).await.unwrap();
+    let rounds = match recv(&mut bob).await {
+        ServerMessage::ChallengeStart { rounds, ghost: Some(ghost), .. } => {
+            assert_eq!(ghost, "Alice");
+            rounds
+        }
+        other => panic!("Expected ChallengeStart, got {:?}", other),
+    };
+
+    // Same words as the recording; giving up hands the round to the ghost if it answered
+    let (bob_words, winners) = play_run(&mut bob, rounds, |_| false).await;
+    assert_eq!(bob_words, alice_words);
+    for (i, winner) in winners.iter().enumerate() {
+        let expected = (i % 2 == 1).then(|| "Alice".to_string());
+        assert_eq!(*winner, expected, "round {}", i + 1);
+    }
+    assert_eq!(
+        recv(&mut bob).await,
+        ServerMessage::GameEnd {
+            winner: Some("Alice".to_string()),
+            reason: GameEndReason::MaxRounds,
+        }
+    );
+
+    // The creator can look up the result later
+    let response = reqwest::get(&server.http_url(&format!("/ghost/{}", challenge_id)))
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["creator"], "Alice");
+    assert_eq!(body["creator_correct"], rounds / 2);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["challenger"], "Bob");
+    assert_eq!(results[0]["challenger_score"], 0);
+    assert_eq!(results[0]["creator_score"], rounds / 2);
+    assert_eq!(results[0]["winner"], "Alice");
+}
+
+#[tokio::test]
+async fn challenger_beats_a_ghost_that_never_answered() {
+    let server = spawn_test_server_with_rate_limits(relaxed_rate_limits()).await;
+    let (challenge_id, _) = record_challenge(&server, |_| false).await;
+
+    let mut bob = connect_ghost(&server).await;
+    bob.send(accept_challenge_msg(&challenge_id, "Bob")).await.unwrap();
+    let ServerMessage::ChallengeStart { rounds, .. } = recv(&mut bob).await else {
+        panic!("Expected ChallengeStart");
+    };
+
+    let (_, winners) = play_run(&mut bob, rounds, |_| true).await;
+    assert!(winners.iter().all(|w| w.as_deref() == Some("Bob")));
+    assert_eq!(
+        recv(&mut bob).await,
+        ServerMessage::GameEnd {
+            winner: Some("Bob".to_string()),
+            reason: GameEndReason::MaxRounds,
+        }
+    );
+}
+
+#[tokio::test]
+async fn unknown_challenge_is_not_found() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ghost(&server).await;
+
+    ws.send(accept_challenge_msg("xyz999", "Bob")).await.unwrap();
+    assert_eq!(recv(&mut ws).await, ServerMessage::GameNotFound);
+
+    ws.send(accept_challenge_msg("abc10l", "Bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::InvalidGameCode, .. }
+    ));
+
+    let response = reqwest::get(&server.http_url("/ghost/xyz999")).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    let response = reqwest::get(&server.http_url("/ghost/abc10l")).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
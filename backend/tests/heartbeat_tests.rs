@@ -0,0 +1,132 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use std::time::Duration;
+use yomitaisen::messages::ServerMessage;
+use yomitaisen::{AppOptions, HeartbeatConfig};
+
+async fn spawn_with_heartbeat(interval_ms: u64, timeout_ms: u64) -> TestServer {
+    spawn_test_server_with_options(AppOptions {
+        heartbeat: HeartbeatConfig {
+            interval: Duration::from_millis(interval_ms),
+            timeout: Duration::from_millis(timeout_ms),
+        },
+        ..AppOptions::default()
+    })
+    .await
+}
+
+/// Keep reading (and thereby answering pings) until a message matches
+async fn recv_until<F>(ws: &mut WsStream, pred: F) -> ServerMessage
+where
+    F: Fn(&ServerMessage) -> bool,
+{
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let msg = recv(ws).await;
+            if pred(&msg) {
+                return msg;
+            }
+        }
+    })
+    .await
+    .expect("Timed out waiting for message")
+}
+
+/// Keep reading (and answering pings) for a while, discarding messages
+async fn drain_for(ws: &mut WsStream, duration: Duration) {
+    let _ = tokio::time::timeout(duration, async {
+        loop {
+            recv(ws).await;
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn silent_client_is_disconnected() {
+    let server = spawn_with_heartbeat(50, 300).await;
+
+    let mut ws1 = connect_matchmaking(&server).await;
+    let mut ws2 = connect_matchmaking(&server).await;
+
+    ws1.send(join_msg("user-1")).await.unwrap();
+    ws2.send(join_msg("user-2")).await.unwrap();
+
+    recv_until(&mut ws1, |m| matches!(m, ServerMessage::RoundStart { .. })).await;
+
+    // ws2 never reads again, so it never answers pings
+    recv_until(&mut ws1, |m| matches!(m, ServerMessage::OpponentDisconnected)).await;
+}
+
+#[tokio::test]
+async fn client_that_talks_but_never_answers_pings_is_disconnected() {
+    let server = spawn_with_heartbeat(50, 300).await;
+
+    let mut ws1 = connect_matchmaking(&server).await;
+    let mut ws2 = connect_matchmaking(&server).await;
+
+    ws1.send(join_msg("user-1")).await.unwrap();
+    ws2.send(join_msg("user-2")).await.unwrap();
+
+    recv_until(&mut ws1, |m| matches!(m, ServerMessage::RoundStart { .. })).await;
+
+    // ws2 keeps sending but never reads, so its pings go unanswered
+    let chatter = tokio::spawn(async move {
+        while ws2.send(sync_msg()).await.is_ok() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    });
+    tokio::time::timeout(
+        Duration::from_secs(1),
+        recv_until(&mut ws1, |m| matches!(m, ServerMessage::OpponentDisconnected)),
+    )
+    .await
+    .expect("Client that sends messages was kept despite unanswered pings");
+    chatter.abort();
+}
+
+#[tokio::test]
+async fn responsive_client_stays_connected_and_reports_latency() {
+    let server = spawn_with_heartbeat(50, 300).await;
+
+    let mut ws = connect_ephemeral(&server).await;
+    ws.send(hello_msg(1, &["latency"])).await.unwrap();
+    assert!(matches!(recv(&mut ws).await, ServerMessage::Welcome { .. }));
+    ws.send(create_game_msg("Alice")).await.unwrap();
+
+    let game_id = match recv(&mut ws).await {
+        ServerMessage::GameCreated { game_id } => game_id,
+        other => panic!("Expected GameCreated, got {:?}", other),
+    };
+    assert_eq!(recv(&mut ws).await, ServerMessage::WaitingForOpponent);
+
+    let mut guest_ws = connect_ephemeral(&server).await;
+    guest_ws.send(hello_msg(1, &["latency"])).await.unwrap();
+    assert!(matches!(recv(&mut guest_ws).await, ServerMessage::Welcome { .. }));
+    guest_ws.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+
+    // Both keep reading well past the pong timeout
+    let (host_latency, guest_latency) = tokio::join!(
+        recv_until(&mut ws, |m| matches!(
+            m,
+            ServerMessage::PlayerLatency { player, .. } if player == "Alice"
+        )),
+        recv_until(&mut guest_ws, |m| matches!(
+            m,
+            ServerMessage::PlayerLatency { player, .. } if player == "Bob"
+        )),
+    );
+    assert!(matches!(host_latency, ServerMessage::PlayerLatency { .. }));
+    assert!(matches!(guest_latency, ServerMessage::PlayerLatency { .. }));
+
+    tokio::join!(
+        drain_for(&mut ws, Duration::from_millis(600)),
+        drain_for(&mut guest_ws, Duration::from_millis(600)),
+    );
+
+    // Still in the game: an answer is judged rather than ignored
+    guest_ws.send(answer_msg("まちがい")).await.unwrap();
+    recv_until(&mut guest_ws, |m| matches!(m, ServerMessage::WrongAnswer)).await;
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use yomitaisen::AppOptions;
+use yomitaisen::messages::{ErrorCode, ServerMessage};
+
+/// Complete the handshake, so the server is counting the connection
+async fn greet(ws: &mut WsStream) {
+    ws.send(hello_msg(1, &[])).await.unwrap();
+    assert!(matches!(recv(ws).await, ServerMessage::Welcome { .. }));
+}
+
+/// The connection is refused with `code` and then closed
+async fn assert_refused(ws: &mut WsStream, code: ErrorCode) {
+    assert!(matches!(
+        recv(ws).await,
+        ServerMessage::Error { code: c, .. } if c == code
+    ));
+    assert!(matches!(
+        ws.next().await,
+        None | Some(Ok(tokio_tungstenite::tungstenite::Message::Close(_))) | Some(Err(_))
+    ));
+}
+
+#[tokio::test]
+async fn connections_per_address_are_capped() {
+    let server = spawn_test_server_with_options(AppOptions {
+        max_connections_per_ip: Some(2),
+        ..AppOptions::default()
+    })
+    .await;
+
+    let mut first = connect_ephemeral(&server).await;
+    greet(&mut first).await;
+    let mut second = connect_daily(&server).await;
+    greet(&mut second).await;
+
+    let mut third = connect_matchmaking(&server).await;
+    assert_refused(&mut third, ErrorCode::TooManyConnections).await;
+
+    // Closing one frees its slot once the server has noticed
+    first.close(None).await.unwrap();
+    drop(first);
+    for _ in 0..50 {
+        let mut again = connect_matchmaking(&server).await;
+        again.send(hello_msg(1, &[])).await.unwrap();
+        if matches!(recv(&mut again).await, ServerMessage::Welcome { .. }) {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("Slot was never freed");
+}
+
+#[tokio::test]
+async fn forwarded_for_is_trusted_only_from_configured_proxies() {
+    let server = spawn_test_server_with_options(AppOptions {
+        max_connections_per_ip: Some(1),
+        trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+        ..AppOptions::default()
+    })
+    .await;
+
+    // Behind the proxy, different clients each get their own allowance
+    let mut alice = connect_forwarded(&server.ephemeral_url(), "198.51.100.1").await;
+    greet(&mut alice).await;
+    let mut bob = connect_forwarded(&server.ephemeral_url(), "198.51.100.2").await;
+    greet(&mut bob).await;
+
+    // A client can't dodge the limit by prepending a fake address
+    let mut alice_again =
+        connect_forwarded(&server.ephemeral_url(), "203.0.113.50, 198.51.100.1").await;
+    assert_refused(&mut alice_again, ErrorCode::TooManyConnections).await;
+}
+
+#[tokio::test]
+async fn forwarded_for_is_ignored_without_trusted_proxies() {
+    let server = spawn_test_server_with_options(AppOptions {
+        max_connections_per_ip: Some(1),
+        ..AppOptions::default()
+    })
+    .await;
+
+    let mut first = connect_forwarded(&server.ephemeral_url(), "198.51.100.1").await;
+    greet(&mut first).await;
+    let mut second = connect_forwarded(&server.ephemeral_url(), "198.51.100.2").await;
+    assert_refused(&mut second, ErrorCode::TooManyConnections).await;
+}
+
+#[tokio::test]
+async fn new_games_are_refused_when_the_server_is_full() {
+    let server = spawn_test_server_with_options(AppOptions {
+        max_games: Some(1),
+        ..AppOptions::default()
+    })
+    .await;
+
+    let mut host = connect_ephemeral(&server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut host).await else {
+        panic!("Expected GameCreated");
+    };
+
+    let mut other = connect_ephemeral(&server).await;
+    other.send(create_game_msg("Carol")).await.unwrap();
+    assert!(matches!(
+        recv(&mut other).await,
+        ServerMessage::Error {
+            code: ErrorCode::ServerFull,
+            ..
+        }
+    ));
+    let mut daily = connect_daily(&server).await;
+    daily.send(start_daily_msg("Dave")).await.unwrap();
+    assert!(matches!(
+        recv(&mut daily).await,
+        ServerMessage::Error {
+            code: ErrorCode::ServerFull,
+            ..
+        }
+    ));
+
+    // Joining the existing game doesn't add one, so it is still allowed
+    let mut guest = connect_ephemeral(&server).await;
+    guest.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(recv(&mut guest).await, ServerMessage::GameStart { .. }));
+}
+
+#[tokio::test]
+async fn abandoned_lobbies_stop_counting_against_capacity() {
+    let server = spawn_test_server_with_options(AppOptions {
+        max_games: Some(1),
+        ..AppOptions::default()
+    })
+    .await;
+
+    let mut host = connect_ephemeral(&server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    assert!(matches!(recv(&mut host).await, ServerMessage::GameCreated { .. }));
+    host.close(None).await.unwrap();
+    drop(host);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let mut other = connect_ephemeral(&server).await;
+    other.send(create_game_msg("Carol")).await.unwrap();
+    assert!(matches!(recv(&mut other).await, ServerMessage::GameCreated { .. }));
+}
+
+#[tokio::test]
+async fn stale_lobbies_stop_counting_against_capacity() {
+    let server = spawn_test_server_with_options(AppOptions {
+        max_games: Some(1),
+        lobby_max_age: Some(std::time::Duration::from_millis(100)),
+        ..AppOptions::default()
+    })
+    .await;
+
+    let mut host = connect_ephemeral(&server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    assert!(matches!(recv(&mut host).await, ServerMessage::GameCreated { .. }));
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let mut other = connect_ephemeral(&server).await;
+    other.send(create_game_msg("Carol")).await.unwrap();
+    assert!(matches!(recv(&mut other).await, ServerMessage::GameCreated { .. }));
+}
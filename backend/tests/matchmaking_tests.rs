@@ -3,7 +3,7 @@ mod common;
 use common::*;
 use futures_util::SinkExt;
 use std::time::Duration;
-use yomitaisen::messages::ServerMessage;
+use yomitaisen::messages::{ErrorCode, ServerMessage};
 
 #[tokio::test]
 async fn player_joins_and_receives_waiting() {
@@ -89,6 +89,43 @@ async fn correct_answer_wins_round() {
     ));
 }
 
+#[tokio::test]
+async fn simultaneous_correct_answers_award_one_point() {
+    let server = spawn_test_server().await;
+
+    let mut ws1 = connect_matchmaking(&server).await;
+    let mut ws2 = connect_matchmaking(&server).await;
+
+    ws1.send(join_msg("user-1")).await.unwrap();
+    ws2.send(join_msg("user-2")).await.unwrap();
+
+    assert!(matches!(recv(&mut ws1).await, ServerMessage::Waiting));
+    assert!(matches!(recv(&mut ws1).await, ServerMessage::GameStart { .. }));
+    let ServerMessage::RoundStart { kanji, .. } = recv(&mut ws1).await else {
+        panic!("Expected RoundStart");
+    };
+    assert!(matches!(recv(&mut ws2).await, ServerMessage::GameStart { .. }));
+    assert!(matches!(recv(&mut ws2).await, ServerMessage::RoundStart { .. }));
+
+    let reading = get_reading(&kanji);
+    let (sent1, sent2) = tokio::join!(ws1.send(answer_msg(reading)), ws2.send(answer_msg(reading)));
+    sent1.unwrap();
+    sent2.unwrap();
+
+    // Exactly one round result; the later answer lands in round 2 or is judged wrong
+    let ServerMessage::RoundResult { winner: Some(winner), .. } = recv(&mut ws1).await else {
+        panic!("Expected RoundResult with a winner");
+    };
+    assert_eq!(
+        recv(&mut ws2).await,
+        ServerMessage::RoundResult {
+            winner: Some(winner),
+            correct_reading: reading.to_string(),
+        }
+    );
+    assert!(matches!(recv(&mut ws1).await, ServerMessage::RoundStart { round: 2, .. }));
+}
+
 #[tokio::test]
 async fn opponent_disconnect_notifies_remaining_player() {
     let server = spawn_test_server().await;
@@ -155,3 +192,35 @@ async fn round_times_out_with_no_winner() {
     assert!(matches!(next1, ServerMessage::RoundStart { round: 2, .. }));
     assert!(matches!(next2, ServerMessage::RoundStart { round: 2, .. }));
 }
+
+#[tokio::test]
+async fn game_action_before_join_is_unauthenticated() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_matchmaking(&server).await;
+
+    ws.send(rematch_msg()).await.unwrap();
+
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::Unauthenticated, .. }
+    ));
+}
+
+#[tokio::test]
+async fn join_with_an_invalid_user_id_is_rejected() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_matchmaking(&server).await;
+
+    for user_id in ["", "<b>alice</b>", " user-1"] {
+        ws.send(join_msg(user_id)).await.unwrap();
+        assert!(matches!(
+            recv(&mut ws).await,
+            ServerMessage::Error { code: ErrorCode::InvalidUserId, .. }
+        ));
+    }
+
+    // Ids are opaque: long ones and ones that would fail the name rules are fine
+    let mut ws = connect_matchmaking(&server).await;
+    ws.send(join_msg("3f2b8c4e-9d1a-4e7b-8c2d-5a6f7e8d9c0b")).await.unwrap();
+    assert_eq!(recv(&mut ws).await, ServerMessage::Waiting);
+}
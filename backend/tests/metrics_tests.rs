@@ -0,0 +1,130 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use yomitaisen::messages::ServerMessage;
+use yomitaisen::{AppOptions, BucketConfig, RateLimitConfig};
+
+async fn scrape(server: &TestServer) -> String {
+    let response = reqwest::get(&server.http_url("/metrics")).await.unwrap();
+    assert!(response.status().is_success());
+    assert!(
+        response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("application/openmetrics-text")
+    );
+    response.text().await.unwrap()
+}
+
+fn assert_metric(text: &str, line: &str) {
+    assert!(
+        text.lines().any(|l| l == line),
+        "Missing `{}` in:\n{}",
+        line,
+        text
+    );
+}
+
+#[tokio::test]
+async fn metrics_report_connections_games_and_rounds() {
+    let server = spawn_test_server_with_options(AppOptions {
+        rate_limits: RateLimitConfig {
+            joins: BucketConfig::new(1.0, 0.0),
+            ..RateLimitConfig::default()
+        },
+        ..AppOptions::default()
+    })
+    .await;
+
+    let mut host = connect_ephemeral(&server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut host).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut host).await, ServerMessage::WaitingForOpponent);
+
+    let text = scrape(&server).await;
+    assert_metric(&text, r#"yomitaisen_connections{handler="ephemeral"} 1"#);
+    assert_metric(&text, "yomitaisen_pending_games 1");
+
+    let mut guest = connect_ephemeral(&server).await;
+    guest.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::GameStart { .. }
+    ));
+    recv(&mut host).await; // OpponentJoined
+    recv(&mut host).await; // GameStart
+
+    for ws in [&mut host, &mut guest] {
+        assert!(matches!(recv(ws).await, ServerMessage::RoundStart { .. }));
+        ws.send(skip_msg()).await.unwrap();
+    }
+    assert_eq!(recv(&mut host).await, ServerMessage::SkipWaiting);
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::RoundResult { .. }
+    ));
+
+    // The guest has used up its one join
+    guest.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::RoundResult { .. }
+    ));
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::RoundStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::Error { .. }
+    ));
+
+    let text = scrape(&server).await;
+    assert_metric(&text, r#"yomitaisen_connections{handler="ephemeral"} 2"#);
+    assert_metric(&text, "yomitaisen_pending_games 0");
+    assert_metric(&text, r#"yomitaisen_active_games{mode="ephemeral"} 1"#);
+    assert_metric(
+        &text,
+        r#"yomitaisen_rounds_total{mode="ephemeral",outcome="skip"} 1"#,
+    );
+    assert_metric(&text, r#"yomitaisen_rate_limited_total{budget="join"} 1"#);
+
+    drop(guest);
+    drop(host);
+    for _ in 0..50 {
+        let text = scrape(&server).await;
+        if text.contains(r#"yomitaisen_connections{handler="ephemeral"} 0"#) {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("Connections were not released");
+}
+
+#[tokio::test]
+async fn matchmaking_wait_is_observed() {
+    let server = spawn_test_server().await;
+
+    let mut ws1 = connect_matchmaking(&server).await;
+    ws1.send(join_msg("user-1")).await.unwrap();
+    assert_eq!(recv(&mut ws1).await, ServerMessage::Waiting);
+    assert_metric(
+        &scrape(&server).await,
+        "yomitaisen_matchmaking_queue_depth 1",
+    );
+
+    let mut ws2 = connect_matchmaking(&server).await;
+    ws2.send(join_msg("user-2")).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws2).await,
+        ServerMessage::GameStart { .. }
+    ));
+
+    let text = scrape(&server).await;
+    assert_metric(&text, "yomitaisen_matchmaking_queue_depth 0");
+    assert_metric(&text, "yomitaisen_matchmaking_wait_seconds_count 1");
+    assert_metric(&text, r#"yomitaisen_active_games{mode="matchmaking"} 1"#);
+}
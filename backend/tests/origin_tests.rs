@@ -0,0 +1,86 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest, http::HeaderValue};
+use yomitaisen::messages::ServerMessage;
+use yomitaisen::{AllowedOrigins, AppOptions};
+
+/// Open a WebSocket as a browser page on `origin` would
+async fn connect_from(url: &str, origin: &str) -> Result<WsStream, tungstenite::Error> {
+    let mut request = url.into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("Origin", HeaderValue::from_str(origin).unwrap());
+    connect_async(request).await.map(|(ws, _)| ws)
+}
+
+fn assert_forbidden(result: Result<WsStream, tungstenite::Error>) {
+    match result {
+        Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), 403),
+        Err(e) => panic!("Expected 403, got {:?}", e),
+        Ok(_) => panic!("Expected 403, connection was accepted"),
+    }
+}
+
+#[tokio::test]
+async fn forged_origin_is_rejected_on_every_websocket() {
+    let server = spawn_test_server().await;
+    for url in [
+        server.ephemeral_url(),
+        server.matchmaking_url(),
+        server.daily_url(),
+        server.ghost_url(),
+        server.replay_url("missing", "speed=1"),
+    ] {
+        assert_forbidden(connect_from(&url, "https://evil.example").await);
+    }
+}
+
+#[tokio::test]
+async fn lookalike_origins_are_rejected() {
+    let server = spawn_test_server().await;
+    for origin in [
+        "https://yomi.alsvik.cloud.evil.example",
+        "http://yomi.alsvik.cloud",
+        "https://evil.example/https://yomi.alsvik.cloud",
+        "null",
+    ] {
+        assert_forbidden(connect_from(&server.ephemeral_url(), origin).await);
+    }
+}
+
+#[tokio::test]
+async fn allowed_origin_can_play() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_from(&server.ephemeral_url(), "https://yomi.alsvik.cloud")
+        .await
+        .unwrap();
+
+    ws.send(create_game_msg("Alice")).await.unwrap();
+    assert!(matches!(recv(&mut ws).await, ServerMessage::GameCreated { .. }));
+}
+
+#[tokio::test]
+async fn configured_origins_replace_the_default() {
+    let server = spawn_test_server_with_options(AppOptions {
+        allowed_origins: AllowedOrigins::parse(&["http://localhost:5173"]).unwrap(),
+        ..AppOptions::default()
+    })
+    .await;
+
+    connect_from(&server.matchmaking_url(), "http://localhost:5173")
+        .await
+        .unwrap();
+    assert_forbidden(connect_from(&server.matchmaking_url(), "https://yomi.alsvik.cloud").await);
+}
+
+#[tokio::test]
+async fn clients_without_an_origin_are_allowed() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(create_game_msg("Alice")).await.unwrap();
+    assert!(matches!(recv(&mut ws).await, ServerMessage::GameCreated { .. }));
+}
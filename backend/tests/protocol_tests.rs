@@ -0,0 +1,60 @@
+mod common;
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use yomitaisen::messages::{Capability, ErrorCode, PROTOCOL_VERSION, ServerMessage};
+
+#[tokio::test]
+async fn hello_is_answered_with_welcome() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_matchmaking(&server).await;
+
+    ws.send(hello_msg(PROTOCOL_VERSION, &["latency", "something_new"]))
+        .await
+        .unwrap();
+
+    let ServerMessage::Welcome {
+        protocol_version,
+        mode,
+        modes,
+        capabilities,
+        ..
+    } = recv(&mut ws).await
+    else {
+        panic!("Expected Welcome");
+    };
+    assert_eq!(protocol_version, PROTOCOL_VERSION);
+    assert_eq!(mode, "matchmaking");
+    assert!(modes.contains(&"ephemeral".to_string()));
+    // Unknown capabilities are dropped rather than rejected
+    assert_eq!(capabilities, vec![Capability::Latency]);
+}
+
+#[tokio::test]
+async fn unsupported_version_gets_error_and_close() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(hello_msg(PROTOCOL_VERSION + 1, &[])).await.unwrap();
+
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error { code: ErrorCode::UnsupportedProtocolVersion, .. }
+    ));
+
+    let next = tokio::time::timeout(Duration::from_secs(2), ws.next())
+        .await
+        .expect("Expected connection to close");
+    assert!(next.is_none_or(|frame| frame.is_ok_and(|msg| msg.is_close())));
+}
+
+#[tokio::test]
+async fn clients_without_hello_still_play() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(create_game_msg("Alice")).await.unwrap();
+
+    assert!(matches!(recv(&mut ws).await, ServerMessage::GameCreated { .. }));
+}
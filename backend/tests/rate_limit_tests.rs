@@ -0,0 +1,144 @@
+mod common;
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use yomitaisen::messages::ServerMessage;
+use yomitaisen::{BucketConfig, RateLimitConfig};
+
+/// Wait for the server to close the connection, skipping any queued messages
+async fn expect_closed(ws: &mut WsStream) {
+    let closed = tokio::time::timeout(Duration::from_secs(2), async {
+        while let Some(Ok(msg)) = ws.next().await {
+            if msg.is_close() {
+                return;
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "Expected server to close the connection");
+}
+
+#[tokio::test]
+async fn answer_spam_is_rate_limited() {
+    let limits = RateLimitConfig {
+        answers: BucketConfig::new(3.0, 0.0),
+        ..RateLimitConfig::default()
+    };
+    let server = spawn_test_server_with_rate_limits(limits).await;
+
+    let mut ws1 = connect_matchmaking(&server).await;
+    let mut ws2 = connect_matchmaking(&server).await;
+
+    ws1.send(join_msg("user-1")).await.unwrap();
+    ws2.send(join_msg("user-2")).await.unwrap();
+
+    assert!(matches!(recv(&mut ws1).await, ServerMessage::Waiting));
+    assert!(matches!(recv(&mut ws1).await, ServerMessage::GameStart { .. }));
+    assert!(matches!(recv(&mut ws1).await, ServerMessage::RoundStart { .. }));
+
+    for _ in 0..4 {
+        ws1.send(answer_msg("まちがい")).await.unwrap();
+    }
+
+    // First three are checked by the game, the fourth is rejected by the
+    // connection (so its error may arrive before the verdicts)
+    let mut wrong = 0;
+    let mut errors = 0;
+    for _ in 0..4 {
+        match recv(&mut ws1).await {
+            ServerMessage::WrongAnswer => wrong += 1,
+            ServerMessage::Error { .. } => errors += 1,
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+    assert_eq!((wrong, errors), (3, 1));
+}
+
+#[tokio::test]
+async fn join_attempts_are_rate_limited() {
+    let limits = RateLimitConfig {
+        joins: BucketConfig::new(2.0, 0.0),
+        ..RateLimitConfig::default()
+    };
+    let server = spawn_test_server_with_rate_limits(limits).await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    for _ in 0..3 {
+        ws.send(join_game_msg("xyz999", "Bob")).await.unwrap();
+    }
+
+    assert_eq!(recv(&mut ws).await, ServerMessage::GameNotFound);
+    assert_eq!(recv(&mut ws).await, ServerMessage::GameNotFound);
+    assert!(matches!(recv(&mut ws).await, ServerMessage::Error { .. }));
+}
+
+#[tokio::test]
+async fn join_budget_is_shared_per_ip() {
+    let limits = RateLimitConfig {
+        ip_joins: BucketConfig::new(1.0, 0.0),
+        ..RateLimitConfig::default()
+    };
+    let server = spawn_test_server_with_rate_limits(limits).await;
+
+    let mut ws1 = connect_ephemeral(&server).await;
+    ws1.send(join_game_msg("xyz999", "Bob")).await.unwrap();
+    assert_eq!(recv(&mut ws1).await, ServerMessage::GameNotFound);
+
+    // A fresh connection from the same address does not get a fresh budget
+    let mut ws2 = connect_ephemeral(&server).await;
+    ws2.send(join_game_msg("xyz999", "Bob")).await.unwrap();
+    assert!(matches!(recv(&mut ws2).await, ServerMessage::Error { .. }));
+}
+
+#[tokio::test]
+async fn cooldown_rejects_other_messages() {
+    let limits = RateLimitConfig {
+        joins: BucketConfig::new(1.0, 0.0),
+        cooldown_base: Duration::from_secs(10),
+        ..RateLimitConfig::default()
+    };
+    let server = spawn_test_server_with_rate_limits(limits).await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(join_game_msg("xyz999", "Bob")).await.unwrap();
+    ws.send(join_game_msg("xyz999", "Bob")).await.unwrap();
+    assert_eq!(recv(&mut ws).await, ServerMessage::GameNotFound);
+    assert!(matches!(recv(&mut ws).await, ServerMessage::Error { .. }));
+
+    // Still cooling down, so even a create is refused
+    ws.send(create_game_msg("Bob")).await.unwrap();
+    assert!(matches!(recv(&mut ws).await, ServerMessage::Error { .. }));
+}
+
+#[tokio::test]
+async fn repeated_parse_failures_disconnect() {
+    let limits = RateLimitConfig {
+        max_invalid_messages: 3,
+        ..RateLimitConfig::default()
+    };
+    let server = spawn_test_server_with_rate_limits(limits).await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    for _ in 0..3 {
+        ws.send(Message::Text("not json".into())).await.unwrap();
+    }
+
+    expect_closed(&mut ws).await;
+}
+
+#[tokio::test]
+async fn repeated_wrong_endpoint_messages_disconnect() {
+    let limits = RateLimitConfig {
+        max_invalid_messages: 2,
+        ..RateLimitConfig::default()
+    };
+    let server = spawn_test_server_with_rate_limits(limits).await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(join_msg("user-1")).await.unwrap();
+    ws.send(join_msg("user-1")).await.unwrap();
+
+    expect_closed(&mut ws).await;
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::*;
+use yomitaisen::{AppOptions, StartupError, WordRepository};
+
+async fn get_ready(server: &TestServer) -> (reqwest::StatusCode, serde_json::Value) {
+    let response = reqwest::get(&server.http_url("/ready")).await.unwrap();
+    let status = response.status();
+    (status, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn ready_reports_each_check() {
+    let server = spawn_test_server().await;
+
+    let (status, body) = get_ready(&server).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["database"]["ok"], true);
+    assert_eq!(body["migrations"]["ok"], true);
+    assert_eq!(
+        body["migrations"]["applied"],
+        body["migrations"]["expected"]
+    );
+    assert_eq!(body["words"]["total"], 10);
+    assert_eq!(
+        body["words"]["bands"][0],
+        serde_json::json!({"band": "common", "count": 10, "ok": true})
+    );
+}
+
+#[tokio::test]
+async fn thin_word_bands_are_not_ready() {
+    let server = spawn_test_server_with_options(AppOptions {
+        min_words_per_band: Some(5),
+        ..AppOptions::default()
+    })
+    .await;
+
+    let (status, body) = get_ready(&server).await;
+    assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["words"]["ok"], false);
+    assert_eq!(body["words"]["bands"][0]["ok"], true);
+    assert_eq!(
+        body["words"]["bands"][1],
+        serde_json::json!({"band": "intermediate", "count": 0, "ok": false})
+    );
+}
+
+#[tokio::test]
+async fn unreachable_database_is_not_ready() {
+    let pool = test_pool().await;
+    let server = spawn_test_server_with_pool(pool.clone(), AppOptions::default()).await;
+    pool.close().await;
+
+    let (status, body) = get_ready(&server).await;
+    assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["database"]["ok"], false);
+    assert!(body["database"]["error"].is_string());
+    assert_eq!(body["migrations"]["applied"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn empty_dictionary_refuses_to_start() {
+    let pool = test_pool().await;
+    sqlx::query("DELETE FROM words")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let words = WordRepository::load(pool.clone()).await.unwrap();
+
+    let result = yomitaisen::build(pool, words, AppOptions::default()).await;
+    assert!(matches!(result, Err(StartupError::EmptyDictionary)));
+}
@@ -0,0 +1,322 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use std::time::Duration;
+use yomitaisen::AppOptions;
+use yomitaisen::messages::{ErrorCode, RoundState, ServerMessage};
+
+/// Connect and opt into resume tokens
+async fn connect_resumable(server: &TestServer) -> WsStream {
+    let mut ws = connect_ephemeral(server).await;
+    ws.send(hello_msg(1, &["resume"])).await.unwrap();
+    assert!(matches!(recv(&mut ws).await, ServerMessage::Welcome { .. }));
+    ws
+}
+
+async fn recv_token(ws: &mut WsStream) -> String {
+    match recv(ws).await {
+        ServerMessage::ResumeToken { token } => token,
+        other => panic!("Expected ResumeToken, got {:?}", other),
+    }
+}
+
+async fn expect_error(ws: &mut WsStream, expected: ErrorCode) {
+    match recv(ws).await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, expected),
+        other => panic!("Expected Error, got {:?}", other),
+    }
+}
+
+struct StartedGame {
+    alice: WsStream,
+    bob: WsStream,
+    bob_token: String,
+    /// Kanji of round 1, which both players are looking at
+    kanji: String,
+}
+
+/// Alice and Bob, both resumable, start a game and see round 1
+async fn start_resumable_game(server: &TestServer) -> StartedGame {
+    let mut alice = connect_resumable(server).await;
+    alice.send(create_game_msg("Alice")).await.unwrap();
+    recv_token(&mut alice).await;
+    let ServerMessage::GameCreated { game_id } = recv(&mut alice).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut alice).await, ServerMessage::WaitingForOpponent);
+
+    let mut bob = connect_resumable(server).await;
+    bob.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    let bob_token = recv_token(&mut bob).await;
+    assert!(matches!(
+        recv(&mut bob).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::OpponentJoined { .. }
+    ));
+    recv_token(&mut alice).await;
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::GameStart { .. }
+    ));
+    let ServerMessage::RoundStart { kanji, .. } = recv(&mut alice).await else {
+        panic!("Expected RoundStart");
+    };
+    assert!(matches!(
+        recv(&mut bob).await,
+        ServerMessage::RoundStart { .. }
+    ));
+
+    StartedGame {
+        alice,
+        bob,
+        bob_token,
+        kanji,
+    }
+}
+
+/// Host and guest in a started game, with round 1 announced
+async fn start_game(server: &TestServer) -> (WsStream, WsStream, String) {
+    let mut host = connect_ephemeral(server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut host).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut host).await, ServerMessage::WaitingForOpponent);
+
+    let mut guest = connect_ephemeral(server).await;
+    guest.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::OpponentJoined { .. }
+    ));
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::GameStart { .. }
+    ));
+    let ServerMessage::RoundStart { kanji, .. } = recv(&mut host).await else {
+        panic!("Expected RoundStart");
+    };
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::RoundStart { .. }
+    ));
+    (host, guest, kanji)
+}
+
+#[tokio::test]
+async fn dropped_player_can_resume_where_they_left_off() {
+    let server = spawn_test_server().await;
+    let StartedGame {
+        mut alice,
+        bob,
+        bob_token,
+        kanji,
+    } = start_resumable_game(&server).await;
+
+    drop(bob);
+    match recv(&mut alice).await {
+        ServerMessage::OpponentConnectionChanged {
+            connected: false,
+            grace_remaining: Some(grace),
+        } => assert!(grace > 0 && grace <= 30),
+        other => panic!("Expected OpponentConnectionChanged, got {:?}", other),
+    }
+
+    // The round is on hold until Bob is back
+    alice.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    expect_error(&mut alice, ErrorCode::GamePaused).await;
+    alice.send(sync_msg()).await.unwrap();
+    match recv(&mut alice).await {
+        ServerMessage::GameState {
+            your_score: 0,
+            opponent_connected: false,
+            round: Some(RoundState { kanji: current, .. }),
+            ..
+        } => assert_eq!(current, kanji),
+        other => panic!("Expected GameState, got {:?}", other),
+    }
+
+    let mut bob = connect_resumable(&server).await;
+    bob.send(resume_msg(&bob_token)).await.unwrap();
+    assert!(matches!(
+        recv(&mut bob).await,
+        ServerMessage::Resumed {
+            your_score: 0,
+            opponent_score: 0,
+            ..
+        }
+    ));
+    match recv(&mut bob).await {
+        ServerMessage::GameState {
+            match_running: true,
+            opponent_connected: true,
+            round: Some(RoundState { kanji: current, .. }),
+            ..
+        } => assert_eq!(current, kanji),
+        other => panic!("Expected GameState, got {:?}", other),
+    }
+    assert_eq!(
+        recv(&mut alice).await,
+        ServerMessage::OpponentConnectionChanged {
+            connected: true,
+            grace_remaining: None,
+        }
+    );
+
+    alice.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    for ws in [&mut alice, &mut bob] {
+        assert!(matches!(recv(ws).await, ServerMessage::RoundResult { .. }));
+    }
+}
+
+#[tokio::test]
+async fn game_ends_when_dropped_player_does_not_return() {
+    let server = spawn_test_server_with_options(AppOptions {
+        reconnect_grace: Some(Duration::from_millis(200)),
+        ..AppOptions::default()
+    })
+    .await;
+    let StartedGame { mut alice, bob, .. } = start_resumable_game(&server).await;
+
+    drop(bob);
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::OpponentConnectionChanged {
+            connected: false,
+            ..
+        }
+    ));
+    assert_eq!(recv(&mut alice).await, ServerMessage::OpponentDisconnected);
+}
+
+#[tokio::test]
+async fn players_without_resume_leave_at_once() {
+    let server = spawn_test_server().await;
+    let (mut host, guest, _) = start_game(&server).await;
+
+    drop(guest);
+    assert_eq!(recv(&mut host).await, ServerMessage::OpponentDisconnected);
+}
+
+#[tokio::test]
+async fn pause_holds_the_round_until_both_unpause() {
+    let server = spawn_test_server().await;
+    let (mut host, mut guest, kanji) = start_game(&server).await;
+
+    host.send(pause_msg()).await.unwrap();
+    for ws in [&mut host, &mut guest] {
+        assert_eq!(
+            recv(ws).await,
+            ServerMessage::GamePaused {
+                player: "Alice".to_string()
+            }
+        );
+    }
+
+    // Answers and skips are refused while paused
+    host.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    expect_error(&mut host, ErrorCode::GamePaused).await;
+    guest.send(skip_msg()).await.unwrap();
+    expect_error(&mut guest, ErrorCode::GamePaused).await;
+    // The error is correlated with the message that caused it
+    guest
+        .send(r#"{"type": "skip", "request_id": "req-3"}"#.into())
+        .await
+        .unwrap();
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::Error { code: ErrorCode::GamePaused, request_id: Some(id), .. } if id == "req-3"
+    ));
+    host.send(sync_msg()).await.unwrap();
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::GameState {
+            paused: true,
+            your_score: 0,
+            ..
+        }
+    ));
+
+    guest.send(unpause_msg()).await.unwrap();
+    for ws in [&mut host, &mut guest] {
+        assert_eq!(
+            recv(ws).await,
+            ServerMessage::UnpauseRequested {
+                player: "Bob".to_string()
+            }
+        );
+    }
+    host.send(unpause_msg()).await.unwrap();
+    for ws in [&mut host, &mut guest] {
+        match recv(ws).await {
+            ServerMessage::GameUnpaused { remaining_ms } => assert!(remaining_ms > 0),
+            other => panic!("Expected GameUnpaused, got {:?}", other),
+        }
+    }
+
+    host.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    for ws in [&mut host, &mut guest] {
+        assert!(matches!(recv(ws).await, ServerMessage::RoundResult { .. }));
+    }
+}
+
+#[tokio::test]
+async fn paused_round_does_not_time_out() {
+    let server = spawn_test_server_with_timeout(Some(Duration::from_millis(300))).await;
+    let (mut host, mut guest, _) = start_game(&server).await;
+
+    host.send(pause_msg()).await.unwrap();
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::GamePaused { .. }
+    ));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    host.send(sync_msg()).await.unwrap();
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::GameState {
+            paused: true,
+            round: Some(RoundState { round: 1, .. }),
+            ..
+        }
+    ));
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::GamePaused { .. }
+    ));
+}
+
+#[tokio::test]
+async fn ranked_games_cannot_be_paused() {
+    let server = spawn_test_server().await;
+    let mut ws1 = connect_matchmaking(&server).await;
+    let mut ws2 = connect_matchmaking(&server).await;
+    ws1.send(join_msg("alice")).await.unwrap();
+    assert_eq!(recv(&mut ws1).await, ServerMessage::Waiting);
+    ws2.send(join_msg("bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws1).await,
+        ServerMessage::GameStart { .. }
+    ));
+
+    ws1.send(pause_msg()).await.unwrap();
+    loop {
+        match recv(&mut ws1).await {
+            ServerMessage::Error { code, .. } => {
+                assert_eq!(code, ErrorCode::PauseNotAllowed);
+                break;
+            }
+            ServerMessage::RoundStart { .. } => continue,
+            other => panic!("Expected Error, got {:?}", other),
+        }
+    }
+}
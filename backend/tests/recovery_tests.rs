@@ -0,0 +1,262 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use std::time::Duration;
+use yomitaisen::AppOptions;
+use yomitaisen::messages::{ErrorCode, ServerMessage};
+
+/// Connect and opt into resume tokens
+async fn connect_resumable(server: &TestServer) -> WsStream {
+    let mut ws = connect_ephemeral(server).await;
+    ws.send(hello_msg(1, &["resume"])).await.unwrap();
+    assert!(matches!(recv(&mut ws).await, ServerMessage::Welcome { .. }));
+    ws
+}
+
+async fn recv_token(ws: &mut WsStream) -> String {
+    match recv(ws).await {
+        ServerMessage::ResumeToken { token } => token,
+        other => panic!("Expected ResumeToken, got {:?}", other),
+    }
+}
+
+async fn expect_resumed(ws: &mut WsStream, game: &str, opponent_name: &str, scores: (u32, u32)) {
+    match recv(ws).await {
+        ServerMessage::Resumed {
+            game_id,
+            opponent,
+            your_score,
+            opponent_score,
+            ..
+        } => {
+            assert_eq!(game_id, game);
+            assert_eq!(opponent, opponent_name);
+            assert_eq!((your_score, opponent_score), scores);
+        }
+        other => panic!("Expected Resumed, got {:?}", other),
+    }
+}
+
+struct StartedGame {
+    game_id: String,
+    alice: WsStream,
+    bob: WsStream,
+    alice_token: String,
+    bob_token: String,
+    /// Kanji of round 2, which both players are looking at
+    kanji: String,
+}
+
+/// Alice and Bob start a game and Alice wins round 1
+async fn play_first_round(server: &TestServer) -> StartedGame {
+    let mut alice = connect_resumable(server).await;
+    alice.send(create_game_msg("Alice")).await.unwrap();
+    recv_token(&mut alice).await;
+    let ServerMessage::GameCreated { game_id } = recv(&mut alice).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut alice).await, ServerMessage::WaitingForOpponent);
+
+    let mut bob = connect_resumable(server).await;
+    bob.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    let bob_token = recv_token(&mut bob).await;
+    assert!(matches!(
+        recv(&mut bob).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::OpponentJoined { .. }
+    ));
+    let alice_token = recv_token(&mut alice).await;
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::GameStart { .. }
+    ));
+
+    let ServerMessage::RoundStart { kanji, .. } = recv(&mut alice).await else {
+        panic!("Expected RoundStart");
+    };
+    recv(&mut bob).await;
+    alice.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    for ws in [&mut alice, &mut bob] {
+        assert!(matches!(recv(ws).await, ServerMessage::RoundResult { .. }));
+    }
+    let ServerMessage::RoundStart {
+        kanji, round: 2, ..
+    } = recv(&mut alice).await
+    else {
+        panic!("Expected RoundStart for round 2");
+    };
+    recv(&mut bob).await;
+
+    StartedGame {
+        game_id,
+        alice,
+        bob,
+        alice_token,
+        bob_token,
+        kanji,
+    }
+}
+
+/// Both players rejoin `game` on `server`; round 2 starts over once both are back
+async fn resume_both(server: &TestServer, game: &StartedGame) -> (WsStream, WsStream) {
+    let mut alice = connect_resumable(server).await;
+    alice.send(resume_msg(&game.alice_token)).await.unwrap();
+    expect_resumed(&mut alice, &game.game_id, "Bob", (1, 0)).await;
+
+    let mut bob = connect_resumable(server).await;
+    bob.send(resume_msg(&game.bob_token)).await.unwrap();
+    expect_resumed(&mut bob, &game.game_id, "Alice", (0, 1)).await;
+
+    for ws in [&mut alice, &mut bob] {
+        match recv(ws).await {
+            ServerMessage::RoundStart { kanji, round, .. } => {
+                assert_eq!(round, 2);
+                assert_eq!(kanji, game.kanji);
+            }
+            other => panic!("Expected RoundStart, got {:?}", other),
+        }
+    }
+    (alice, bob)
+}
+
+#[tokio::test]
+async fn game_survives_a_graceful_restart() {
+    let pool = test_pool().await;
+    let server = spawn_test_server_with_pool(pool.clone(), AppOptions::default()).await;
+    let mut game = play_first_round(&server).await;
+
+    server.recovery.shutdown().await;
+    assert_eq!(recv(&mut game.alice).await, ServerMessage::ServerShutdown);
+    assert_eq!(recv(&mut game.bob).await, ServerMessage::ServerShutdown);
+
+    let restarted = spawn_test_server_with_pool(pool, AppOptions::default()).await;
+    let (mut alice, mut bob) = resume_both(&restarted, &game).await;
+
+    // The game carries on with the saved score
+    bob.send(answer_msg(get_reading(&game.kanji)))
+        .await
+        .unwrap();
+    for ws in [&mut alice, &mut bob] {
+        match recv(ws).await {
+            ServerMessage::RoundResult { winner, .. } => assert_eq!(winner.as_deref(), Some("Bob")),
+            other => panic!("Expected RoundResult, got {:?}", other),
+        }
+    }
+
+    // A token only works while its player is away
+    let mut again = connect_resumable(&restarted).await;
+    again.send(resume_msg(&game.alice_token)).await.unwrap();
+    assert!(matches!(
+        recv(&mut again).await,
+        ServerMessage::Error {
+            code: ErrorCode::InvalidResumeToken,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn game_survives_a_crash_after_a_periodic_save() {
+    let pool = test_pool().await;
+    let options = AppOptions {
+        snapshot_interval: Some(Duration::from_millis(50)),
+        ..AppOptions::default()
+    };
+    let server = spawn_test_server_with_pool(pool.clone(), options).await;
+    let game = play_first_round(&server).await;
+
+    // No shutdown: the next server picks up the last periodic save
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let restarted = spawn_test_server_with_pool(pool, AppOptions::default()).await;
+    resume_both(&restarted, &game).await;
+}
+
+#[tokio::test]
+async fn host_reclaims_a_pending_game_after_restart() {
+    let pool = test_pool().await;
+    let server = spawn_test_server_with_pool(pool.clone(), AppOptions::default()).await;
+
+    let mut alice = connect_resumable(&server).await;
+    alice.send(create_game_msg("Alice")).await.unwrap();
+    let token = recv_token(&mut alice).await;
+    let ServerMessage::GameCreated { game_id } = recv(&mut alice).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut alice).await, ServerMessage::WaitingForOpponent);
+
+    server.recovery.shutdown().await;
+    assert_eq!(recv(&mut alice).await, ServerMessage::ServerShutdown);
+
+    let restarted = spawn_test_server_with_pool(pool, AppOptions::default()).await;
+    let mut alice = connect_resumable(&restarted).await;
+    alice.send(resume_msg(&token)).await.unwrap();
+    assert_eq!(
+        recv(&mut alice).await,
+        ServerMessage::GameCreated {
+            game_id: game_id.clone()
+        }
+    );
+    assert_eq!(recv(&mut alice).await, ServerMessage::WaitingForOpponent);
+
+    let mut carol = connect_ephemeral(&restarted).await;
+    carol.send(join_game_msg(&game_id, "Carol")).await.unwrap();
+    assert!(matches!(
+        recv(&mut carol).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::OpponentJoined { opponent_name } if opponent_name == "Carol"
+    ));
+}
+
+#[tokio::test]
+async fn pending_games_past_the_lobby_age_are_not_restored() {
+    let pool = test_pool().await;
+    let server = spawn_test_server_with_pool(pool.clone(), AppOptions::default()).await;
+
+    let mut alice = connect_resumable(&server).await;
+    alice.send(create_game_msg("Alice")).await.unwrap();
+    let token = recv_token(&mut alice).await;
+    assert!(matches!(recv(&mut alice).await, ServerMessage::GameCreated { .. }));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    server.recovery.shutdown().await;
+
+    // Saved 300ms old, which is already too old for the restarted server
+    let restarted = spawn_test_server_with_pool(
+        pool,
+        AppOptions {
+            lobby_max_age: Some(Duration::from_millis(200)),
+            ..AppOptions::default()
+        },
+    )
+    .await;
+    let mut alice = connect_resumable(&restarted).await;
+    alice.send(resume_msg(&token)).await.unwrap();
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::Error {
+            code: ErrorCode::InvalidResumeToken,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn unknown_resume_token_is_rejected() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_resumable(&server).await;
+
+    ws.send(resume_msg("not-a-token")).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error {
+            code: ErrorCode::InvalidResumeToken,
+            ..
+        }
+    ));
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use common::*;
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::{Error, Message};
+use yomitaisen::AppOptions;
+use yomitaisen::messages::{ErrorCode, ServerMessage};
+
+/// Start an ephemeral game between Alice and Bob and return its code and both sockets
+async fn start_game(server: &TestServer) -> (String, WsStream, WsStream) {
+    let mut host = connect_ephemeral(server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut host).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut host).await, ServerMessage::WaitingForOpponent);
+
+    let mut guest = connect_ephemeral(server).await;
+    guest.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::OpponentJoined { .. }
+    ));
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::GameStart { .. }
+    ));
+    (game_id, host, guest)
+}
+
+/// The log is saved in the background, so poll until it shows up
+async fn find_replay(server: &TestServer, game_id: &str) -> String {
+    let url = server.http_url(&format!("/replays?game_id={}", game_id));
+    for _ in 0..50 {
+        let body: serde_json::Value = reqwest::get(&url).await.unwrap().json().await.unwrap();
+        if let Some(replay) = body["replays"].as_array().and_then(|r| r.first()) {
+            assert_eq!(replay["player1"], "Alice");
+            assert_eq!(replay["player2"], "Bob");
+            return replay["replay_id"].as_str().unwrap().to_string();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("No replay recorded for {}", game_id);
+}
+
+/// Every message of a replay, until the server closes it
+async fn watch(server: &TestServer, replay_id: &str, query: &str) -> Vec<ServerMessage> {
+    let (mut ws, _) = connect_async(&server.replay_url(replay_id, query))
+        .await
+        .expect("Failed to connect");
+    let mut messages = Vec::new();
+    while let Some(msg) = ws.next().await {
+        match msg.unwrap() {
+            Message::Text(text) => messages.push(serde_json::from_str(&text).unwrap()),
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+    messages
+}
+
+#[tokio::test]
+async fn replay_streams_the_game_from_each_players_view() {
+    let server = spawn_test_server().await;
+    let (game_id, mut alice, mut bob) = start_game(&server).await;
+
+    let ServerMessage::RoundStart { kanji, .. } = recv(&mut alice).await else {
+        panic!("Expected RoundStart");
+    };
+    recv(&mut bob).await;
+
+    bob.send(answer_msg("まちがい")).await.unwrap();
+    assert_eq!(recv(&mut bob).await, ServerMessage::WrongAnswer);
+    alice.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    assert!(matches!(
+        recv(&mut alice).await,
+        ServerMessage::RoundResult { .. }
+    ));
+    let ServerMessage::RoundStart {
+        kanji: next_kanji, ..
+    } = recv(&mut alice).await
+    else {
+        panic!("Expected RoundStart");
+    };
+    bob.close(None).await.unwrap();
+    assert_eq!(recv(&mut alice).await, ServerMessage::OpponentDisconnected);
+
+    let replay_id = find_replay(&server, &game_id).await;
+    let round_result = ServerMessage::RoundResult {
+        winner: Some("Alice".to_string()),
+        correct_reading: get_reading(&kanji).to_string(),
+    };
+
+    let alice_view = watch(&server, &replay_id, "speed=100").await;
+    assert_eq!(alice_view.len(), 5, "{:?}", alice_view);
+    assert!(matches!(
+        &alice_view[0],
+        ServerMessage::GameStart { opponent, .. } if opponent == "Bob"
+    ));
+    assert!(
+        matches!(&alice_view[1], ServerMessage::RoundStart { kanji: k, round: 1, .. } if *k == kanji)
+    );
+    assert_eq!(alice_view[2], round_result);
+    assert!(
+        matches!(&alice_view[3], ServerMessage::RoundStart { kanji: k, round: 2, .. } if *k == next_kanji)
+    );
+    assert_eq!(alice_view[4], ServerMessage::OpponentDisconnected);
+
+    // Bob saw his own wrong answer and nothing after he left
+    let bob_view = watch(&server, &replay_id, "speed=100&player=Bob").await;
+    assert_eq!(bob_view.len(), 5, "{:?}", bob_view);
+    assert!(matches!(
+        &bob_view[0],
+        ServerMessage::GameStart { opponent, .. } if opponent == "Alice"
+    ));
+    assert_eq!(bob_view[2], ServerMessage::WrongAnswer);
+    assert_eq!(bob_view[3], round_result);
+}
+
+#[tokio::test]
+async fn replay_rejects_bad_requests() {
+    let server = spawn_test_server().await;
+    let (game_id, mut alice, mut bob) = start_game(&server).await;
+    recv(&mut alice).await;
+    recv(&mut bob).await;
+    bob.close(None).await.unwrap();
+    assert_eq!(recv(&mut alice).await, ServerMessage::OpponentDisconnected);
+    let replay_id = find_replay(&server, &game_id).await;
+
+    for (replay_id, query, status) in [
+        (replay_id.as_str(), "speed=0", 400),
+        (replay_id.as_str(), "speed=1000", 400),
+        (replay_id.as_str(), "player=Carol", 400),
+        ("no-such-replay", "", 404),
+    ] {
+        match connect_async(&server.replay_url(replay_id, query)).await {
+            Err(Error::Http(response)) => assert_eq!(response.status(), status, "{query}"),
+            other => panic!("Expected HTTP {}, got {:?}", status, other.map(|_| ())),
+        }
+    }
+}
+
+#[tokio::test]
+async fn replays_count_against_the_connection_cap() {
+    let server = spawn_test_server_with_options(AppOptions {
+        max_connections_per_ip: Some(2),
+        ..AppOptions::default()
+    })
+    .await;
+    let (game_id, mut alice, mut bob) = start_game(&server).await;
+    recv(&mut alice).await;
+    recv(&mut bob).await;
+    bob.close(None).await.unwrap();
+    assert_eq!(recv(&mut alice).await, ServerMessage::OpponentDisconnected);
+    let replay_id = find_replay(&server, &game_id).await;
+
+    // Another connection takes Bob's slot once the server has noticed he left
+    let mut other = None;
+    for _ in 0..50 {
+        let mut ws = connect_ephemeral(&server).await;
+        ws.send(hello_msg(1, &[])).await.unwrap();
+        if matches!(recv(&mut ws).await, ServerMessage::Welcome { .. }) {
+            other = Some(ws);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let _other = other.expect("Bob's slot was never freed");
+
+    let (mut viewer, _) = connect_async(&server.replay_url(&replay_id, "speed=100"))
+        .await
+        .expect("Failed to connect");
+    match recv(&mut viewer).await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::TooManyConnections),
+        other => panic!("Expected Error, got {:?}", other),
+    }
+}
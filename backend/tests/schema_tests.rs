@@ -0,0 +1,52 @@
+use yomitaisen::messages::{ClientEnvelope, ServerMessage};
+use yomitaisen::protocol;
+
+fn committed(file: &str) -> String {
+    let path = protocol::output_dir().join(file);
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e))
+}
+
+#[test]
+fn committed_json_schema_is_up_to_date() {
+    assert!(
+        committed(protocol::JSON_SCHEMA_FILE) == protocol::json_schema_string(),
+        "protocol/{} is out of date; run `cargo run --bin protocol_schema`",
+        protocol::JSON_SCHEMA_FILE
+    );
+}
+
+#[test]
+fn committed_typescript_is_up_to_date() {
+    assert!(
+        committed(protocol::TYPESCRIPT_FILE) == protocol::typescript(),
+        "protocol/{} is out of date; run `cargo run --bin protocol_schema`",
+        protocol::TYPESCRIPT_FILE
+    );
+}
+
+#[test]
+fn schema_describes_every_message_type() {
+    let schema = protocol::json_schema();
+    let defs = &schema["$defs"];
+
+    for name in [
+        "ClientEnvelope",
+        "ServerMessage",
+        "ErrorCode",
+        "LobbyList",
+        "DailyLeaderboard",
+    ] {
+        assert!(defs.get(name).is_some(), "Missing definition for {}", name);
+    }
+
+    // Each variant shows up as a constant `type` tag
+    let server = defs["ServerMessage"].to_string();
+    let sample = serde_json::to_value(ServerMessage::GameFull).unwrap();
+    assert!(server.contains(&format!("\"const\":{}", sample["type"])));
+
+    let client: ClientEnvelope =
+        serde_json::from_str(r#"{"type": "skip", "request_id": "r1"}"#).unwrap();
+    assert_eq!(client.request_id.as_deref(), Some("r1"));
+    assert!(defs["ClientEnvelope"].to_string().contains("\"request_id\""));
+}
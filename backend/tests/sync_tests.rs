@@ -0,0 +1,162 @@
+mod common;
+
+use common::*;
+use futures_util::SinkExt;
+use yomitaisen::messages::{ErrorCode, RoundState, ServerMessage};
+
+/// Host and guest in a started ephemeral game, with round 1 announced
+async fn start_game(server: &TestServer) -> (WsStream, WsStream, String) {
+    let mut host = connect_ephemeral(server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut host).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut host).await, ServerMessage::WaitingForOpponent);
+
+    let mut guest = connect_ephemeral(server).await;
+    guest.send(join_game_msg(&game_id, "Bob")).await.unwrap();
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::OpponentJoined { .. }
+    ));
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::RoundStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::GameStart { .. }
+    ));
+    assert!(matches!(
+        recv(&mut guest).await,
+        ServerMessage::RoundStart { .. }
+    ));
+    (host, guest, game_id)
+}
+
+#[tokio::test]
+async fn sync_reports_scores_round_and_votes() {
+    let server = spawn_test_server().await;
+    let (mut host, mut guest, game_id) = start_game(&server).await;
+
+    // Alice wins round 1, then skips round 2
+    host.send(sync_msg()).await.unwrap();
+    let ServerMessage::GameState {
+        round: Some(RoundState { kanji, .. }),
+        ..
+    } = recv(&mut host).await
+    else {
+        panic!("Expected GameState with a round");
+    };
+    host.send(answer_msg(get_reading(&kanji))).await.unwrap();
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::RoundResult { .. }
+    ));
+    assert!(matches!(
+        recv(&mut host).await,
+        ServerMessage::RoundStart { round: 2, .. }
+    ));
+    host.send(skip_msg()).await.unwrap();
+    assert_eq!(recv(&mut host).await, ServerMessage::SkipWaiting);
+
+    guest.send(sync_msg()).await.unwrap();
+    let state = loop {
+        match recv(&mut guest).await {
+            state @ ServerMessage::GameState { .. } => break state,
+            _ => continue,
+        }
+    };
+    let ServerMessage::GameState {
+        game_id: synced_id,
+        opponent,
+        your_score,
+        opponent_score,
+        match_running,
+        round: Some(round),
+        paused,
+        you_want_rematch,
+        opponent_wants_rematch,
+        you_offered_draw,
+        opponent_offered_draw,
+        opponent_connected,
+    } = state
+    else {
+        panic!("Expected GameState with a round, got {:?}", state);
+    };
+    assert_eq!(synced_id, game_id);
+    assert_eq!(opponent, "Alice");
+    assert_eq!((your_score, opponent_score), (0, 1));
+    assert!(match_running && !paused);
+    assert_eq!(round.round, 2);
+    assert!(!round.readings.is_empty());
+    assert!(round.remaining_ms > 0 && round.remaining_ms <= 30_000);
+    assert!(!round.you_skipped && round.opponent_skipped);
+    assert!(!you_want_rematch && !opponent_wants_rematch);
+    assert!(!you_offered_draw && !opponent_offered_draw);
+    assert!(opponent_connected);
+}
+
+#[tokio::test]
+async fn host_waiting_for_an_opponent_is_reminded_of_the_game() {
+    let server = spawn_test_server().await;
+    let mut host = connect_ephemeral(&server).await;
+    host.send(create_game_msg("Alice")).await.unwrap();
+    let ServerMessage::GameCreated { game_id } = recv(&mut host).await else {
+        panic!("Expected GameCreated");
+    };
+    assert_eq!(recv(&mut host).await, ServerMessage::WaitingForOpponent);
+
+    host.send(sync_msg()).await.unwrap();
+    assert_eq!(
+        recv(&mut host).await,
+        ServerMessage::GameCreated { game_id }
+    );
+    assert_eq!(recv(&mut host).await, ServerMessage::WaitingForOpponent);
+}
+
+#[tokio::test]
+async fn sync_outside_a_game_is_an_error() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_ephemeral(&server).await;
+
+    ws.send(sync_msg()).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::Error {
+            code: ErrorCode::NotInGame,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn matchmaking_player_in_the_queue_is_waiting() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_matchmaking(&server).await;
+    ws.send(join_msg("alice")).await.unwrap();
+    assert_eq!(recv(&mut ws).await, ServerMessage::Waiting);
+
+    ws.send(sync_msg()).await.unwrap();
+    assert_eq!(recv(&mut ws).await, ServerMessage::Waiting);
+}
+
+#[tokio::test]
+async fn solo_run_sends_the_current_round_again() {
+    let server = spawn_test_server().await;
+    let mut ws = connect_daily(&server).await;
+    ws.send(start_daily_msg("Alice")).await.unwrap();
+    assert!(matches!(
+        recv(&mut ws).await,
+        ServerMessage::DailyStart { .. }
+    ));
+    let first = recv(&mut ws).await;
+    assert!(matches!(first, ServerMessage::RoundStart { round: 1, .. }));
+
+    ws.send(sync_msg()).await.unwrap();
+    assert_eq!(recv(&mut ws).await, first);
+}